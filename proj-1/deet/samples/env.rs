@@ -0,0 +1,6 @@
+fn main() {
+    match std::env::var("MY_VAR") {
+        Ok(val) => println!("MY_VAR={}", val),
+        Err(_) => println!("MY_VAR is not set"),
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+struct Counter<T> {
+    counts: HashMap<T, usize>,
+}
+
+impl<T: std::hash::Hash + Eq + Debug> Counter<T> {
+    fn new() -> Counter<T> {
+        Counter { counts: HashMap::new() }
+    }
+
+    fn add(&mut self, item: T) {
+        *self.counts.entry(item).or_insert(0) += 1;
+    }
+}
+
+fn largest<T: PartialOrd + Copy>(items: &[T]) -> T {
+    let mut largest = items[0];
+    for &item in items {
+        if item > largest {
+            largest = item;
+        }
+    }
+    largest
+}
+
+fn main() {
+    let mut counter = Counter::new();
+    for word in vec!["a", "b", "a"] {
+        counter.add(word);
+    }
+    println!("{}", largest(&[3, 7, 2]));
+    println!("{:?}", counter.counts.get("a"));
+}
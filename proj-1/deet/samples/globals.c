@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+int first = 0xdead;
+int second = -2;
+long third = 42;
+
+int main() {
+    first = 7;
+    second *= 10;
+    third += first;
+    printf("%d %d %ld\n", first, second, third);
+    return 0;
+}
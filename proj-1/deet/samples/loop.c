@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+int main() {
+    int total = 0;
+    for (int i = 0; i < 10; i++) {
+        total += i;
+        printf("i = %d, total = %d\n", i, total);
+    }
+    return 0;
+}
@@ -0,0 +1,11 @@
+fn divide(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        panic!("tried to divide {} by zero", a);
+    }
+    a / b
+}
+
+fn main() {
+    println!("{}", divide(10, 2));
+    println!("{}", divide(7, 0));
+}
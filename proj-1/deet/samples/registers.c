@@ -0,0 +1,7 @@
+#include <stdio.h>
+
+int main() {
+    asm volatile("mov $0x1234, %%rbx\n\tmov $-42, %%r12" ::: "rbx", "r12");
+    printf("registers set\n");
+    return 0;
+}
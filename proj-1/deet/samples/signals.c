@@ -0,0 +1,18 @@
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+static void handle_usr1(int sig) {
+    const char *msg = "Got SIGUSR1\n";
+    (void)sig;
+    write(STDOUT_FILENO, msg, strlen(msg));
+}
+
+int main() {
+    signal(SIGUSR1, handle_usr1);
+    for (int i = 0; i < 3; i++) {
+        printf("Tick %d\n", i);
+    }
+    return 0;
+}
@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+long result;
+
+int main() {
+    // Stores rax straight after loading it, so changing $rax in between changes what gets stored
+    __asm__ volatile("mov $1, %%rax\n\tmov %%rax, result(%%rip)" ::: "rax", "memory");
+    printf("result = %ld\n", result);
+    return 0;
+}
@@ -0,0 +1,19 @@
+#include <pthread.h>
+#include <stdio.h>
+
+static int counter = 0;
+
+void *worker(void *arg) {
+    int amount = *(int *)arg;
+    counter += amount;
+    return NULL;
+}
+
+int main() {
+    pthread_t thread;
+    int amount = 5;
+    pthread_create(&thread, NULL, worker, &amount);
+    pthread_join(thread, NULL);
+    printf("counter = %d\n", counter);
+    return 0;
+}
@@ -0,0 +1,123 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// The commands offered when completing the first word of a line.
+const COMMANDS: &[&str] = &[
+    "attach",
+    "backtrace",
+    "break",
+    "catch",
+    "commands",
+    "continue",
+    "delete",
+    "disable",
+    "disassemble",
+    "down",
+    "enable",
+    "finish",
+    "frame",
+    "hbreak",
+    "info",
+    "list",
+    "next",
+    "print",
+    "quit",
+    "regs",
+    "run",
+    "set",
+    "signal",
+    "source",
+    "step",
+    "stepi",
+    "tbreak",
+    "thread",
+    "unset",
+    "up",
+    "watch",
+];
+
+/// Tab completion for the deet prompt: command names, and function names after break/tbreak.
+pub struct DebuggerCompleter {
+    functions: Vec<String>,
+}
+
+impl DebuggerCompleter {
+    pub fn new(functions: Vec<String>) -> DebuggerCompleter {
+        DebuggerCompleter { functions }
+    }
+
+    /// Returns where the word being completed starts, and what it could be completed to.
+    pub fn completions(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let earlier: Vec<&str> = line[..start].split_whitespace().collect();
+        let options: Vec<&str> = match earlier.as_slice() {
+            [] => COMMANDS.to_vec(),
+            ["b"] | ["break"] | ["tb"] | ["tbreak"] | ["hb"] | ["hbreak"] => {
+                self.functions.iter().map(String::as_str).collect()
+            }
+            _ => vec![],
+        };
+        let matches = options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .map(String::from)
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for DebuggerCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completions(line, pos))
+    }
+}
+
+impl Hinter for DebuggerCompleter {}
+
+impl Highlighter for DebuggerCompleter {}
+
+impl Validator for DebuggerCompleter {}
+
+impl Helper for DebuggerCompleter {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn completer() -> DebuggerCompleter {
+        DebuggerCompleter::new(vec!["main".to_string(), "make_thing".to_string(), "other".to_string()])
+    }
+
+    #[test]
+    fn test_command_completion() {
+        assert_eq!(completer().completions("br", 2), (0, vec!["break".to_string()]));
+        assert_eq!(
+            completer().completions("b", 1),
+            (0, vec!["backtrace".to_string(), "break".to_string()])
+        );
+        // Only the text before the cursor counts
+        assert_eq!(completer().completions("stepi", 4), (0, vec!["step".to_string(), "stepi".to_string()]));
+    }
+
+    #[test]
+    fn test_function_completion() {
+        let expected = (6, vec!["main".to_string(), "make_thing".to_string()]);
+        assert_eq!(completer().completions("break ma", 8), expected);
+        assert_eq!(completer().completions("tbreak ma", 9), (7, expected.1.clone()));
+        assert_eq!(completer().completions("b  oth", 6), (3, vec!["other".to_string()]));
+        // Other commands don't take function names
+        assert_eq!(completer().completions("print ma", 8), (6, vec![]));
+    }
+}
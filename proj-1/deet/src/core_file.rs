@@ -0,0 +1,169 @@
+use nix::sys::signal::Signal;
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+
+/// ELF constants we need for reading x86-64 core dumps (see elf(5) and <linux/elfcore.h>).
+const ET_CORE: u16 = 4;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+/// Offsets into struct elf_prstatus, the payload of an NT_PRSTATUS note
+const PRSTATUS_CURSIG: usize = 12;
+const PRSTATUS_PID: usize = 32;
+const PRSTATUS_REGS: usize = 112;
+
+/// A piece of the crashed process's memory that was saved in the core file.
+struct Segment {
+    vaddr: u64,
+    /// Where the segment's contents start in the core file
+    offset: usize,
+    /// How much of the segment was saved. Segments the kernel considered uninteresting (such as
+    /// code, which can be found in the executable instead) are left out entirely.
+    size: u64,
+}
+
+/// A core dump: the registers and memory of a process at the moment it crashed.
+pub struct CoreFile {
+    data: memmap::Mmap,
+    segments: Vec<Segment>,
+    regs: libc::user_regs_struct,
+    pid: i32,
+    signal: Option<Signal>,
+}
+
+impl CoreFile {
+    /// Loads a core file. Only the first thread's registers are read, which is the thread that
+    /// crashed.
+    pub fn open(path: &str) -> Result<CoreFile, String> {
+        let file = fs::File::open(path).map_err(|err| err.to_string())?;
+        let data = unsafe { memmap::Mmap::map(&file).map_err(|err| err.to_string())? };
+        if data.len() < 64 || &data[..4] != b"\x7fELF" || data[4] != 2 {
+            return Err("not a 64-bit ELF file".to_string());
+        }
+        if read_u16(&data, 16) != Some(ET_CORE) {
+            return Err("not a core file".to_string());
+        }
+        let phoff = read_u64(&data, 32).unwrap() as usize;
+        let phentsize = read_u16(&data, 54).unwrap() as usize;
+        let phnum = read_u16(&data, 56).unwrap() as usize;
+
+        let mut segments = Vec::new();
+        let mut prstatus = None;
+        for i in 0..phnum {
+            let header = phoff + i * phentsize;
+            let truncated = || "truncated program header".to_string();
+            let p_type = read_u32(&data, header).ok_or_else(truncated)?;
+            let offset = read_u64(&data, header + 8).ok_or_else(truncated)? as usize;
+            let vaddr = read_u64(&data, header + 16).ok_or_else(truncated)?;
+            let size = read_u64(&data, header + 32).ok_or_else(truncated)?;
+            match p_type {
+                PT_LOAD => segments.push(Segment { vaddr, offset, size }),
+                PT_NOTE if prstatus.is_none() => {
+                    prstatus = find_note(&data, offset, size as usize, NT_PRSTATUS);
+                }
+                _ => {}
+            }
+        }
+
+        let (desc, desc_len) = prstatus.ok_or_else(|| "no NT_PRSTATUS note".to_string())?;
+        let regs_end = desc + PRSTATUS_REGS + 27 * 8;
+        if desc_len < PRSTATUS_REGS + 27 * 8 || regs_end > data.len() {
+            return Err("NT_PRSTATUS note is too short".to_string());
+        }
+        let reg = |i: usize| read_u64(&data, desc + PRSTATUS_REGS + i * 8).unwrap();
+        // pr_reg is laid out like struct user_regs_struct
+        let regs = libc::user_regs_struct {
+            r15: reg(0),
+            r14: reg(1),
+            r13: reg(2),
+            r12: reg(3),
+            rbp: reg(4),
+            rbx: reg(5),
+            r11: reg(6),
+            r10: reg(7),
+            r9: reg(8),
+            r8: reg(9),
+            rax: reg(10),
+            rcx: reg(11),
+            rdx: reg(12),
+            rsi: reg(13),
+            rdi: reg(14),
+            orig_rax: reg(15),
+            rip: reg(16),
+            cs: reg(17),
+            eflags: reg(18),
+            rsp: reg(19),
+            ss: reg(20),
+            fs_base: reg(21),
+            gs_base: reg(22),
+            ds: reg(23),
+            es: reg(24),
+            fs: reg(25),
+            gs: reg(26),
+        };
+        let cursig = read_u16(&data, desc + PRSTATUS_CURSIG).unwrap() as i32;
+        let pid = read_u32(&data, desc + PRSTATUS_PID).unwrap() as i32;
+        Ok(CoreFile {
+            data,
+            segments,
+            regs,
+            pid,
+            signal: Signal::try_from(cursig).ok(),
+        })
+    }
+
+    /// The registers of the crashed thread.
+    pub fn regs(&self) -> libc::user_regs_struct {
+        self.regs
+    }
+
+    /// The pid the crashed process had.
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// The signal that killed the process, if any.
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+
+    /// Reads a word of the process's memory, or returns None if that memory wasn't saved.
+    pub fn read_word(&self, addr: u64) -> Option<u64> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|seg| addr >= seg.vaddr && addr + 8 <= seg.vaddr + seg.size)?;
+        read_u64(&self.data, segment.offset + (addr - segment.vaddr) as usize)
+    }
+}
+
+/// Looks through the notes in data[offset..offset + size] for one of the given type, returning
+/// the offset and length of its payload.
+fn find_note(data: &[u8], offset: usize, size: usize, note_type: u32) -> Option<(usize, usize)> {
+    let align4 = |n: usize| (n + 3) & !3;
+    let end = offset + size;
+    let mut pos = offset;
+    while pos + 12 <= end {
+        let name_len = read_u32(data, pos)? as usize;
+        let desc_len = read_u32(data, pos + 4)? as usize;
+        let this_type = read_u32(data, pos + 8)?;
+        let desc = pos + 12 + align4(name_len);
+        if this_type == note_type {
+            return Some((desc, desc_len));
+        }
+        pos = desc + align4(desc_len);
+    }
+    None
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
@@ -1,19 +1,75 @@
 use libc::{exit, stat};
 use nix::Error;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use nix::unistd::ForkResult::Child;
-use crate::debugger_command::DebuggerCommand;
-use crate::inferior::{Inferior, Status};
+use crate::completer::DebuggerCompleter;
+use crate::debugger_command::{parse_value, DebuggerCommand};
+use crate::inferior::{self, Frame, Inferior, Status};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
+use crate::dwarf_data::{DwarfData, Error as DwarfError, Line, Type, Variable};
+use iced_x86::{Decoder, DecoderOptions, Formatter, GasFormatter, Instruction};
+use std::collections::{HashMap, VecDeque};
+use std::convert::{TryFrom, TryInto};
+
+/// A breakpoint set by the user. Breakpoints are inserted into every inferior that gets run.
+pub struct Breakpoint {
+    /// The number the user refers to the breakpoint by. Numbers aren't reused after a
+    /// breakpoint is deleted.
+    pub number: usize,
+    pub addr: u64,
+    /// An expression such as "x == 5". If present, the breakpoint only stops the inferior when
+    /// the expression holds.
+    pub condition: Option<String>,
+    /// How many times the inferior has stopped here
+    pub hits: usize,
+    /// Disabled breakpoints are kept around, but not inserted into the inferior
+    pub enabled: bool,
+    /// Whether to delete the breakpoint once it has stopped the inferior
+    pub one_shot: bool,
+    /// Whether the breakpoint uses a debug register instead of an int3
+    pub hardware: bool,
+    /// Whether the breakpoint was set by catch panic, so that hitting it means a panic
+    pub catch_panic: bool,
+    /// Commands to run whenever the breakpoint stops the inferior
+    pub commands: Vec<DebuggerCommand>,
+}
+
+/// Functions that every Rust panic goes through, in order of preference. rust_begin_unwind is the
+/// panic handler, which is passed the location of the panic.
+const PANIC_FUNCTIONS: [&str; 3] = ["rust_begin_unwind", "rust_panic", "core::panicking::panic"];
+
+/// A watchpoint set by the user. Unlike breakpoints, watchpoints belong to the inferior they were
+/// set in, since the variable being watched may live somewhere else in the next one.
+struct Watchpoint {
+    var_name: String,
+    entity_type: Type,
+    addr: u64,
+    /// The debug register the inferior keeps the watchpoint in
+    slot: usize,
+    /// The variable's value when we last looked
+    value: i64,
+}
 
 pub struct Debugger {
     target: String,
     history_path: String,
-    readline: Editor<()>,
+    readline: Editor<DebuggerCompleter>,
     inferior: Option<Inferior>,
     dwarf_data: DwarfData,
-    breakpoints: Vec<u64>,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_number: usize,
+    watchpoints: Vec<Watchpoint>,
+    /// Lines of the source files that have been listed, by path
+    source_files: HashMap<String, Vec<String>>,
+    /// The stack frame that variable and register lookups apply to, 0 being the innermost
+    selected_frame: usize,
+    /// Commands from breakpoints that were hit, waiting to be run
+    queued_commands: VecDeque<DebuggerCommand>,
+    /// Changes to the environment the inferior is run with (None meaning the variable is unset)
+    env: HashMap<String, Option<String>>,
 }
 
 impl Debugger {
@@ -32,7 +88,8 @@ impl Debugger {
         };
         debug_data.print();
         let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
-        let mut readline = Editor::<()>::new();
+        let mut readline = Editor::<DebuggerCompleter>::new();
+        readline.set_helper(Some(DebuggerCompleter::new(debug_data.function_names())));
         // Attempt to load history from ~/.deet_history if it exists
         let _ = readline.load_history(&history_path);
 
@@ -43,81 +100,1168 @@ impl Debugger {
             inferior: None,
             dwarf_data: debug_data,
             breakpoints: vec![],
+            next_breakpoint_number: 0,
+            watchpoints: vec![],
+            source_files: HashMap::new(),
+            selected_frame: 0,
+            queued_commands: VecDeque::new(),
+            env: HashMap::new(),
         }
     }
 
+    /// Sets whether function names are shown demangled (the default) or as raw symbols.
+    pub fn set_demangle(&mut self, demangle: bool) {
+        self.dwarf_data.set_demangle(demangle);
+    }
+
     pub fn run(&mut self) {
         loop {
-            match self.get_next_command() {
-                DebuggerCommand::Run(args) => {
-                    if self.inferior.is_some() {
-                        let _ = self.inferior.take().unwrap().kill();
-                    }
-                    if let Some(mut inferior) = Inferior::new(&self.target, &args, &self.breakpoints) {
-                        let _ = inferior.cont();
-                        let result = inferior.wait(None);
-                        self.print_status(result);
-                        self.inferior = Some(inferior);
-                    } else {
-                        println!("Error starting subprocess");
+            let cmd = self.get_next_command();
+            if !self.execute_all(cmd) {
+                return;
+            }
+        }
+    }
+
+    /// Runs the commands in a file as if they had been typed in. Blank lines and lines starting
+    /// with # are skipped. Returns false if the script quit the debugger.
+    pub fn source(&mut self, path: &str) -> bool {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                println!("Could not read {}: {}", path, err);
+                return true;
+            }
+        };
+        let mut lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from);
+        while let Some(line) = lines.next() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match DebuggerCommand::from_tokens(&tokens) {
+                // A breakpoint's commands come from the script too, not from the prompt
+                Some(DebuggerCommand::Commands(number)) => {
+                    let commands = Debugger::parse_command_block(&mut lines);
+                    if let Some(index) = self.commands_target(number) {
+                        self.breakpoints[index].commands = commands;
                     }
                 }
-                DebuggerCommand::Continue => {
-                    if self.inferior.is_none() {
-                        println!("run process first");
-                        continue;
+                Some(cmd) => {
+                    if !self.execute_all(cmd) {
+                        return false;
                     }
-                    let mut inferior = self.inferior.as_mut().unwrap();
-                    let _ = inferior.cont();
-                    let result = inferior.wait(None);
+                }
+                None => println!("Warning: skipping unrecognized command in {}: {}", path, line),
+            }
+        }
+        true
+    }
+
+    /// Carries out a command, followed by the commands of any breakpoints it hits. Returns false
+    /// if the debugger should exit.
+    fn execute_all(&mut self, cmd: DebuggerCommand) -> bool {
+        if !self.execute(cmd) {
+            return false;
+        }
+        // Breakpoint commands can hit breakpoints in turn, which queues up more commands
+        while let Some(cmd) = self.queued_commands.pop_front() {
+            if !self.execute(cmd) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Carries out a command. Returns false if the debugger should exit.
+    fn execute(&mut self, cmd: DebuggerCommand) -> bool {
+        match cmd {
+            DebuggerCommand::Run(args) => {
+                self.end_inferior();
+                let addrs = self
+                    .breakpoints
+                    .iter()
+                    .filter(|bp| bp.enabled && !bp.hardware)
+                    .map(|bp| bp.addr)
+                    .collect();
+                if let Some(inferior) = Inferior::new(&self.target, &args, &addrs, &self.env, None) {
+                    self.inferior = Some(inferior);
+                    self.insert_hw_breakpoints();
+                    let result = self.continue_inferior(None, None);
                     self.print_status(result);
+                } else {
+                    println!("Error starting subprocess");
+                }
+            }
+            DebuggerCommand::Continue => {
+                if !self.can_run() {
+                    return true;
                 }
-                DebuggerCommand::Quit => {
-                    if self.inferior.is_some() {
-                        let inferior = self.inferior.as_mut().unwrap();
-                        let _ = inferior.kill();
-                        let result = inferior.wait(None);
+                let result = self.continue_inferior(None, None);
+                self.print_status(result);
+            }
+            DebuggerCommand::Signal(name) => {
+                if !self.can_run() {
+                    return true;
+                }
+                match Debugger::parse_signal(&name) {
+                    Some(sig) => {
+                        let result = self.continue_inferior(Some(sig), None);
                         self.print_status(result);
                     }
-                    return;
+                    None => println!("Unknown signal {}", name),
+                }
+            }
+            DebuggerCommand::StepInstruction => {
+                if !self.can_run() {
+                    return true;
+                }
+                let result = self.step_instruction();
+                self.forget_if_exited(&result);
+                self.print_status(result);
+            }
+            DebuggerCommand::Next => {
+                if !self.can_run() {
+                    return true;
+                }
+                let result = self.next_line();
+                self.forget_if_exited(&result);
+                self.print_status(result);
+            }
+            DebuggerCommand::Step => {
+                if !self.can_run() {
+                    return true;
                 }
-                DebuggerCommand::Backtrace => {
-                    match &self.inferior {
-                        Some(inferior) => {
-                            let _ = inferior.print_backtrace(&self.dwarf_data);
+                let result = self.step_to_new_line();
+                self.forget_if_exited(&result);
+                self.print_status(result);
+            }
+            DebuggerCommand::Finish => {
+                if !self.can_run() {
+                    return true;
+                }
+                let result = self.finish();
+                self.forget_if_exited(&result);
+                self.print_status(result);
+            }
+            DebuggerCommand::Attach(pid) => self.attach(pid),
+            DebuggerCommand::Quit => {
+                self.end_inferior();
+                return false;
+            }
+            DebuggerCommand::Backtrace => {
+                match &self.inferior {
+                    Some(inferior) => {
+                        let _ = inferior.print_backtrace(&self.dwarf_data, self.selected_frame);
+                    }
+                    _ => {}
+                }
+            }
+            DebuggerCommand::ListBreakpoints => {
+                if self.breakpoints.is_empty() {
+                    println!("No breakpoints");
+                }
+                for bp in &self.breakpoints {
+                    let addr = bp.addr as usize;
+                    let func = self.dwarf_data.get_function_from_addr(addr);
+                    match (func, self.dwarf_data.get_line_from_addr(addr)) {
+                        (Some(func), Some(line)) => {
+                            print!("{}: {:#x} in {} ({})", bp.number, addr, func, line)
                         }
-                        _ => {}
+                        (Some(func), None) => print!("{}: {:#x} in {}", bp.number, addr, func),
+                        (None, _) => print!("{}: {:#x}", bp.number, addr),
+                    }
+                    if let Some(condition) = &bp.condition {
+                        print!(" if {}", condition);
+                    }
+                    if !bp.enabled {
+                        print!(" (disabled)");
                     }
+                    if bp.one_shot {
+                        print!(" (temporary)");
+                    }
+                    if bp.hardware {
+                        print!(" (hardware)");
+                    }
+                    if bp.catch_panic {
+                        print!(" (catches panics)");
+                    }
+                    println!(", hit {} time{}", bp.hits, if bp.hits == 1 { "" } else { "s" });
+                }
+            }
+            DebuggerCommand::DeleteBreakpoint(number) => {
+                if let Some(index) = self.find_breakpoint(number) {
+                    let addr = self.breakpoints.remove(index).addr;
+                    self.update_breakpoint(addr);
+                    println!("Deleted breakpoint {}", number);
+                }
+            }
+            DebuggerCommand::EnableBreakpoint(number) => {
+                if let Some(index) = self.find_breakpoint(number) {
+                    self.breakpoints[index].enabled = true;
+                    self.update_breakpoint(self.breakpoints[index].addr);
+                }
+            }
+            DebuggerCommand::DisableBreakpoint(number) => {
+                if let Some(index) = self.find_breakpoint(number) {
+                    self.breakpoints[index].enabled = false;
+                    self.update_breakpoint(self.breakpoints[index].addr);
                 }
-                DebuggerCommand::BreakPoint(regex) => {
-                    let mut point: u64 = 0;
-                    if regex.starts_with("*") {
-                        let nregex = regex.replace("*", "");
-                        if let Some(addr) = Debugger::parse_address(nregex.as_str()) {
-                            point = addr;
+            }
+            DebuggerCommand::Watch(var_name) => {
+                if !self.can_run() {
+                    return true;
+                }
+                match self.watch(&var_name) {
+                    Ok(addr) => println!("Watching {} at {:#x}", var_name, addr),
+                    Err(err) => println!("Could not watch {}: {}", var_name, err),
+                }
+            }
+            DebuggerCommand::List(location) => {
+                let line = match location {
+                    Some(location) => self.parse_source_location(&location),
+                    None => match &self.inferior {
+                        Some(_) => self
+                            .frame(self.selected_frame)
+                            .ok()
+                            .and_then(|frame| self.dwarf_data.get_line_from_addr(frame.rip as usize)),
+                        None => {
+                            println!("run process first");
+                            return true;
                         }
-                        println!("no breakpoint set for {}", nregex);
+                    },
+                };
+                match line {
+                    Some(line) => self.list_source(&line.file, line.number),
+                    None => println!("No source information available"),
+                }
+            }
+            DebuggerCommand::Disassemble(location) => {
+                if self.inferior.is_none() {
+                    println!("run process first");
+                    return true;
+                }
+                if let Err(err) = self.disassemble(location.as_deref()) {
+                    println!("{}", err);
+                }
+            }
+            DebuggerCommand::InfoRegisters => {
+                if self.inferior.is_none() {
+                    println!("run process first");
+                    return true;
+                }
+                match self.selected_regs() {
+                    Ok(regs) => inferior::print_registers(&regs),
+                    Err(err) => println!("Could not read registers: {}", err),
+                }
+            }
+            DebuggerCommand::PrintRegister(name) => {
+                if self.inferior.is_none() {
+                    println!("run process first");
+                    return true;
+                }
+                match self.selected_regs().map(|regs| inferior::register_value(&regs, &name)) {
+                    Ok(Some(value)) => println!("${} = {:#x} ({})", name, value, value as i64),
+                    Ok(None) => println!("No register named ${}", name),
+                    Err(err) => println!("Could not read ${}: {}", name, err),
+                }
+            }
+            DebuggerCommand::FrameUp => {
+                if self.inferior.is_some() {
+                    self.select_frame(self.selected_frame + 1);
+                } else {
+                    println!("run process first");
+                }
+            }
+            DebuggerCommand::FrameDown => {
+                if self.inferior.is_none() {
+                    println!("run process first");
+                } else if self.selected_frame == 0 {
+                    println!("Already at the innermost frame");
+                } else {
+                    self.select_frame(self.selected_frame - 1);
+                }
+            }
+            DebuggerCommand::Frame(index) => {
+                if self.inferior.is_some() {
+                    self.select_frame(index);
+                } else {
+                    println!("run process first");
+                }
+            }
+            DebuggerCommand::InfoThreads => {
+                let inferior = match &self.inferior {
+                    Some(inferior) => inferior,
+                    None => {
+                        println!("run process first");
+                        return true;
+                    }
+                };
+                for (i, &tid) in inferior.threads().iter().enumerate() {
+                    match inferior.thread_regs(tid) {
+                        Ok(regs) => inferior::print_thread(
+                            &self.dwarf_data,
+                            i,
+                            tid,
+                            regs.rip,
+                            tid == inferior.current_thread(),
+                        ),
+                        Err(err) => println!("  {} Thread {}: {}", i, tid, err),
+                    }
+                }
+            }
+            DebuggerCommand::Thread(index) => {
+                let inferior = match &mut self.inferior {
+                    Some(inferior) => inferior,
+                    None => {
+                        println!("run process first");
+                        return true;
+                    }
+                };
+                match inferior.threads().get(index).copied() {
+                    Some(tid) => {
+                        inferior.select_thread(tid);
+                        self.select_frame(0);
+                    }
+                    None => println!("No thread {}", index),
+                }
+            }
+            DebuggerCommand::Examine(format, addr) => {
+                if self.inferior.is_none() {
+                    println!("run process first");
+                    return true;
+                }
+                if let Err(err) = self.examine(&format, &addr) {
+                    println!("{}", err);
+                }
+            }
+            DebuggerCommand::SetMemory(target, value) => {
+                if !self.can_run() {
+                    return true;
+                }
+                if let Err(err) = self.set_memory(&target, &value) {
+                    println!("{}", err);
+                }
+            }
+            DebuggerCommand::SetRegister(name, value) => {
+                if !self.can_run() {
+                    return true;
+                }
+                match self.inferior.as_mut().unwrap().set_register(&name, value) {
+                    // Any frame other than the innermost is now out of date
+                    Ok(()) => self.selected_frame = 0,
+                    Err(nix::Error::Sys(nix::errno::Errno::EINVAL)) => {
+                        println!("No register named ${}", name)
+                    }
+                    Err(err) => println!("Could not set ${}: {}", name, err),
+                }
+            }
+            DebuggerCommand::SetEnv(key, val) => {
+                self.env.insert(key, Some(val));
+                self.note_env_change();
+            }
+            DebuggerCommand::UnsetEnv(key) => {
+                self.env.insert(key, None);
+                self.note_env_change();
+            }
+            DebuggerCommand::BreakPoint(location, condition) => {
+                self.set_breakpoint(&location, condition, false, false);
+            }
+            DebuggerCommand::TempBreakpoint(location, condition) => {
+                self.set_breakpoint(&location, condition, true, false);
+            }
+            DebuggerCommand::HardwareBreakPoint(location, condition) => {
+                self.set_breakpoint(&location, condition, false, true);
+            }
+            DebuggerCommand::CatchPanic => self.catch_panic(),
+            DebuggerCommand::Source(path) => return self.source(&path),
+            DebuggerCommand::Commands(number) => {
+                if let Some(index) = self.commands_target(number) {
+                    println!(
+                        "Type commands for breakpoint {}, one per line. End with \"end\".",
+                        self.breakpoints[index].number
+                    );
+                    self.breakpoints[index].commands = self.read_command_block();
+                }
+            }
+        }
+        true
+    }
+
+    /// Sets a breakpoint at a location, which is either `*<address>`, `<file>:<line>`, a line
+    /// number in the main source file, or the name of a function. One-shot breakpoints are
+    /// deleted the first time they stop the inferior.
+    fn set_breakpoint(
+        &mut self,
+        regex: &str,
+        condition: Option<String>,
+        one_shot: bool,
+        hardware: bool,
+    ) {
+        if let Some(condition) = &condition {
+            if Debugger::parse_condition(condition).is_none() {
+                println!("Invalid condition \"{}\"", condition);
+                return;
+            }
+        }
+        let point = if let Some(addr) = regex.strip_prefix('*') {
+            Debugger::parse_address(addr)
+        } else if let Some(line) = self.parse_source_location(regex) {
+            Some(line.address as u64)
+        } else if let Ok(line) = regex.parse::<usize>() {
+            self.dwarf_data.get_addr_for_line(None, line).map(|addr| addr as u64)
+        } else {
+            self.dwarf_data.get_addr_for_function(None, regex).map(|addr| addr as u64)
+        };
+        let point = match point {
+            Some(point) if point != 0 => point,
+            _ => {
+                println!("no breakpoint set for {}", regex);
+                return;
+            }
+        };
+        if hardware {
+            let mut addrs: Vec<u64> = self
+                .breakpoints
+                .iter()
+                .filter(|bp| bp.hardware && bp.enabled)
+                .map(|bp| bp.addr)
+                .collect();
+            addrs.push(point);
+            addrs.sort();
+            addrs.dedup();
+            // There are only four debug registers
+            if addrs.len() > 4 {
+                println!("Can't have more than 4 hardware breakpoints");
+                return;
+            }
+        }
+        let number = self.next_breakpoint_number;
+        self.next_breakpoint_number += 1;
+        let kind = if one_shot {
+            "temporary breakpoint"
+        } else if hardware {
+            "hardware breakpoint"
+        } else {
+            "breakpoint"
+        };
+        println!("Set {} {} at {:#x}", kind, number, point);
+        self.breakpoints.push(Breakpoint {
+            number,
+            addr: point,
+            condition,
+            hits: 0,
+            enabled: true,
+            one_shot,
+            hardware,
+            catch_panic: false,
+            commands: vec![],
+        });
+        self.update_breakpoint(point);
+    }
+
+    /// Lets the user know that a change to the environment won't affect a running inferior.
+    fn note_env_change(&self) {
+        if self.inferior.is_some() {
+            println!("Environment changes take effect on next 'run'.");
+        }
+    }
+
+    /// Sets a breakpoint that catches Rust panics, stopping the inferior before it unwinds (or
+    /// aborts).
+    fn catch_panic(&mut self) {
+        let addr = PANIC_FUNCTIONS
+            .iter()
+            .find_map(|name| self.dwarf_data.get_addr_for_function(None, name));
+        let addr = match addr {
+            Some(addr) => addr,
+            None => {
+                println!("Could not find Rust's panic handler (is this a Rust program?)");
+                return;
+            }
+        };
+        let count = self.breakpoints.len();
+        self.set_breakpoint(&format!("*{:#x}", addr), None, false, false);
+        if self.breakpoints.len() > count {
+            self.breakpoints.last_mut().unwrap().catch_panic = true;
+        }
+    }
+
+    /// Reports a panic caught by catch panic, with where it happened if we can tell.
+    fn report_panic(&self) {
+        match self.panic_location() {
+            Some(location) => println!("Inferior panicked at {}", location),
+            None => println!("Inferior panicked"),
+        }
+    }
+
+    /// Finds where a panic happened, when stopped at the start of the panic handler. Its argument
+    /// is a &PanicInfo, which holds a reference to the message and one to the location, in
+    /// whichever order the compiler chose.
+    fn panic_location(&self) -> Option<String> {
+        let inferior = self.inferior.as_ref()?;
+        let regs = inferior.regs().ok()?;
+        let func = self.dwarf_data.get_function_from_addr(regs.rip as usize)?;
+        if !func.ends_with("rust_begin_unwind") {
+            return None;
+        }
+        (0..2).find_map(|i| {
+            let location = inferior.read_word(regs.rdi + 8 * i).ok()?;
+            // A Location is the file name (a &str), then the line and column as u32s
+            let bytes = inferior.read_memory(location, 24).ok()?;
+            let word = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+            let half = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+            let (file, len) = (word(0), word(8) as usize);
+            if len == 0 || len > 4096 {
+                return None;
+            }
+            let file = String::from_utf8(inferior.read_memory(file, len).ok()?).ok()?;
+            Some(format!("{}:{}:{}", file, half(16), half(20)))
+        })
+    }
+
+    /// Attaches to a running process, replacing the current inferior (if any).
+    pub fn attach(&mut self, pid: u32) {
+        self.end_inferior();
+        let addrs = self
+            .breakpoints
+            .iter()
+            .filter(|bp| bp.enabled && !bp.hardware)
+            .map(|bp| bp.addr)
+            .collect();
+        let pid = Pid::from_raw(pid as i32);
+        match Inferior::new(&self.target, &vec![], &addrs, &self.env, Some(pid)) {
+            Some(inferior) => {
+                println!("Attached to process {}", pid);
+                let rip = inferior.regs().map(|regs| regs.rip as usize);
+                self.inferior = Some(inferior);
+                self.insert_hw_breakpoints();
+                self.print_status(rip.map(|rip| Status::Stopped(Signal::SIGSTOP, rip)));
+            }
+            None => println!("Could not attach to process {}", pid),
+        }
+    }
+
+    /// Loads a core file, whose crashed process then stands in for the inferior so that it can be
+    /// inspected (though not run).
+    pub fn load_core(&mut self, path: &str) {
+        self.end_inferior();
+        match Inferior::from_core(path) {
+            Ok((inferior, signal)) => {
+                if let Some(signal) = signal {
+                    println!("Program terminated with signal {}", signal);
+                }
+                if let Ok(regs) = inferior.regs() {
+                    if let Some(line) = self.dwarf_data.get_line_from_addr(regs.rip as usize) {
+                        println!("rip {:#x}, {}", regs.rip, line);
+                    }
+                }
+                self.inferior = Some(inferior);
+            }
+            Err(err) => println!("Could not load core file {}: {}", path, err),
+        }
+    }
+
+    /// Checks that there is an inferior that can be run, explaining why not otherwise.
+    fn can_run(&self) -> bool {
+        match &self.inferior {
+            None => {
+                println!("run process first");
+                false
+            }
+            Some(inferior) if inferior.is_core() => {
+                println!("Cannot run in core mode.");
+                false
+            }
+            Some(_) => true,
+        }
+    }
+
+    /// Gets rid of the current inferior, if there is one. Processes we started are killed, while
+    /// ones we attached to are detached from and left running.
+    fn end_inferior(&mut self) {
+        self.watchpoints.clear();
+        let mut inferior = match self.inferior.take() {
+            Some(inferior) => inferior,
+            None => return,
+        };
+        if inferior.is_core() {
+            // Nothing to clean up
+        } else if inferior.is_attached() {
+            match inferior.detach() {
+                Ok(()) => println!("Detached from process {}", inferior.pid()),
+                Err(err) => println!("Could not detach from process {}: {}", inferior.pid(), err),
+            }
+        } else {
+            let _ = inferior.kill();
+            let result = inferior.wait(None);
+            self.print_status(result);
+        }
+    }
+
+    /// Continues the inferior until it stops somewhere worth reporting, which is anywhere other
+    /// than a breakpoint whose condition doesn't hold or a watchpoint whose variable didn't
+    /// change. Reaching `until` (where the caller has put a breakpoint of its own) always is.
+    fn continue_inferior(
+        &mut self,
+        mut signal: Option<Signal>,
+        until: Option<u64>,
+    ) -> Result<Status, nix::Error> {
+        loop {
+            let inferior = self.inferior.as_mut().unwrap();
+            // The signal (if any) is only delivered when first resuming
+            let _ = inferior.cont(signal.take());
+            let status = inferior.wait(None)?;
+            if let Status::Stopped(Signal::SIGTRAP, _) = status {
+                if let Some(slot) = inferior.watchpoint_hit()? {
+                    if !self.report_watchpoint(slot)? {
                         continue;
                     }
-                    if let Some(line) = Debugger::parse_address(regex.as_str()) {
-                        if let Some(addr) = self.dwarf_data.get_addr_for_line(None, line as usize) {
-                            point = addr as u64;
+                    return Ok(status);
+                }
+            }
+            if let Status::Stopped(Signal::SIGTRAP, rip) = status {
+                let here: Vec<usize> = (0..self.breakpoints.len())
+                    .filter(|&i| self.breakpoints[i].enabled && self.breakpoints[i].addr == rip as u64)
+                    .collect();
+                let hit: Vec<usize> = here
+                    .iter()
+                    .copied()
+                    .filter(|&i| self.should_stop(&self.breakpoints[i]))
+                    .collect();
+                if !here.is_empty() && hit.is_empty() && until != Some(rip as u64) {
+                    continue;
+                }
+                if hit.iter().any(|&i| self.breakpoints[i].catch_panic) {
+                    self.report_panic();
+                }
+                for &i in &hit {
+                    self.breakpoints[i].hits += 1;
+                    let commands = self.breakpoints[i].commands.iter().cloned();
+                    self.queued_commands.extend(commands);
+                }
+                // Going backwards so that removing one doesn't shift the rest
+                for &i in hit.iter().rev() {
+                    if self.breakpoints[i].one_shot {
+                        let bp = self.breakpoints.remove(i);
+                        println!("Deleted temporary breakpoint {}", bp.number);
+                        self.update_breakpoint(bp.addr);
+                    }
+                }
+            }
+            let result = Ok(status);
+            self.forget_if_exited(&result);
+            return result;
+        }
+    }
+
+    /// Executes a single instruction, reporting any watchpoint it sets off.
+    fn step_instruction(&mut self) -> Result<Status, nix::Error> {
+        let status = self.inferior.as_mut().unwrap().step_instruction()?;
+        if let Status::Stopped(Signal::SIGTRAP, _) = status {
+            self.watchpoint_fired()?;
+        }
+        Ok(status)
+    }
+
+    /// Reports the watchpoint behind the inferior's last SIGTRAP, if there was one, returning
+    /// whether its variable changed.
+    fn watchpoint_fired(&mut self) -> Result<bool, nix::Error> {
+        match self.inferior.as_ref().unwrap().watchpoint_hit()? {
+            Some(slot) => self.report_watchpoint(slot),
+            None => Ok(false),
+        }
+    }
+
+    /// Drops the inferior if it has exited (or been killed), since there is nothing left to run.
+    fn forget_if_exited(&mut self, result: &Result<Status, nix::Error>) {
+        if let Ok(Status::Exited(_)) | Ok(Status::Signaled(_)) = result {
+            self.inferior = None;
+            self.watchpoints.clear();
+        }
+    }
+
+    /// Sets a watchpoint on a variable visible from where the inferior is stopped, returning the
+    /// variable's address.
+    fn watch(&mut self, var_name: &str) -> Result<u64, String> {
+        let frame = self.frame(self.selected_frame)?;
+        let (var, addr) = self.find_variable(&frame, var_name)?;
+        let entity_type = var.entity_type.clone();
+        let inferior = self.inferior.as_mut().unwrap();
+        let value = inferior
+            .read_int(addr, &entity_type)
+            .map_err(|err| err.to_string())?;
+        let slot = inferior
+            .set_watchpoint(addr, entity_type.size)
+            .map_err(|err| err.to_string())?;
+        self.watchpoints.push(Watchpoint {
+            var_name: var_name.to_string(),
+            entity_type,
+            addr,
+            slot,
+            value,
+        });
+        Ok(addr)
+    }
+
+    /// Prints the old and new values of the variable whose watchpoint was just hit. Returns false
+    /// if the value didn't actually change, in which case the inferior needn't stop.
+    fn report_watchpoint(&mut self, slot: usize) -> Result<bool, nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let watchpoint = match self.watchpoints.iter_mut().find(|w| w.slot == slot) {
+            Some(watchpoint) => watchpoint,
+            None => return Ok(true),
+        };
+        let value = inferior.read_int(watchpoint.addr, &watchpoint.entity_type)?;
+        if value == watchpoint.value {
+            return Ok(false);
+        }
+        println!("Watchpoint on {} hit", watchpoint.var_name);
+        println!("Old value = {}", watchpoint.value);
+        println!("New value = {}", value);
+        watchpoint.value = value;
+        Ok(true)
+    }
+
+    /// Turns a `<file>:<line>` location into the full path of the file, as recorded in the debug
+    /// info, and the line number.
+    fn parse_source_location(&self, location: &str) -> Option<Line> {
+        let (file, number) = location.rsplit_once(':')?;
+        let number = number.parse().ok()?;
+        let addr = self.dwarf_data.get_addr_for_line(Some(file), number)?;
+        let file = self.dwarf_data.get_line_from_addr(addr)?.file;
+        Some(Line { file, number, address: addr })
+    }
+
+    /// Prints the lines of a source file around the given line, marking that line with an arrow.
+    fn list_source(&mut self, path: &str, number: usize) {
+        if !self.source_files.contains_key(path) {
+            match std::fs::read_to_string(path) {
+                Ok(contents) => {
+                    let lines = contents.lines().map(|line| line.to_string()).collect();
+                    self.source_files.insert(path.to_string(), lines);
+                }
+                Err(err) => {
+                    println!("Could not read {}: {}", path, err);
+                    return;
+                }
+            }
+        }
+        let lines = &self.source_files[path];
+        let first = number.saturating_sub(5).max(1);
+        let last = (number + 5).min(lines.len());
+        for i in first..=last {
+            let marker = if i == number { "\u{2192}" } else { " " };
+            println!("{} {:>4}\t{}", marker, i, lines[i - 1]);
+        }
+    }
+
+    /// Disassembles a whole function: the one named by location, or the one containing the
+    /// address it gives, or the current function if there is no location. The instruction at rip
+    /// is marked with an arrow.
+    fn disassemble(&self, location: Option<&str>) -> Result<(), String> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let rip = self.frame(self.selected_frame)?.rip;
+        let func = match location {
+            // Function names take precedence over addresses, since names like "add" are valid hex
+            Some(name) if self.dwarf_data.get_function_range(name).is_some() => name.to_string(),
+            Some(location) => {
+                let addr = Debugger::parse_address(location)
+                    .ok_or_else(|| format!("No function or address {}", location))?;
+                self.dwarf_data
+                    .get_function_from_addr(addr as usize)
+                    .ok_or_else(|| format!("No function contains address {:#x}", addr))?
+            }
+            None => self
+                .dwarf_data
+                .get_function_from_addr(rip as usize)
+                .ok_or_else(|| "No function contains the current instruction".to_string())?,
+        };
+        let (start, end) = self
+            .dwarf_data
+            .get_function_range(&func)
+            .ok_or_else(|| format!("No code for function {}", func))?;
+        let mut code = inferior
+            .read_memory(start as u64, end - start)
+            .map_err(|err| format!("Cannot access memory at {:#x}: {}", start, err))?;
+        // Show the code as written, not with our breakpoints in it
+        for (i, byte) in code.iter_mut().enumerate() {
+            if let Some(orig_byte) = inferior.original_byte((start + i) as u64) {
+                *byte = orig_byte;
+            }
+        }
+
+        println!("Dump of assembler code for function {}:", func);
+        let mut decoder = Decoder::with_ip(64, &code, start as u64, DecoderOptions::NONE);
+        let mut formatter = GasFormatter::new();
+        let mut instruction = Instruction::default();
+        let mut text = String::new();
+        while decoder.can_decode() {
+            decoder.decode_out(&mut instruction);
+            text.clear();
+            formatter.format(&instruction, &mut text);
+            let offset = instruction.ip() as usize - start;
+            let bytes: Vec<String> = code[offset..offset + instruction.len()]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let marker = if instruction.ip() == rip { "\u{2192}" } else { " " };
+            println!(
+                "{} {:#x} <+{}>:\t{:<24}{}",
+                marker,
+                instruction.ip(),
+                offset,
+                bytes.join(" "),
+                text
+            );
+        }
+        println!("End of assembler dump.");
+        Ok(())
+    }
+
+    /// Writes value to a variable visible from the selected frame, or given `*<address>`, to the
+    /// 8-byte word there, and prints what was there before.
+    fn set_memory(&mut self, target: &str, value: &str) -> Result<(), String> {
+        let (addr, entity_type) = match target.strip_prefix('*') {
+            Some(addr) => (
+                Debugger::parse_address(addr).ok_or_else(|| format!("Invalid address {}", addr))?,
+                Type::new("long".to_string(), 8),
+            ),
+            None => {
+                let (var, addr) = self.find_variable(&self.frame(self.selected_frame)?, target)?;
+                (addr, var.entity_type.clone())
+            }
+        };
+        let value = parse_value(value).ok_or_else(|| format!("Invalid value {}", value))?;
+        let size = entity_type.size.max(1).min(8);
+        let inferior = self.inferior.as_mut().unwrap();
+        let access_err = |err: nix::Error| format!("Cannot access memory at {:#x}: {}", addr, err);
+        if !inferior.is_mapped(addr, size) {
+            return Err(format!("Cannot access memory at {:#x}: not mapped", addr));
+        }
+        let previous = inferior.read_int(addr, &entity_type).map_err(access_err)?;
+        if size == 8 {
+            inferior.write_word(addr, value as u64).map_err(access_err)?;
+        } else {
+            inferior
+                .write_memory(addr, &value.to_le_bytes()[..size])
+                .map_err(access_err)?;
+        }
+        println!("Previous value: {}", previous);
+        Ok(())
+    }
+
+    /// Prints memory starting at addr, which is either a hex address or `&` followed by the name of
+    /// a variable. The format is a count followed by one of:
+    ///
+    /// * `x`: 4-byte words in hex
+    /// * `d`: 4-byte words in decimal
+    /// * `s`: null-terminated strings
+    /// * `i`: instructions, though since we can't decode them, this just prints raw bytes
+    fn examine(&self, format: &str, addr: &str) -> Result<(), String> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let split = format.find(|c: char| !c.is_ascii_digit()).unwrap_or(format.len());
+        let count = match &format[..split] {
+            "" => 1,
+            count => count.parse::<usize>().map_err(|_| format!("Invalid count {}", count))?,
+        };
+        let addr = match addr.strip_prefix('&') {
+            Some(var_name) => self.find_variable(&self.frame(self.selected_frame)?, var_name)?.1,
+            None => Debugger::parse_address(addr).ok_or_else(|| format!("Invalid address {}", addr))?,
+        };
+        let read = |addr, count| {
+            inferior
+                .read_memory(addr, count)
+                .map_err(|err| format!("Cannot access memory at {:#x}: {}", addr, err))
+        };
+        match &format[split..] {
+            "x" | "d" | "" => {
+                let bytes = read(addr, 4 * count)?;
+                for (i, word) in bytes.chunks(4).enumerate() {
+                    if i % 4 == 0 {
+                        if i > 0 {
+                            println!();
                         }
-                    } else if let Some(addr) = self.dwarf_data.get_addr_for_function(None, regex.as_str()) {
-                        point = addr as u64;
+                        print!("{:#x}:", addr + 4 * i as u64);
                     }
-                    if point == 0 {
-                        println!("no breakpoint set for {}", regex);
-                        continue;
+                    let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                    if &format[split..] == "d" {
+                        print!("\t{}", word as i32);
+                    } else {
+                        print!("\t{:#010x}", word);
                     }
-                    println!("Set breakpoint {} at {:#x}",  self.breakpoints.len(), point);
-                    self.breakpoints.push(point);
-                    if self.inferior.is_some() {
-                        self.inferior.as_mut().unwrap().write_byte(point, 0xcc).unwrap();
+                }
+                println!();
+            }
+            "s" => {
+                let mut addr = addr;
+                for _ in 0..count {
+                    // Read a chunk at a time until we find the terminating null byte
+                    let mut string = Vec::new();
+                    loop {
+                        let chunk = read(addr + string.len() as u64, 64)?;
+                        match chunk.iter().position(|&b| b == 0) {
+                            Some(end) => {
+                                string.extend_from_slice(&chunk[..end]);
+                                break;
+                            }
+                            None => string.extend_from_slice(&chunk),
+                        }
                     }
+                    println!("{:#x}:\t{:?}", addr, String::from_utf8_lossy(&string));
+                    addr += string.len() as u64 + 1;
+                }
+            }
+            "i" => {
+                let bytes = read(addr, count)?;
+                for (i, line) in bytes.chunks(8).enumerate() {
+                    let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+                    println!("{:#x}:\t{}", addr + 8 * i as u64, hex.join(" "));
                 }
             }
+            other => return Err(format!("Unknown format {}", other)),
         }
+        Ok(())
+    }
+
+    /// Returns the index in `breakpoints` of the breakpoint with the given number, or prints an
+    /// error if there is none.
+    fn find_breakpoint(&self, number: usize) -> Option<usize> {
+        let index = self.breakpoints.iter().position(|bp| bp.number == number);
+        if index.is_none() {
+            println!("No breakpoint number {}", number);
+        }
+        index
+    }
+
+    /// Makes the running inferior (if any) agree with `breakpoints` about addr: there should be an
+    /// int3 there exactly when some enabled software breakpoint is at addr, and a debug register
+    /// holding addr exactly when some enabled hardware breakpoint is. Several breakpoints can share
+    /// an address, but there is only one int3 (or debug register) to insert.
+    fn update_breakpoint(&mut self, addr: u64) {
+        let wanted = |hardware: bool| {
+            self.breakpoints
+                .iter()
+                .any(|bp| bp.enabled && bp.addr == addr && bp.hardware == hardware)
+        };
+        let (int3_wanted, hw_wanted) = (wanted(false), wanted(true));
+        if let Some(inferior) = self.inferior.as_mut().filter(|inferior| !inferior.is_core()) {
+            if int3_wanted && inferior.original_byte(addr).is_none() {
+                inferior.write_byte(addr, 0xcc).unwrap();
+            } else if !int3_wanted {
+                inferior.remove_breakpoint(addr).unwrap();
+            }
+            let hw_set = inferior.hw_breakpoint_slot(addr).is_some();
+            if hw_wanted && !hw_set {
+                let result = match inferior.free_debug_slot() {
+                    Some(slot) => inferior.set_hw_breakpoint(addr, slot),
+                    None => Err(nix::Error::Sys(nix::errno::Errno::ENOSPC)),
+                };
+                if let Err(err) = result {
+                    println!("Could not set hardware breakpoint at {:#x}: {}", addr, err);
+                }
+            } else if !hw_wanted && hw_set {
+                inferior.remove_hw_breakpoint(addr).unwrap();
+            }
+        }
+    }
+
+    /// Puts the enabled hardware breakpoints into a new inferior's debug registers. (Software
+    /// breakpoints are inserted as the inferior is created.)
+    fn insert_hw_breakpoints(&mut self) {
+        let addrs: Vec<u64> = self
+            .breakpoints
+            .iter()
+            .filter(|bp| bp.hardware)
+            .map(|bp| bp.addr)
+            .collect();
+        for addr in addrs {
+            self.update_breakpoint(addr);
+        }
+    }
+
+    /// Returns whether the inferior, having reached bp, should stop there.
+    fn should_stop(&self, bp: &Breakpoint) -> bool {
+        match &bp.condition {
+            None => true,
+            Some(condition) => match self.eval_condition(condition) {
+                Ok(holds) => holds,
+                Err(err) => {
+                    println!("Error evaluating condition \"{}\": {}", condition, err);
+                    true
+                }
+            },
+        }
+    }
+
+    /// Evaluates a breakpoint condition in the frame the inferior is stopped in.
+    fn eval_condition(&self, condition: &str) -> Result<bool, String> {
+        let (lhs, op, rhs) =
+            Debugger::parse_condition(condition).ok_or_else(|| "invalid condition".to_string())?;
+        let lhs = self.eval_operand(lhs)?;
+        let rhs = self.eval_operand(rhs)?;
+        Ok(match op {
+            "==" => lhs == rhs,
+            "!=" => lhs != rhs,
+            "<=" => lhs <= rhs,
+            ">=" => lhs >= rhs,
+            "<" => lhs < rhs,
+            _ => lhs > rhs,
+        })
+    }
+
+    /// Evaluates one side of a condition: either an integer (in decimal or hex) or the name of a
+    /// variable.
+    fn eval_operand(&self, operand: &str) -> Result<i64, String> {
+        if let Some(value) = parse_value(operand) {
+            return Ok(value);
+        }
+        // Conditions are checked right as the inferior stops, so they apply to the innermost frame
+        let (var, addr) = self.find_variable(&self.frame(0)?, operand)?;
+        self.inferior
+            .as_ref()
+            .unwrap()
+            .read_int(addr, &var.entity_type)
+            .map_err(|err| err.to_string())
+    }
+
+    /// Returns the given frame of the inferior's stack, 0 being the innermost.
+    fn frame(&self, index: usize) -> Result<Frame, String> {
+        let frames = self
+            .inferior
+            .as_ref()
+            .unwrap()
+            .frames(&self.dwarf_data)
+            .map_err(|err| err.to_string())?;
+        frames.get(index).copied().ok_or_else(|| format!("No frame {}", index))
+    }
+
+    /// Selects a frame for inspection and prints it.
+    fn select_frame(&mut self, index: usize) {
+        match self.frame(index) {
+            Ok(frame) => {
+                self.selected_frame = index;
+                inferior::print_frame(&self.dwarf_data, index, &frame, true);
+            }
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    /// Returns the inferior's registers as seen from the selected frame. Only rip, rsp and rbp
+    /// can be recovered for outer frames; the other registers are shown as they are now.
+    fn selected_regs(&self) -> Result<libc::user_regs_struct, String> {
+        let mut regs = self
+            .inferior
+            .as_ref()
+            .unwrap()
+            .regs()
+            .map_err(|err| err.to_string())?;
+        let frame = self.frame(self.selected_frame)?;
+        regs.rip = frame.rip;
+        regs.rsp = frame.rsp;
+        regs.rbp = frame.rbp;
+        Ok(regs)
+    }
+
+    /// Looks up a variable visible from the given frame, returning it along with its address.
+    fn find_variable(&self, frame: &Frame, var_name: &str) -> Result<(&Variable, u64), String> {
+        let func = self.dwarf_data.get_function_from_addr(frame.rip as usize);
+        let var = self
+            .dwarf_data
+            .get_variable(func.as_deref(), var_name)
+            .ok_or_else(|| format!("No variable named {}", var_name))?;
+        Ok((var, frame.variable_addr(var)))
+    }
+
+    /// Runs until the next source line of the current function, stepping over any calls. This is
+    /// done with a temporary breakpoint on the next line. If there is no next line in this
+    /// function (e.g. we are on the closing brace), a single instruction is executed instead.
+    fn next_line(&mut self) -> Result<Status, nix::Error> {
+        let rip = self.inferior.as_ref().unwrap().regs()?.rip as usize;
+        let function = self.dwarf_data.get_function_from_addr(rip);
+        let next_addr = self
+            .dwarf_data
+            .get_line_from_addr(rip)
+            .and_then(|line| {
+                self.dwarf_data
+                    .get_addr_for_line(Some(&line.file), line.number + 1)
+            })
+            .filter(|addr| {
+                function.is_some() && self.dwarf_data.get_function_from_addr(*addr) == function
+            });
+        match next_addr {
+            Some(addr) => self.run_to(addr as u64),
+            None => self.step_instruction(),
+        }
+    }
+
+    /// Runs until the current function returns to its caller, using a temporary breakpoint on the
+    /// return address.
+    fn finish(&mut self) -> Result<Status, nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let (return_addr, _) = inferior.caller_frame(&self.dwarf_data)?;
+        self.run_to(return_addr as u64)
+    }
+
+    /// Single-steps until execution reaches a different source line than the one it started on,
+    /// following calls into functions that have debug info. Calls into code without line
+    /// information (e.g. library functions) are run to completion rather than stepped through.
+    /// A watchpoint going off stops the stepping early.
+    fn step_to_new_line(&mut self) -> Result<Status, nix::Error> {
+        let rip = self.inferior.as_ref().unwrap().regs()?.rip as usize;
+        let start_line = self
+            .dwarf_data
+            .get_line_from_addr(rip)
+            .map(|line| (line.file, line.number));
+        loop {
+            let mut status = self.inferior.as_mut().unwrap().step_instruction()?;
+            let mut rip = match status {
+                Status::Stopped(_, rip) => rip,
+                _ => return Ok(status),
+            };
+            if self.watchpoint_fired()? {
+                return Ok(status);
+            }
+            if self.dwarf_data.get_line_from_addr(rip).is_none() {
+                // We just called into code without line info, so the return address is on top of
+                // the stack
+                let inferior = self.inferior.as_ref().unwrap();
+                let return_addr = inferior.read_word(inferior.regs()?.rsp)?;
+                if self.dwarf_data.get_line_from_addr(return_addr as usize).is_none() {
+                    return Ok(status);
+                }
+                status = self.run_to(return_addr)?;
+                match status {
+                    Status::Stopped(Signal::SIGTRAP, addr) if addr as u64 == return_addr => rip = addr,
+                    _ => return Ok(status),
+                }
+            }
+            let line = self.dwarf_data.get_line_from_addr(rip).unwrap();
+            if start_line != Some((line.file, line.number)) {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Continues until addr is reached, using a temporary breakpoint that is removed again once
+    /// the inferior stops. Breakpoints and watchpoints along the way are dealt with as they are
+    /// by continue, so the inferior may stop somewhere else first. If there is already a
+    /// breakpoint at addr, it is left alone.
+    fn run_to(&mut self, addr: u64) -> Result<Status, nix::Error> {
+        let inferior = self.inferior.as_mut().unwrap();
+        let temporary = inferior.original_byte(addr).is_none();
+        if temporary {
+            inferior.write_byte(addr, 0xcc)?;
+        }
+        let result = self.continue_inferior(None, Some(addr));
+        if temporary {
+            // If the process is gone, so is its memory, and the breakpoint with it
+            if let Some(inferior) = self.inferior.as_mut() {
+                inferior.remove_breakpoint(addr)?;
+            }
+        }
+        result
     }
 
     /// This function prompts the user to enter a command, and continues re-prompting until the user
@@ -161,7 +1305,54 @@ impl Debugger {
         }
     }
 
-    fn print_status(&self, result: Result<Status, nix::Error>) {
+    /// Finds the breakpoint a commands command refers to: the one numbered, or else the most
+    /// recently set one.
+    fn commands_target(&self, number: Option<usize>) -> Option<usize> {
+        match number {
+            Some(number) => self.find_breakpoint(number),
+            None if self.breakpoints.is_empty() => {
+                println!("No breakpoints");
+                None
+            }
+            None => Some(self.breakpoints.len() - 1),
+        }
+    }
+
+    /// Reads commands from the prompt, one per line, up to a line saying "end".
+    fn read_command_block(&mut self) -> Vec<DebuggerCommand> {
+        let readline = &mut self.readline;
+        let mut lines = std::iter::from_fn(|| match readline.readline(">") {
+            Ok(line) => Some(line),
+            // Treat ctrl+c and ctrl+d like "end"
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
+            Err(err) => panic!("Unexpected I/O error: {:?}", err),
+        });
+        Debugger::parse_command_block(&mut lines)
+    }
+
+    /// Parses commands, one per line, up to a line saying "end".
+    fn parse_command_block(lines: &mut dyn Iterator<Item = String>) -> Vec<DebuggerCommand> {
+        let mut commands = Vec::new();
+        for line in lines {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+            if tokens == ["end"] {
+                break;
+            }
+            match DebuggerCommand::from_tokens(&tokens) {
+                Some(cmd) => commands.push(cmd),
+                None => println!("Unrecognized command."),
+            }
+        }
+        commands
+    }
+
+    /// Reports how the inferior stopped. Since it has moved on, this also goes back to the
+    /// innermost frame.
+    fn print_status(&mut self, result: Result<Status, nix::Error>) {
+        self.selected_frame = 0;
         match result {
             Ok(Status::Exited(exit_code)) => {
                 println!("Child exited (status {})", exit_code);
@@ -171,6 +1362,14 @@ impl Debugger {
             }
             Ok(Status::Stopped(signal, rip)) => {
                 println!("Child stopped (signal {})", signal);
+                if let Some(inferior) = &self.inferior {
+                    let threads = inferior.threads();
+                    if threads.len() > 1 {
+                        let tid = inferior.current_thread();
+                        let index = threads.iter().position(|&thread| thread == tid).unwrap();
+                        println!("In thread {} ({})", index, tid);
+                    }
+                }
                 // if let Some(func) = self.dwarf_data.get_function_from_addr(rip) {
                 //     print!("Stopped at {}", func);
                 // }
@@ -182,6 +1381,34 @@ impl Debugger {
         }
     }
 
+    /// Splits a condition of the form `<operand> <op> <operand>` into its parts, where op is one of
+    /// ==, !=, <, <=, > or >=.
+    fn parse_condition(condition: &str) -> Option<(&str, &str, &str)> {
+        let op = ["==", "!=", "<=", ">=", "<", ">"]
+            .iter()
+            .find(|op| condition.contains(*op))?;
+        let (lhs, rhs) = condition.split_once(op)?;
+        let (lhs, rhs) = (lhs.trim(), rhs.trim());
+        if lhs.is_empty() || rhs.is_empty() {
+            return None;
+        }
+        Some((lhs, op, rhs))
+    }
+
+    /// Parses a signal given by number or by name, with or without the SIG prefix (e.g. 10, SIGUSR1
+    /// or usr1).
+    fn parse_signal(name: &str) -> Option<Signal> {
+        if let Ok(number) = name.parse::<i32>() {
+            return Signal::try_from(number).ok();
+        }
+        let name = name.to_uppercase();
+        if name.starts_with("SIG") {
+            name.parse().ok()
+        } else {
+            format!("SIG{}", name).parse().ok()
+        }
+    }
+
     fn parse_address(addr: &str) -> Option<u64> {
         let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
             &addr[2..]
@@ -190,4 +1417,20 @@ impl Debugger {
         };
         u64::from_str_radix(addr_without_0x, 16).ok()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_condition() {
+        assert_eq!(Debugger::parse_condition("x==5"), Some(("x", "==", "5")));
+        assert_eq!(Debugger::parse_condition("x <= -1"), Some(("x", "<=", "-1")));
+        assert_eq!(Debugger::parse_condition("count != 0x10"), Some(("count", "!=", "0x10")));
+        assert_eq!(Debugger::parse_condition("i>3"), Some(("i", ">", "3")));
+        // Both operands are required
+        assert_eq!(Debugger::parse_condition("x =="), None);
+        assert_eq!(Debugger::parse_condition("< 5"), None);
+        assert_eq!(Debugger::parse_condition("x"), None);
+    }
+}
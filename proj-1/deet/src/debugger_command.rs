@@ -1,9 +1,58 @@
+#[derive(Clone)]
 pub enum DebuggerCommand {
     Quit,
     Run(Vec<String>),
+    Attach(u32),
     Continue,
+    /// Resume the inferior, delivering the given signal to it
+    Signal(String),
     Backtrace,
-    BreakPoint(String),
+    /// A location, and optionally a condition that must hold for the breakpoint to stop
+    BreakPoint(String, Option<String>),
+    /// Like BreakPoint, but deleted after it is first hit
+    TempBreakpoint(String, Option<String>),
+    /// Like BreakPoint, but using a debug register rather than modifying the inferior's code
+    HardwareBreakPoint(String, Option<String>),
+    /// Stop when a Rust program panics
+    CatchPanic,
+    /// Attach commands to a breakpoint (by default, the last one set)
+    Commands(Option<usize>),
+    /// Run the commands in a file
+    Source(String),
+    ListBreakpoints,
+    DeleteBreakpoint(usize),
+    EnableBreakpoint(usize),
+    DisableBreakpoint(usize),
+    /// Stop whenever the named variable changes
+    Watch(String),
+    /// Write a value to a variable, or given *addr, to the 8-byte word at addr
+    SetMemory(String, String),
+    /// Set a register, named without the leading $, in the current thread
+    SetRegister(String, u64),
+    /// Set an environment variable for the inferior, from the next run on
+    SetEnv(String, String),
+    /// Remove an environment variable from the inferior's environment, from the next run on
+    UnsetEnv(String),
+    /// Print the source around a `<file>:<line>` location, or around the current line
+    List(Option<String>),
+    /// Disassemble the function with the given name or containing the given address, or the
+    /// current function
+    Disassemble(Option<String>),
+    InfoRegisters,
+    FrameUp,
+    FrameDown,
+    Frame(usize),
+    InfoThreads,
+    /// Switch to the thread with the given number, as listed by info threads
+    Thread(usize),
+    /// Print one register, named without the leading $
+    PrintRegister(String),
+    /// Print memory, given a format like GDB's (e.g. "4x") and an address
+    Examine(String, String),
+    StepInstruction,
+    Next,
+    Step,
+    Finish,
 }
 
 impl DebuggerCommand {
@@ -16,15 +65,135 @@ impl DebuggerCommand {
                     args.iter().map(|s| s.to_string()).collect(),
                 ))
             },
+            "attach" => {
+                let pid = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::Attach(pid))
+            }
             "c" | "cont" | "continue" => {
                 Some(DebuggerCommand::Continue)
             },
+            "signal" => Some(DebuggerCommand::Signal(tokens.get(1)?.to_string())),
+            "source" => Some(DebuggerCommand::Source(tokens.get(1)?.to_string())),
             "bt" | "back" | "backtrace" => {
                 Some(DebuggerCommand::Backtrace)
             },
-            "b" | "break" => {
-                let arg = tokens[1];
-                Some(DebuggerCommand::BreakPoint(arg.to_string()))
+            "b" | "break" | "tb" | "tbreak" | "hb" | "hbreak" => {
+                let arg = tokens.get(1)?;
+                let condition = match tokens.get(2) {
+                    Some(&"if") => Some(tokens[3..].join(" ")),
+                    Some(_) => return None,
+                    None => None,
+                };
+                if tokens[0].starts_with('t') {
+                    Some(DebuggerCommand::TempBreakpoint(arg.to_string(), condition))
+                } else if tokens[0].starts_with('h') {
+                    Some(DebuggerCommand::HardwareBreakPoint(arg.to_string(), condition))
+                } else {
+                    Some(DebuggerCommand::BreakPoint(arg.to_string(), condition))
+                }
+            }
+            "lb" => Some(DebuggerCommand::ListBreakpoints),
+            "commands" => match tokens.get(1) {
+                Some(number) => Some(DebuggerCommand::Commands(Some(number.parse().ok()?))),
+                None => Some(DebuggerCommand::Commands(None)),
+            },
+            "regs" => Some(DebuggerCommand::InfoRegisters),
+            "up" => Some(DebuggerCommand::FrameUp),
+            "down" => Some(DebuggerCommand::FrameDown),
+            "f" | "frame" => {
+                let index = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::Frame(index))
+            }
+            "thread" => {
+                let index = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::Thread(index))
+            }
+            "disas" | "disassemble" => {
+                let location = tokens.get(1).map(|location| location.to_string());
+                Some(DebuggerCommand::Disassemble(location))
+            }
+            "l" | "list" => {
+                let location = tokens.get(1).map(|location| location.to_string());
+                Some(DebuggerCommand::List(location))
+            }
+            "p" | "print" => {
+                let register = tokens.get(1)?.strip_prefix('$')?;
+                Some(DebuggerCommand::PrintRegister(register.to_string()))
+            }
+            "info" => match tokens.get(1) {
+                Some(&"b") | Some(&"break") | Some(&"breakpoints") => {
+                    Some(DebuggerCommand::ListBreakpoints)
+                }
+                Some(&"r") | Some(&"reg") | Some(&"registers") => {
+                    Some(DebuggerCommand::InfoRegisters)
+                }
+                Some(&"threads") => Some(DebuggerCommand::InfoThreads),
+                _ => None,
+            },
+            "d" | "delete" => {
+                let number = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::DeleteBreakpoint(number))
+            }
+            "enable" => {
+                let number = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::EnableBreakpoint(number))
+            }
+            "disable" => {
+                let number = tokens.get(1)?.parse().ok()?;
+                Some(DebuggerCommand::DisableBreakpoint(number))
+            }
+            "watch" => {
+                let var_name = tokens.get(1)?;
+                Some(DebuggerCommand::Watch(var_name.to_string()))
+            }
+            "catch" => match tokens.get(1) {
+                Some(&"panic") => Some(DebuggerCommand::CatchPanic),
+                _ => None,
+            },
+            "set" if tokens.get(1) == Some(&"env") => {
+                let assignment = tokens[2..].join(" ");
+                let (key, val) = assignment.split_once('=')?;
+                let key = key.trim();
+                if key.is_empty() {
+                    return None;
+                }
+                Some(DebuggerCommand::SetEnv(key.to_string(), val.trim().to_string()))
+            }
+            "unset" => match tokens.get(1) {
+                Some(&"env") => Some(DebuggerCommand::UnsetEnv(tokens.get(2)?.to_string())),
+                _ => None,
+            },
+            "set" => {
+                let assignment = tokens[1..].join(" ");
+                let (target, value) = assignment.split_once('=')?;
+                let (target, value) = (target.trim(), value.trim());
+                if target.is_empty() || value.is_empty() {
+                    return None;
+                }
+                match target.strip_prefix('$') {
+                    Some(name) => {
+                        let value = parse_value(value)? as u64;
+                        Some(DebuggerCommand::SetRegister(name.to_string(), value))
+                    }
+                    None => Some(DebuggerCommand::SetMemory(target.to_string(), value.to_string())),
+                }
+            }
+            cmd if cmd == "x" || cmd.starts_with("x/") => {
+                let format = cmd.trim_start_matches('x').trim_start_matches('/');
+                let addr = tokens.get(1)?;
+                Some(DebuggerCommand::Examine(format.to_string(), addr.to_string()))
+            }
+            "si" | "stepi" => {
+                Some(DebuggerCommand::StepInstruction)
+            }
+            "n" | "next" => {
+                Some(DebuggerCommand::Next)
+            }
+            "s" | "step" => {
+                Some(DebuggerCommand::Step)
+            }
+            "fin" | "finish" => {
+                Some(DebuggerCommand::Finish)
             }
             // Default case:
             _ => None,
@@ -32,3 +201,58 @@ impl DebuggerCommand {
     }
 
 }
+
+/// Parses a number given in decimal, or in hex with a leading 0x.
+pub fn parse_value(value: &str) -> Option<i64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|value| value as i64),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Option<DebuggerCommand> {
+        DebuggerCommand::from_tokens(&line.split_whitespace().collect())
+    }
+
+    #[test]
+    fn test_breakpoint_condition() {
+        assert!(matches!(
+            parse("break main if x==5"),
+            Some(DebuggerCommand::BreakPoint(location, Some(condition)))
+                if location == "main" && condition == "x==5"
+        ));
+        assert!(matches!(
+            parse("tbreak loop.c:7 if x <= -1"),
+            Some(DebuggerCommand::TempBreakpoint(location, Some(condition)))
+                if location == "loop.c:7" && condition == "x <= -1"
+        ));
+        assert!(matches!(
+            parse("hb *0x401136"),
+            Some(DebuggerCommand::HardwareBreakPoint(location, None)) if location == "*0x401136"
+        ));
+        // Anything after the location other than a condition is an error
+        assert!(parse("break main x==5").is_none());
+        assert!(parse("break").is_none());
+    }
+
+    #[test]
+    fn test_env() {
+        // Only the first = separates the name from the value
+        assert!(matches!(
+            parse("set env A=b=c"),
+            Some(DebuggerCommand::SetEnv(key, val)) if key == "A" && val == "b=c"
+        ));
+        assert!(matches!(
+            parse("set env A ="),
+            Some(DebuggerCommand::SetEnv(key, val)) if key == "A" && val.is_empty()
+        ));
+        assert!(parse("set env =x").is_none());
+        assert!(parse("set env A").is_none());
+        assert!(matches!(parse("unset env A"), Some(DebuggerCommand::UnsetEnv(key)) if key == "A"));
+        assert!(parse("unset env").is_none());
+    }
+}
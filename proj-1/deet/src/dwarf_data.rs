@@ -12,6 +12,8 @@ pub enum Error {
 
 pub struct DwarfData {
     files: Vec<File>,
+    /// Whether to demangle the function names we hand out
+    demangle: bool,
     addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
 }
 
@@ -40,14 +42,23 @@ impl DwarfData {
         };
         Ok(DwarfData {
             files: gimli_wrapper::load_file(&object, endian)?,
+            demangle: true,
             addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
         })
     }
 
+    /// Sets whether function names are shown demangled (the default) or as raw symbols.
+    pub fn set_demangle(&mut self, demangle: bool) {
+        self.demangle = demangle;
+    }
+
     #[allow(dead_code)]
     fn get_target_file(&self, file: &str) -> Option<&File> {
         self.files.iter().find(|f| {
-            f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
+            f.name == file
+                || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
+                // addr2line gives full paths, while the compile unit may use a relative one
+                || file.ends_with(&format!("/{}", f.name))
         })
     }
 
@@ -73,12 +84,16 @@ impl DwarfData {
                 self.get_target_file(filename)?
                     .functions
                     .iter()
-                    .find(|func| func.name == func_name)?
+                    .find(|func| func.matches(func_name))?
                     .address,
             ),
             None => {
                 for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
+                    if let Some(func) = file
+                        .functions
+                        .iter()
+                        .find(|func| func.matches(func_name) && func.address != 0)
+                    {
                         return Some(func.address);
                     }
                 }
@@ -87,6 +102,21 @@ impl DwarfData {
         }
     }
 
+    /// Returns the names of all the functions with code in the target, sorted and without
+    /// duplicates.
+    pub fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .filter(|func| func.address != 0 && !func.name.is_empty())
+            .map(|func| func.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
     #[allow(dead_code)]
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
         let location = self
@@ -100,6 +130,17 @@ impl DwarfData {
         })
     }
 
+    /// Returns the start and end addresses of a function's code.
+    #[allow(dead_code)]
+    pub fn get_function_range(&self, func_name: &str) -> Option<(usize, usize)> {
+        let func = self
+            .files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| func.matches(func_name) && func.address != 0)?;
+        Some((func.address, func.address + func.text_length))
+    }
+
     #[allow(dead_code)]
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
         let frame = self
@@ -108,7 +149,31 @@ impl DwarfData {
             .ok()?
             .next()
             .ok()??;
-        Some(frame.function?.raw_name().ok()?.to_string())
+        let name = frame.function?.raw_name().ok()?.to_string();
+        if self.demangle {
+            Some(demangle(&name))
+        } else {
+            Some(name)
+        }
+    }
+
+    /// Looks up a variable by name as seen from inside func_name: one of the function's
+    /// parameters or local variables, or failing that, a global variable.
+    #[allow(dead_code)]
+    pub fn get_variable(&self, func_name: Option<&str>, var_name: &str) -> Option<&Variable> {
+        let local = self
+            .files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .filter(|func| func_name.map_or(false, |name| func.matches(name)))
+            .flat_map(|func| func.variables.iter())
+            .find(|var| var.name == var_name);
+        local.or_else(|| {
+            self.files
+                .iter()
+                .flat_map(|file| file.global_variables.iter())
+                .find(|var| var.name == var_name)
+        })
     }
 
     #[allow(dead_code)]
@@ -196,12 +261,35 @@ pub struct Variable {
 #[derive(Debug, Default, Clone)]
 pub struct Function {
     pub name: String,
+    /// The symbol name (e.g. _ZN4core3ptr...), for languages that mangle them. Empty otherwise.
+    pub linkage_name: String,
     pub address: usize,
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
     pub variables: Vec<Variable>,
 }
 
+impl Function {
+    /// Whether name refers to this function: either its plain name, its symbol name, or (the tail
+    /// end of) the demangled form of its symbol name.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name
+            || (!self.linkage_name.is_empty() && {
+                // Allow leaving off the leading module path, e.g. Counter<T>::add for
+                // generics::Counter<T>::add
+                let demangled = demangle(&self.linkage_name);
+                self.linkage_name == name
+                    || demangled == name
+                    || demangled.ends_with(&format!("::{}", name))
+            })
+    }
+}
+
+/// Demangles a Rust symbol name, leaving off the trailing hash. Other names are returned as-is.
+fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct File {
     pub name: String,
@@ -223,4 +311,37 @@ impl fmt::Display for Line {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The symbol rustc gives `Counter<T>::add` in samples/generics.rs.
+    const COUNTER_ADD: &str = "_ZN8generics16Counter$LT$T$GT$3add17h1f4c4805c8a96e99E";
 
+    #[test]
+    fn test_demangle() {
+        assert_eq!(demangle(COUNTER_ADD), "generics::Counter<T>::add");
+        // C names aren't mangled
+        assert_eq!(demangle("main"), "main");
+    }
+
+    #[test]
+    fn test_function_matches() {
+        let add = Function {
+            name: "add".to_string(),
+            linkage_name: COUNTER_ADD.to_string(),
+            ..Default::default()
+        };
+        assert!(add.matches("add"));
+        assert!(add.matches(COUNTER_ADD));
+        assert!(add.matches("generics::Counter<T>::add"));
+        assert!(add.matches("Counter<T>::add"));
+        assert!(!add.matches("Counter<T>::new"));
+        // Suffixes must start at a path separator
+        assert!(!add.matches("unter<T>::add"));
+
+        let main = Function { name: "main".to_string(), ..Default::default() };
+        assert!(main.matches("main"));
+        assert!(!main.matches("ain"));
+    }
+}
@@ -41,6 +41,9 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
 
     // Define a mapping from type offsets to type structs
     let mut offset_to_type: HashMap<usize, Type> = HashMap::new();
+    // Define a mapping from function offsets to their names, for definitions that refer back to
+    // an earlier declaration (as Rust methods do) instead of repeating the names
+    let mut offset_to_function_names: HashMap<usize, (String, String)> = HashMap::new();
 
     let mut compilation_units: Vec<File> = Vec::new();
 
@@ -103,6 +106,7 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                 }
                 gimli::DW_TAG_subprogram => {
                     let mut func: Function = Default::default();
+                    let mut specification = None;
                     let mut attrs = entry.attrs();
                     while let Some(attr) = attrs.next()? {
                         let val = get_attr_value(&attr, &unit, &dwarf);
@@ -113,6 +117,16 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.name = name;
                                 }
                             }
+                            gimli::DW_AT_linkage_name | gimli::DW_AT_MIPS_linkage_name => {
+                                if let Ok(DebugValue::Str(name)) = val {
+                                    func.linkage_name = name;
+                                }
+                            }
+                            gimli::DW_AT_specification => {
+                                if let Ok(DebugValue::Size(offset)) = val {
+                                    specification = Some(offset);
+                                }
+                            }
                             gimli::DW_AT_high_pc => {
                                 if let Ok(DebugValue::Uint(high_pc)) = val {
                                     func.text_length = high_pc.try_into().unwrap();
@@ -132,6 +146,20 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             _ => {}
                         }
                     }
+                    if let Some(names) = specification.and_then(|o| offset_to_function_names.get(&o)) {
+                        if func.name.is_empty() {
+                            func.name = names.0.clone();
+                        }
+                        if func.linkage_name.is_empty() {
+                            func.linkage_name = names.1.clone();
+                        }
+                    }
+                    let func_offset = match entry.offset().to_unit_section_offset(&unit) {
+                        UnitSectionOffset::DebugInfoOffset(goff) => goff.0,
+                        UnitSectionOffset::DebugTypesOffset(goff) => goff.0,
+                    };
+                    offset_to_function_names
+                        .insert(func_offset, (func.name.clone(), func.linkage_name.clone()));
                     compilation_units.last_mut().unwrap().functions.push(func);
                 }
                 gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
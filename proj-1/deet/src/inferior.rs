@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Error;
 use std::mem::size_of;
 use std::os::unix::process::CommandExt;
+use nix::errno::Errno;
 use nix::sys::ptrace;
 use nix::sys::signal;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
@@ -12,7 +13,8 @@ use libc::{ptrace, wait};
 use nix::sys::ptrace::traceme;
 use nix::sys::signal::Signal;
 use crate::dwarf_data;
-use crate::dwarf_data::DwarfData;
+use crate::core_file::CoreFile;
+use crate::dwarf_data::{DwarfData, Location, Type, Variable};
 
 pub enum Status {
     /// Indicates inferior stopped. Contains the signal that stopped the process, as well as the
@@ -27,6 +29,15 @@ pub enum Status {
     Signaled(signal::Signal),
 }
 
+/// si_code values of a SIGTRAP raised by an int3 instruction. x86 Linux reports SI_KERNEL, but
+/// TRAP_BRKPT is what the trap "should" be, so accept both.
+const SI_KERNEL: i32 = 0x80;
+const TRAP_BRKPT: i32 = 1;
+
+/// Where the debug registers are found in struct user on x86-64, which is what the offsets given
+/// to PTRACE_PEEKUSER and PTRACE_POKEUSER are relative to.
+const DEBUG_REG_OFFSET: usize = 848;
+
 /// This function calls ptrace with PTRACE_TRACEME to enable debugging on a process. You should use
 /// pre_exec with Command to call this in the child process.
 fn child_traceme() -> Result<(), std::io::Error> {
@@ -36,22 +47,66 @@ fn child_traceme() -> Result<(), std::io::Error> {
     )))
 }
 
+/// The process being debugged: either one we started, or an existing one we attached to.
+enum Process {
+    Child(Child),
+    Attached(Pid),
+    /// A process that crashed, as recorded in a core file. It can be inspected but not run.
+    Core(CoreFile),
+}
+
 pub struct Inferior {
-    child: Child,
+    process: Process,
     breakpoint: HashMap<u64, u8>,
+    /// Addresses of the watchpoints in each of the debug registers DR0-DR3
+    watchpoints: [Option<u64>; 4],
+    /// Addresses of the hardware breakpoints in each of the debug registers DR0-DR3
+    hw_breakpoints: [Option<u64>; 4],
+    /// Every thread in the process, main thread first
+    threads: Vec<Pid>,
+    /// The thread that commands apply to: whose registers are shown, and which gets stepped
+    current_thread: Pid,
+    /// Whether every thread was resumed, as opposed to just the current one being stepped
+    all_running: bool,
+    /// Threads that were sent a SIGSTOP but stopped for some other reason first. The SIGSTOP will
+    /// show up once they resume, and is ignored then.
+    pending_stops: HashSet<Pid>,
+    /// Signals that threads stopped with while we were stopping them, to pass on when they resume
+    pending_signals: HashMap<Pid, Signal>,
 }
 
 impl Inferior {
-    /// Attempts to start a new inferior process. Returns Some(Inferior) if successful, or None if
-    /// an error is encountered.
-    pub fn new(target: &str, args: &Vec<String>, breakpoints: &Vec<u64>) -> Option<Inferior> {
+    /// Attempts to start a new inferior process, or if a pid is given, to attach to that process
+    /// instead (in which case target and args are ignored). Returns Some(Inferior) if successful,
+    /// or None if an error is encountered.
+    pub fn new(
+        target: &str,
+        args: &Vec<String>,
+        breakpoints: &Vec<u64>,
+        env: &HashMap<String, Option<String>>,
+        pid: Option<Pid>,
+    ) -> Option<Inferior> {
         unsafe {
-            let child = Command::new(target)
-                .args(args)
-                .pre_exec(child_traceme)
-                .spawn()
-                .ok()?;
-            let mut i = Inferior { child, breakpoint: HashMap::new() };
+            let (process, expected_signal) = match pid {
+                Some(pid) => {
+                    // Attaching sends the process a SIGSTOP
+                    ptrace::attach(pid).ok()?;
+                    (Process::Attached(pid), Signal::SIGSTOP)
+                }
+                None => {
+                    let mut command = Command::new(target);
+                    command.args(args).pre_exec(child_traceme);
+                    for (key, val) in env {
+                        match val {
+                            Some(val) => command.env(key, val),
+                            None => command.env_remove(key),
+                        };
+                    }
+                    let child = command.spawn().ok()?;
+                    (Process::Child(child), Signal::SIGTRAP)
+                }
+            };
+            let mut i = Inferior::from_process(process);
             // When a process that has PTRACE_TRACEME enabled calls exec, the OS will load the specified program into the process,
             // and then, before the program starts running, it will pause the process with SIGTRAP.
             let status = i.wait(None).ok()?;
@@ -61,95 +116,650 @@ impl Inferior {
                 }
                 _ => None
             }?;
+            // Have new threads traced too
+            ptrace::setoptions(i.pid(), ptrace::Options::PTRACE_O_TRACECLONE).ok()?;
+            if i.is_attached() {
+                i.attach_other_threads().ok()?;
+            }
             for addr in breakpoints {
-                i.write_byte(*addr, 0xcc).unwrap();
+                // Several breakpoints can share an address, but there is only one int3 to insert
+                if i.original_byte(*addr).is_none() {
+                    i.write_byte(*addr, 0xcc).unwrap();
+                }
             }
             // wait until child process turns its status to Stopped
-            match signal {
-                Signal::SIGTRAP => {
-                    Some(i)
-                }
-                _ => None
+            if signal == expected_signal {
+                Some(i)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn from_process(process: Process) -> Inferior {
+        let mut inferior = Inferior {
+            process,
+            breakpoint: HashMap::new(),
+            watchpoints: [None; 4],
+            hw_breakpoints: [None; 4],
+            threads: Vec::new(),
+            current_thread: Pid::from_raw(0),
+            all_running: false,
+            pending_stops: HashSet::new(),
+            pending_signals: HashMap::new(),
+        };
+        let pid = inferior.pid();
+        inferior.threads.push(pid);
+        inferior.current_thread = pid;
+        inferior
+    }
+
+    /// Attaches to the threads of an attached process other than its main thread.
+    fn attach_other_threads(&mut self) -> Result<(), nix::Error> {
+        let entries = match std::fs::read_dir(format!("/proc/{}/task", self.pid())) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries.flatten() {
+            let tid = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(tid) => Pid::from_raw(tid),
+                None => continue,
+            };
+            if tid == self.pid() {
+                continue;
             }
+            ptrace::attach(tid)?;
+            waitpid(tid, Some(WaitPidFlag::__WALL))?;
+            ptrace::setoptions(tid, ptrace::Options::PTRACE_O_TRACECLONE)?;
+            self.threads.push(tid);
         }
+        Ok(())
     }
 
     /// Returns the pid of this inferior.
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
-    }
-
-    /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
-    /// after the waitpid call.
-    pub fn wait(&self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
-        Ok(match waitpid(self.pid(), options)? {
-            WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
-            WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
-            WaitStatus::Stopped(_pid, signal) => {
-                let regs = ptrace::getregs(self.pid())?;
-                Status::Stopped(signal, regs.rip as usize)
+        match &self.process {
+            Process::Child(child) => nix::unistd::Pid::from_raw(child.id() as i32),
+            Process::Attached(pid) => *pid,
+            Process::Core(core) => nix::unistd::Pid::from_raw(core.pid()),
+        }
+    }
+
+    /// Loads a core file as an inferior. Returns the inferior along with the signal that killed
+    /// the process, if known.
+    pub fn from_core(path: &str) -> Result<(Inferior, Option<Signal>), String> {
+        let core = CoreFile::open(path)?;
+        let signal = core.signal();
+        let inferior = Inferior::from_process(Process::Core(core));
+        Ok((inferior, signal))
+    }
+
+    /// Returns true if we attached to this inferior rather than starting it.
+    pub fn is_attached(&self) -> bool {
+        match self.process {
+            Process::Attached(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this inferior comes from a core file, and so can't be run.
+    pub fn is_core(&self) -> bool {
+        match self.process {
+            Process::Core(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the registers of the current thread.
+    pub fn regs(&self) -> Result<libc::user_regs_struct, nix::Error> {
+        self.thread_regs(self.current_thread)
+    }
+
+    /// Returns the registers of one of the inferior's threads.
+    pub fn thread_regs(&self, tid: Pid) -> Result<libc::user_regs_struct, nix::Error> {
+        match &self.process {
+            Process::Core(core) => Ok(core.regs()),
+            _ => ptrace::getregs(tid),
+        }
+    }
+
+    /// Sets one of the current thread's registers, named as in register_value.
+    pub fn set_register(&mut self, reg: &str, val: u64) -> Result<(), nix::Error> {
+        let mut regs = ptrace::getregs(self.current_thread)?;
+        *register_mut(&mut regs, reg).ok_or(nix::Error::Sys(Errno::EINVAL))? = val;
+        ptrace::setregs(self.current_thread, regs)
+    }
+
+    /// Returns the inferior's threads, main thread first.
+    pub fn threads(&self) -> &[Pid] {
+        &self.threads
+    }
+
+    /// Returns the thread that commands apply to.
+    pub fn current_thread(&self) -> Pid {
+        self.current_thread
+    }
+
+    /// Makes one of the inferior's threads the one that commands apply to.
+    pub fn select_thread(&mut self, tid: Pid) {
+        self.current_thread = tid;
+    }
+
+    /// Waits for the inferior to stop or exit and returns a Status to indicate the state of the
+    /// process. Threads starting and exiting are dealt with along the way. The thread that stops
+    /// becomes the current thread, and the other threads are stopped too.
+    pub fn wait(&mut self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
+        let options = options.unwrap_or_else(WaitPidFlag::empty) | WaitPidFlag::__WALL;
+        loop {
+            match waitpid(None, Some(options))? {
+                WaitStatus::Exited(tid, exit_code) => {
+                    if tid == self.pid() {
+                        return Ok(Status::Exited(exit_code));
+                    }
+                    self.forget_thread(tid);
+                }
+                WaitStatus::Signaled(tid, signal, _core_dumped) => {
+                    if tid == self.pid() {
+                        return Ok(Status::Signaled(signal));
+                    }
+                    self.forget_thread(tid);
+                }
+                WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_CLONE) => {
+                    let new_thread = self.add_cloned_thread(tid)?;
+                    if self.all_running {
+                        ptrace::cont(new_thread, None)?;
+                        ptrace::cont(tid, None)?;
+                    } else {
+                        // Only the current thread is being stepped; the new one waits its turn
+                        ptrace::step(tid, None)?;
+                    }
+                }
+                WaitStatus::Stopped(tid, Signal::SIGSTOP) if !self.threads.contains(&tid) => {
+                    // A new thread can stop before its parent reports the clone, which is when
+                    // it gets resumed
+                    self.threads.push(tid);
+                }
+                WaitStatus::Stopped(tid, Signal::SIGSTOP) if self.pending_stops.remove(&tid) => {
+                    ptrace::cont(tid, None)?;
+                }
+                WaitStatus::Stopped(tid, signal) => {
+                    self.current_thread = tid;
+                    let rip = self.rewind_breakpoint(tid, signal)?;
+                    if self.all_running {
+                        self.stop_other_threads()?;
+                        self.all_running = false;
+                    }
+                    return Ok(Status::Stopped(signal, rip as usize));
+                }
+                other => panic!("waitpid returned unexpected status: {:?}", other),
             }
-            other => panic!("waitpid returned unexpected status: {:?}", other),
-        })
+        }
+    }
+
+    /// Starts tracking the thread that tid just created, once it has stopped for the first time.
+    /// The new thread is left stopped.
+    fn add_cloned_thread(&mut self, tid: Pid) -> Result<Pid, nix::Error> {
+        let new_thread = Pid::from_raw(ptrace::getevent(tid)? as i32);
+        if !self.threads.contains(&new_thread) {
+            waitpid(new_thread, Some(WaitPidFlag::__WALL))?;
+            self.threads.push(new_thread);
+        }
+        Ok(new_thread)
+    }
+
+    fn forget_thread(&mut self, tid: Pid) {
+        self.threads.retain(|&thread| thread != tid);
+        self.pending_stops.remove(&tid);
+        self.pending_signals.remove(&tid);
+        if self.current_thread == tid {
+            self.current_thread = self.pid();
+        }
+    }
+
+    /// After a thread hits one of our breakpoints, its rip is one past the int3. This backs it up
+    /// so that rip points at the breakpoint, whose original instruction has yet to run. Returns
+    /// the thread's rip.
+    fn rewind_breakpoint(&self, tid: Pid, signal: Signal) -> Result<u64, nix::Error> {
+        let mut regs = ptrace::getregs(tid)?;
+        if signal == Signal::SIGTRAP
+            && self.breakpoint.contains_key(&(regs.rip - 1))
+            && self.trapped_by_int3(tid)?
+        {
+            regs.rip -= 1;
+            ptrace::setregs(tid, regs)?;
+        }
+        Ok(regs.rip)
+    }
+
+    /// Stops every thread other than the current one, which has just stopped.
+    fn stop_other_threads(&mut self) -> Result<(), nix::Error> {
+        for tid in self.threads.clone() {
+            if tid == self.current_thread {
+                continue;
+            }
+            // nix has no tgkill, and kill would signal the whole process
+            unsafe {
+                libc::syscall(libc::SYS_tgkill, self.pid().as_raw(), tid.as_raw(), libc::SIGSTOP);
+            }
+            loop {
+                match waitpid(tid, Some(WaitPidFlag::__WALL))? {
+                    WaitStatus::Stopped(_, Signal::SIGSTOP) => break,
+                    WaitStatus::Stopped(_, signal) => {
+                        // The thread got somewhere first. If that was a breakpoint, it will hit it
+                        // again when it resumes.
+                        self.pending_stops.insert(tid);
+                        self.rewind_breakpoint(tid, signal)?;
+                        if signal != Signal::SIGTRAP {
+                            self.pending_signals.insert(tid, signal);
+                        }
+                        break;
+                    }
+                    WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_CLONE) => {
+                        self.add_cloned_thread(tid)?;
+                        // Let the SIGSTOP through
+                        ptrace::cont(tid, None)?;
+                    }
+                    _ => {
+                        self.forget_thread(tid);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the thread's last SIGTRAP came from executing an int3 instruction (as
+    /// opposed to e.g. finishing a single step).
+    fn trapped_by_int3(&self, tid: Pid) -> Result<bool, nix::Error> {
+        let si_code = ptrace::getsiginfo(tid)?.si_code;
+        Ok(si_code == SI_KERNEL || si_code == TRAP_BRKPT)
     }
 
     // Normally, SIGINT (triggered by Ctrl-C) will terminate a process, but if a process is being traced under ptrace,
     // SIGINT will cause it to temporarily stop instead, as if it were sent SIGSTOP.
     /// Calls cont on this inferior to get the stopped child process start executing again.
-    pub fn cont(&mut self) -> Result<(), Error> {
-        let mut regs = ptrace::getregs(self.pid()).unwrap();
-        let rip = regs.rip as u64;
-        let mut addr: u64 = 0;
-        let mut origin: u8 = 0;
-        for (k, v) in &self.breakpoint {
-            if *k == rip - 1 {
-                addr = *k;
-                origin = *v;
+    pub fn cont(&mut self, signal: Option<Signal>) -> Result<(), Error> {
+        let rip = self.regs().unwrap().rip as u64;
+        // If we're stopped on a breakpoint, its original instruction has to run before the int3
+        // goes back in (or for a hardware breakpoint, before the CPU would just stop again)
+        if self.breakpoint.contains_key(&rip) || self.hw_breakpoint_slot(rip).is_some() {
+            if let Ok(Status::Exited(exit_code)) = self.step_instruction() {
+                println!("Child exited (status {})", exit_code);
+                return Ok(());
+            }
+        }
+        for tid in self.threads.clone() {
+            let signal = if tid == self.current_thread {
+                signal
+            } else {
+                self.pending_signals.remove(&tid)
+            };
+            ptrace::cont(tid, signal).or(Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "ptrace cont failed",
+            )))?;
+        }
+        self.all_running = true;
+        Ok(())
+    }
+
+    /// Executes a single machine instruction in the current thread. The caller should wait for
+    /// the inferior to stop again afterwards.
+    pub fn step(&self) -> Result<(), nix::Error> {
+        ptrace::step(self.current_thread, None)
+    }
+
+    /// Returns the byte that the breakpoint at addr replaced, if there is one.
+    pub fn original_byte(&self, addr: u64) -> Option<u8> {
+        self.breakpoint.get(&addr).copied()
+    }
+
+    /// Executes a single machine instruction and waits for the inferior to stop. If the inferior
+    /// is stopped on one of our breakpoints, the original byte is put back for the step and the
+    /// breakpoint is re-inserted afterwards. Likewise, a hardware breakpoint there is switched off
+    /// for the step.
+    pub fn step_instruction(&mut self) -> Result<Status, nix::Error> {
+        let rip = self.regs()?.rip as u64;
+        let on_breakpoint = self.breakpoint.contains_key(&rip);
+        if on_breakpoint {
+            let orig_byte = self.original_byte(rip).unwrap();
+            self.write_byte(rip, orig_byte)?;
+        }
+        let hw_slot = self.hw_breakpoint_slot(rip);
+        if let Some(slot) = hw_slot {
+            self.enable_debug_slot(slot, false)?;
+        }
+        self.step()?;
+        let status = self.wait(None)?;
+        if let Status::Stopped(..) = status {
+            if on_breakpoint {
+                self.write_byte(rip, 0xcc)?;
+            }
+            if let Some(slot) = hw_slot {
+                self.enable_debug_slot(slot, true)?;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Puts back the byte that the breakpoint at addr replaced and forgets about the breakpoint.
+    pub fn remove_breakpoint(&mut self, addr: u64) -> Result<(), nix::Error> {
+        if let Some(orig_byte) = self.original_byte(addr) {
+            self.write_byte(addr, orig_byte)?;
+            self.breakpoint.remove(&addr);
+        }
+        Ok(())
+    }
+
+    /// Calls kill on this inferior to kill it and reap the process.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        match &mut self.process {
+            Process::Child(child) => child.kill(),
+            Process::Attached(_) => self
+                .signal(Signal::SIGKILL)
+                .or(Err(std::io::Error::new(std::io::ErrorKind::Other, "kill failed"))),
+            Process::Core(_) => Ok(()),
+        }
+    }
+
+    /// Sends a signal to the inferior. If the inferior is stopped, the signal is only seen once it
+    /// resumes, and the inferior will stop again (with that signal) right away.
+    pub fn signal(&mut self, sig: Signal) -> Result<(), nix::Error> {
+        signal::kill(self.pid(), sig)
+    }
+
+    /// Takes out our breakpoints and watchpoints and lets the inferior carry on without us.
+    pub fn detach(&mut self) -> Result<(), nix::Error> {
+        let addrs: Vec<u64> = self.breakpoint.keys().copied().collect();
+        for addr in addrs {
+            self.remove_breakpoint(addr)?;
+        }
+        if self.watchpoints.iter().chain(&self.hw_breakpoints).any(|addr| addr.is_some()) {
+            self.write_debug_reg(7, 0)?;
+            self.watchpoints = [None; 4];
+            self.hw_breakpoints = [None; 4];
+        }
+        for &tid in &self.threads {
+            ptrace::detach(tid, None)?;
+        }
+        Ok(())
+    }
+
+    /// Finds where on the stack the return address of the function the inferior is stopped in is
+    /// kept. Returns that address and the caller's frame pointer.
+    fn return_slot(&self, dwarf_data: &DwarfData) -> Result<(u64, u64), nix::Error> {
+        let regs = self.regs()?;
+        let pc = regs.rip;
+        let entry = dwarf_data
+            .get_function_from_addr(pc as usize)
+            .and_then(|name| dwarf_data.get_addr_for_function(None, &name))
+            .map(|addr| addr as u64);
+        // On entry the return address is on top of the stack, and rbp still belongs to the caller.
+        // Once the prologue has pushed rbp and set up the frame pointer, the return address sits
+        // just above the saved rbp.
+        let (slot, caller_rbp) = match entry {
+            Some(entry) if pc == entry => (regs.rsp, regs.rbp),
+            Some(entry) if pc == entry + 1 => (regs.rsp + 8, regs.rbp),
+            _ => (regs.rbp + 8, self.read_word(regs.rbp)?),
+        };
+        Ok((slot, caller_rbp))
+    }
+
+    /// Finds the caller of the function the inferior is stopped in. Returns the return address
+    /// and the caller's frame pointer.
+    pub fn caller_frame(&self, dwarf_data: &DwarfData) -> Result<(usize, usize), nix::Error> {
+        let (slot, caller_rbp) = self.return_slot(dwarf_data)?;
+        Ok((self.read_word(slot)? as usize, caller_rbp as usize))
+    }
+
+    /// Lists the frames on the call stack, innermost first, by following the chain of saved
+    /// frame pointers. The walk stops at main, or when the chain ends or leads to unreadable
+    /// memory.
+    pub fn frames(&self, dwarf_data: &DwarfData) -> Result<Vec<Frame>, nix::Error> {
+        let regs = self.regs()?;
+        let (slot, caller_rbp) = self.return_slot(dwarf_data)?;
+        let mut frame = Frame {
+            rip: regs.rip,
+            rsp: regs.rsp,
+            rbp: regs.rbp,
+            cfa: slot + 8,
+        };
+        let mut caller_rbp = Ok(caller_rbp);
+        let mut frames = Vec::new();
+        loop {
+            frames.push(frame);
+            // Beyond main is libc, which doesn't keep frame pointers
+            if dwarf_data.get_function_from_addr(frame.rip as usize).as_deref() == Some("main") {
                 break;
             }
+            // Callers' frames are further up the stack, so a frame pointer that goes back down
+            // means the chain is garbage (as it is in code that doesn't keep frame pointers)
+            let (return_addr, rbp) = match (self.read_word(frame.cfa - 8), caller_rbp) {
+                (Ok(return_addr), Ok(rbp)) if rbp != 0 && rbp >= frame.cfa => (return_addr, rbp),
+                _ => break,
+            };
+            // Callers are always past their prologue, so their frame pointer is set up
+            frame = Frame {
+                rip: return_addr,
+                rsp: frame.cfa,
+                rbp,
+                cfa: rbp + 16,
+            };
+            caller_rbp = self.read_word(rbp);
+        }
+        Ok(frames)
+    }
+
+    /// Reads an integer of the given type from the inferior's memory.
+    pub fn read_int(&self, addr: u64, entity_type: &Type) -> Result<i64, nix::Error> {
+        let word = self.read_word(addr)?;
+        let bits = 8 * entity_type.size.max(1).min(8) as u32;
+        if bits == 64 {
+            Ok(word as i64)
+        } else if entity_type.name.contains("unsigned") {
+            Ok((word & ((1 << bits) - 1)) as i64)
+        } else {
+            Ok(((word << (64 - bits)) as i64) >> (64 - bits))
         }
-        if addr != 0 {
-            self.write_byte(addr, origin).unwrap();
-            regs.rip = rip - 1;
-            ptrace::setregs(self.pid(), regs).unwrap();
-            ptrace::step(self.pid(), None).unwrap();
-            match self.wait(None) {
-                Ok(Status::Exited(exit_code)) => {
-                    println!("Child exited (status {})", exit_code);
-                    return Ok(());
+    }
+
+    pub fn read_word(&self, addr: u64) -> Result<u64, nix::Error> {
+        match &self.process {
+            Process::Core(core) => core.read_word(addr).ok_or(nix::Error::Sys(Errno::EIO)),
+            _ => Ok(ptrace::read(self.pid(), addr as ptrace::AddressType)? as u64),
+        }
+    }
+
+    /// Reads count bytes of the inferior's memory starting at addr.
+    pub fn read_memory(&self, addr: u64, count: usize) -> Result<Vec<u8>, nix::Error> {
+        let start = align_addr_to_word(addr);
+        let mut bytes = Vec::new();
+        let mut word_addr = start;
+        while word_addr < addr + count as u64 {
+            bytes.extend_from_slice(&self.read_word(word_addr)?.to_le_bytes());
+            word_addr += size_of::<u64>() as u64;
+        }
+        let offset = (addr - start) as usize;
+        Ok(bytes[offset..offset + count].to_vec())
+    }
+
+    /// Writes an 8-byte word to the inferior's memory.
+    pub fn write_word(&mut self, addr: u64, val: u64) -> Result<(), nix::Error> {
+        self.write_memory(addr, &val.to_le_bytes())
+    }
+
+    /// Writes bytes to the inferior's memory starting at addr. Our breakpoints stay in place: if
+    /// one is in the way, the byte it replaced is what gets changed.
+    pub fn write_memory(&mut self, addr: u64, bytes: &[u8]) -> Result<(), nix::Error> {
+        let end = addr + bytes.len() as u64;
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < end {
+            let mut word = self.read_word(word_addr)?.to_le_bytes();
+            for (i, byte) in word.iter_mut().enumerate() {
+                let byte_addr = word_addr + i as u64;
+                if byte_addr < addr || byte_addr >= end {
+                    continue;
+                }
+                let val = bytes[(byte_addr - addr) as usize];
+                match self.breakpoint.get_mut(&byte_addr) {
+                    Some(orig_byte) => *orig_byte = val,
+                    None => *byte = val,
                 }
-                Ok(Status::Stopped(Signal::SIGTRAP, rip)) => {
-                    println!("stop at rip {:#x}", rip);
-                    self.write_byte(addr, 0xcc).unwrap();
+            }
+            ptrace::write(
+                self.pid(),
+                word_addr as ptrace::AddressType,
+                u64::from_le_bytes(word) as *mut std::ffi::c_void,
+            )?;
+            word_addr += size_of::<u64>() as u64;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the len bytes at addr lie within one of the inferior's memory mappings.
+    pub fn is_mapped(&self, addr: u64, len: usize) -> bool {
+        let maps = match std::fs::read_to_string(format!("/proc/{}/maps", self.pid())) {
+            Ok(maps) => maps,
+            Err(_) => return false,
+        };
+        // Each line starts with the mapping's range, e.g. "00400000-00452000 r-xp ..."
+        maps.lines().any(|line| {
+            let range = line.split_whitespace().next().and_then(|range| range.split_once('-'));
+            match range {
+                Some((start, end)) => {
+                    let start = u64::from_str_radix(start, 16).unwrap_or(u64::MAX);
+                    let end = u64::from_str_radix(end, 16).unwrap_or(0);
+                    addr >= start && addr + len as u64 <= end
                 }
-                _ => {}
+                None => false,
             }
+        })
+    }
+
+    /// Sets a hardware watchpoint, which stops the inferior with SIGTRAP right after it writes to
+    /// any of the len bytes at addr. len must be 1, 2, 4 or 8, and addr must be a multiple of len.
+    /// Returns the debug register (0-3) holding the watchpoint.
+    pub fn set_watchpoint(&mut self, addr: u64, len: usize) -> Result<usize, nix::Error> {
+        let len_bits: u64 = match len {
+            1 => 0b00,
+            2 => 0b01,
+            4 => 0b11,
+            8 => 0b10,
+            _ => return Err(nix::Error::Sys(Errno::EINVAL)),
+        };
+        if addr % len as u64 != 0 {
+            return Err(nix::Error::Sys(Errno::EINVAL));
         }
-        ptrace::cont(self.pid(), None).or(Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "ptrace cont failed",
-        )))
+        let slot = self.free_debug_slot().ok_or(nix::Error::Sys(Errno::ENOSPC))?;
+        self.write_debug_reg(slot, addr)?;
+        // DR7 has a local enable bit for each slot, and from bit 16 on, four bits of settings per
+        // slot: the condition (01 = break on writes) and then the length
+        let mut dr7 = self.read_debug_reg(7)?;
+        dr7 |= 1 << (2 * slot);
+        dr7 &= !(0b1111 << (16 + 4 * slot));
+        dr7 |= (0b01 | len_bits << 2) << (16 + 4 * slot);
+        self.write_debug_reg(7, dr7)?;
+        self.watchpoints[slot] = Some(addr);
+        Ok(slot)
     }
 
-    /// Calls kill on this inferior to kill it and reap the process.
-    pub fn kill(&mut self) -> std::io::Result<()> {
-        self.child.kill()
-    }
-
-    /// print_backtrace
-    pub fn print_backtrace(&self, dwarf_data: &DwarfData) -> Result<(), nix::Error> {
-        let regs = ptrace::getregs(self.pid())?;
-        let rip = regs.rip as usize;
-        println!("%rip register: {:#x}", rip);
-        // let rsp = regs.rsp as usize;
-        let line = dwarf_data.get_line_from_addr(rip).unwrap();
-        let func = dwarf_data.get_function_from_addr(rip).unwrap();
-        println!("#{} (#{})", func, line);
+    /// Returns a debug register (0-3) that no watchpoint or hardware breakpoint is using.
+    pub fn free_debug_slot(&self) -> Option<usize> {
+        (0..4).find(|&slot| self.watchpoints[slot].is_none() && self.hw_breakpoints[slot].is_none())
+    }
+
+    /// Sets a hardware breakpoint in the given debug register (0-3), which stops the inferior with
+    /// SIGTRAP just before it executes the instruction at addr. Unlike an int3, this doesn't touch
+    /// the inferior's code.
+    pub fn set_hw_breakpoint(&mut self, addr: u64, slot: usize) -> Result<(), nix::Error> {
+        if slot >= 4 {
+            return Err(nix::Error::Sys(Errno::EINVAL));
+        }
+        self.write_debug_reg(slot, addr)?;
+        // Condition 00 (break on execution) and length 00, which execution breakpoints require
+        let mut dr7 = self.read_debug_reg(7)?;
+        dr7 &= !(0b1111 << (16 + 4 * slot));
+        self.write_debug_reg(7, dr7)?;
+        self.hw_breakpoints[slot] = Some(addr);
+        self.enable_debug_slot(slot, true)
+    }
+
+    /// Returns the debug register holding a hardware breakpoint at addr, if there is one.
+    pub fn hw_breakpoint_slot(&self, addr: u64) -> Option<usize> {
+        (0..4).find(|&slot| self.hw_breakpoints[slot] == Some(addr))
+    }
+
+    /// Takes out the hardware breakpoint at addr, if there is one.
+    pub fn remove_hw_breakpoint(&mut self, addr: u64) -> Result<(), nix::Error> {
+        if let Some(slot) = self.hw_breakpoint_slot(addr) {
+            self.enable_debug_slot(slot, false)?;
+            self.hw_breakpoints[slot] = None;
+        }
+        Ok(())
+    }
+
+    /// Sets or clears a debug register's local enable bit in DR7.
+    fn enable_debug_slot(&self, slot: usize, enabled: bool) -> Result<(), nix::Error> {
+        let mut dr7 = self.read_debug_reg(7)?;
+        if enabled {
+            dr7 |= 1 << (2 * slot);
+        } else {
+            dr7 &= !(1 << (2 * slot));
+        }
+        self.write_debug_reg(7, dr7)
+    }
+
+    /// If the inferior's last SIGTRAP was caused by a watchpoint, returns the debug register that
+    /// held it.
+    pub fn watchpoint_hit(&self) -> Result<Option<usize>, nix::Error> {
+        let dr6 = self.read_debug_reg(6)?;
+        let slot = (0..4).find(|&slot| dr6 & (1 << slot) != 0 && self.watchpoints[slot].is_some());
+        // The CPU never clears DR6 by itself
+        self.write_debug_reg(6, 0)?;
+        Ok(slot)
+    }
+
+    fn read_debug_reg(&self, reg: usize) -> Result<u64, nix::Error> {
+        let offset = DEBUG_REG_OFFSET + reg * size_of::<u64>();
+        // PEEKUSER returns the value read, so the only way to spot an error is through errno
+        let value = unsafe {
+            Errno::clear();
+            libc::ptrace(
+                libc::PTRACE_PEEKUSER,
+                self.pid().as_raw(),
+                offset as *mut libc::c_void,
+                std::ptr::null_mut::<libc::c_void>(),
+            )
+        };
+        if value == -1 && Errno::last() != Errno::UnknownErrno {
+            return Err(nix::Error::Sys(Errno::last()));
+        }
+        Ok(value as u64)
+    }
+
+    fn write_debug_reg(&self, reg: usize, value: u64) -> Result<(), nix::Error> {
+        let offset = DEBUG_REG_OFFSET + reg * size_of::<u64>();
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_POKEUSER,
+                self.pid().as_raw(),
+                offset as *mut libc::c_void,
+                value as *mut libc::c_void,
+            )
+        };
+        Errno::result(ret).map(drop)
+    }
+
+    /// Prints the call stack, innermost frame first, marking the selected frame.
+    pub fn print_backtrace(&self, dwarf_data: &DwarfData, selected: usize) -> Result<(), nix::Error> {
+        for (i, frame) in self.frames(dwarf_data)?.iter().enumerate() {
+            print_frame(dwarf_data, i, frame, i == selected);
+        }
         Ok(())
     }
 
     pub(crate) fn write_byte(&mut self, addr: u64, val: u8) -> Result<u8, nix::Error> {
-        println!("write addr {:#x}", addr);
         let aligned_addr = align_addr_to_word(addr);
         let byte_offset = addr - aligned_addr;
         let word = ptrace::read(self.pid(), aligned_addr as ptrace::AddressType)? as u64;
@@ -166,6 +776,108 @@ impl Inferior {
     }
 }
 
+/// Where one of the function calls on the inferior's stack is at.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub rip: u64,
+    pub rsp: u64,
+    pub rbp: u64,
+    /// The value rsp had just before the call instruction that created this frame, i.e. the
+    /// address just above the return address. gcc makes local variable locations relative to it.
+    pub cfa: u64,
+}
+
+impl Frame {
+    /// Finds where a variable lives in memory, looking up local variables in this frame.
+    pub fn variable_addr(&self, var: &Variable) -> u64 {
+        match var.location {
+            Location::Address(addr) => addr as u64,
+            Location::FramePointerOffset(offset) => self.cfa.wrapping_add(offset as u64),
+        }
+    }
+}
+
+/// Prints one line of a backtrace.
+pub fn print_frame(dwarf_data: &DwarfData, index: usize, frame: &Frame, selected: bool) {
+    let rip = frame.rip as usize;
+    let marker = if selected { "\u{2192}" } else { " " };
+    match (dwarf_data.get_function_from_addr(rip), dwarf_data.get_line_from_addr(rip)) {
+        (Some(func), Some(line)) => {
+            println!("{} #{} {:#x} in {} ({})", marker, index, rip, func, line)
+        }
+        (Some(func), None) => println!("{} #{} {:#x} in {}", marker, index, rip, func),
+        (None, _) => println!("{} #{} {:#x}", marker, index, rip),
+    }
+}
+
+/// Prints where one of the inferior's threads is, marking the current thread.
+pub fn print_thread(dwarf_data: &DwarfData, index: usize, tid: Pid, rip: u64, current: bool) {
+    let rip = rip as usize;
+    let marker = if current { "\u{2192}" } else { " " };
+    match (dwarf_data.get_function_from_addr(rip), dwarf_data.get_line_from_addr(rip)) {
+        (Some(func), Some(line)) => {
+            println!("{} {} Thread {} {:#x} in {} ({})", marker, index, tid, rip, func, line)
+        }
+        (Some(func), None) => println!("{} {} Thread {} {:#x} in {}", marker, index, tid, rip, func),
+        (None, _) => println!("{} {} Thread {} {:#x}", marker, index, tid, rip),
+    }
+}
+
+/// Prints the value of every register, in hex and in decimal.
+pub fn print_registers(regs: &libc::user_regs_struct) {
+    for name in REGISTER_NAMES.iter() {
+        let value = register_value(regs, name).unwrap();
+        println!("{:<10}{:#018x}  {}", name, value, value as i64);
+    }
+}
+
+/// Every register in user_regs_struct, in the order "info registers" shows them.
+const REGISTER_NAMES: [&str; 27] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "eflags", "cs", "ss", "ds", "es", "fs", "gs", "fs_base", "gs_base",
+    "orig_rax",
+];
+
+/// Reads a register by name (e.g. "rax"). Returns None if there is no such register.
+pub fn register_value(regs: &libc::user_regs_struct, name: &str) -> Option<u64> {
+    let mut regs = *regs;
+    register_mut(&mut regs, name).copied()
+}
+
+/// Finds a register by name. rflags is accepted as another name for eflags.
+fn register_mut<'a>(regs: &'a mut libc::user_regs_struct, name: &str) -> Option<&'a mut u64> {
+    Some(match name {
+        "rax" => &mut regs.rax,
+        "rbx" => &mut regs.rbx,
+        "rcx" => &mut regs.rcx,
+        "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi,
+        "rdi" => &mut regs.rdi,
+        "rbp" => &mut regs.rbp,
+        "rsp" => &mut regs.rsp,
+        "r8" => &mut regs.r8,
+        "r9" => &mut regs.r9,
+        "r10" => &mut regs.r10,
+        "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12,
+        "r13" => &mut regs.r13,
+        "r14" => &mut regs.r14,
+        "r15" => &mut regs.r15,
+        "rip" => &mut regs.rip,
+        "eflags" | "rflags" => &mut regs.eflags,
+        "cs" => &mut regs.cs,
+        "ss" => &mut regs.ss,
+        "ds" => &mut regs.ds,
+        "es" => &mut regs.es,
+        "fs" => &mut regs.fs,
+        "gs" => &mut regs.gs,
+        "fs_base" => &mut regs.fs_base,
+        "gs_base" => &mut regs.gs_base,
+        "orig_rax" => &mut regs.orig_rax,
+        _ => return None,
+    })
+}
+
 fn align_addr_to_word(addr: u64) -> u64 {
     addr & (-(size_of::<u64>() as i64) as u64)
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+mod completer;
+mod core_file;
 mod debugger;
 mod debugger_command;
 mod inferior;
@@ -10,15 +12,69 @@ use std::env;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
+    let usage = || -> ! {
+        println!(
+            "Usage: {} [--pid <pid> | --core <core file>] [--no-demangle] [--script <file>] \
+             <target program>",
+            args[0]
+        );
         std::process::exit(1);
+    };
+    let mut target = None;
+    let mut pid: Option<u32> = None;
+    let mut core = None;
+    let mut demangle = true;
+    let mut script = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--pid" => match rest.next().map(|pid| pid.parse()) {
+                Some(Ok(num)) => pid = Some(num),
+                Some(Err(_)) => {
+                    println!("Invalid pid");
+                    std::process::exit(1);
+                }
+                None => usage(),
+            },
+            "--core" => core = Some(rest.next().unwrap_or_else(|| usage()).clone()),
+            "--no-demangle" => demangle = false,
+            "--script" => script = Some(rest.next().unwrap_or_else(|| usage()).clone()),
+            _ if target.is_none() => target = Some(arg.clone()),
+            _ => usage(),
+        }
     }
-    let target = &args[1];
+    if pid.is_some() && core.is_some() {
+        usage();
+    }
+    let target = match (target, pid) {
+        (Some(target), _) => target,
+        // When attaching without a target, debug whatever program the process is running
+        (None, Some(pid)) => match std::fs::read_link(format!("/proc/{}/exe", pid)) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(err) => {
+                println!("Could not find the program process {} is running: {}", pid, err);
+                std::process::exit(1);
+            }
+        },
+        (None, None) => usage(),
+    };
 
     // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
     // processes)
     unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
 
-    Debugger::new(target).run();
+    let mut debugger = Debugger::new(&target);
+    debugger.set_demangle(demangle);
+    if let Some(pid) = pid {
+        debugger.attach(pid);
+    }
+    if let Some(core) = core {
+        debugger.load_core(&core);
+    }
+    if let Some(script) = script {
+        if !debugger.source(&script) {
+            return;
+        }
+    }
+    debugger.run();
 }
@@ -0,0 +1,739 @@
+use std::fs;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Held while running make, so that tests sharing a sample don't build it at the same time.
+static BUILD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Numbers the script files of tests running at the same time.
+static SCRIPT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Builds samples/<name> with the Makefile, returning its path.
+fn build_sample(name: &str) -> String {
+    let _guard = BUILD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let status = Command::new("make")
+        .arg(format!("samples/{}", name))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdout(Stdio::null())
+        .status()
+        .expect("Could not run make");
+    assert!(status.success(), "Could not build samples/{}", name);
+    format!("{}/samples/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Runs deet on a sample, feeding it the given commands as a script, and returns what it printed
+/// after its dump of the debugging info.
+fn run_deet(sample: &str, commands: &[&str]) -> String {
+    run_deet_with_args(&[&build_sample(sample)], commands)
+}
+
+/// Like run_deet, but with the rest of deet's command line given in full.
+fn run_deet_with_args(args: &[&str], commands: &[&str]) -> String {
+    let script = std::env::temp_dir().join(format!(
+        "deet-test-{}-{}",
+        std::process::id(),
+        SCRIPT_COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::write(&script, commands.join("\n") + "\nquit\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_deet"))
+        .arg("--script")
+        .arg(&script)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .expect("Could not run deet");
+    fs::remove_file(&script).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(
+        output.status.success(),
+        "deet exited with {}:\n{}",
+        output.status,
+        stdout
+    );
+
+    // The dump ends with the line numbers of the last file
+    let lines: Vec<&str> = stdout.lines().collect();
+    let dump_end = lines
+        .iter()
+        .rposition(|line| *line == "Line numbers:")
+        .map_or(0, |i| i + 1);
+    let rest = lines[dump_end..]
+        .iter()
+        .skip_while(|line| line.starts_with("  * "));
+    rest.map(|line| format!("{}\n", line)).collect()
+}
+
+/// The rip and source location of each place deet reported stopping at, in order.
+fn stops(output: &str) -> Vec<(u64, &str)> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("rip 0x"))
+        .map(|rest| {
+            let (rip, location) = rest.split_once(", ").unwrap();
+            (u64::from_str_radix(rip, 16).unwrap(), location)
+        })
+        .collect()
+}
+
+/// Checks that deet stopped at exactly the given `<file>:<line>` locations, in order.
+fn assert_stopped_at(output: &str, expected: &[&str]) {
+    let locations: Vec<&str> = stops(output)
+        .into_iter()
+        .map(|(_, location)| location)
+        .collect();
+    assert_eq!(
+        locations.len(),
+        expected.len(),
+        "Stopped at {:?}:\n{}",
+        locations,
+        output
+    );
+    for (location, expected) in locations.iter().zip(expected) {
+        assert!(
+            location.ends_with(&format!("/{}", expected)),
+            "Stopped at {:?}, expected {:?}:\n{}",
+            locations,
+            expected,
+            output
+        );
+    }
+}
+
+/// The values printed by `x` commands, in order.
+fn examined(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("0x") && line.contains(":\t"))
+        .map(|line| line.split_once(":\t").unwrap().1)
+        .collect()
+}
+
+#[test]
+fn test_stepi() {
+    let output = run_deet("store_rax", &["break store_rax.c:7", "run", "stepi"]);
+    let stops = stops(&output);
+    assert_stopped_at(&output, &["store_rax.c:7", "store_rax.c:7"]);
+    assert!(
+        stops[1].0 > stops[0].0,
+        "stepi didn't move forward: {:?}",
+        stops
+    );
+}
+
+#[test]
+fn test_stepping_after_exit() {
+    let output = run_deet("exit", &["break main", "run", "continue", "stepi"]);
+    assert_stopped_at(&output, &["exit.c:3"]);
+    assert!(output.contains("Child exited (status 0)"));
+    assert!(output.contains("run process first"), "{}", output);
+
+    // The second next runs into the exit syscall before reaching the next line
+    let output = run_deet(
+        "exit",
+        &["break main", "run", "next", "next", "continue", "stepi"],
+    );
+    assert_stopped_at(&output, &["exit.c:3", "exit.c:4"]);
+    assert!(output.contains("Child exited (status 0)"));
+    assert_eq!(output.matches("run process first").count(), 2, "{}", output);
+}
+
+#[test]
+fn test_next() {
+    let output = run_deet("function_calls", &["break func2", "run", "next"]);
+    assert_stopped_at(&output, &["function_calls.c:9", "function_calls.c:10"]);
+}
+
+#[test]
+fn test_next_stops_in_callee() {
+    // func2 calls func3, so next stops there, but only if the breakpoint's condition holds
+    let output = run_deet(
+        "function_calls",
+        &[
+            "break function_calls.c:18",
+            "break func3 if global == 6",
+            "run",
+            "next",
+            "info breakpoints",
+        ],
+    );
+    assert_stopped_at(&output, &["function_calls.c:18", "function_calls.c:19"]);
+    assert!(output.contains("if global == 6, hit 0 times"), "{}", output);
+
+    let output = run_deet(
+        "function_calls",
+        &[
+            "break function_calls.c:18",
+            "break func3",
+            "run",
+            "next",
+            "info breakpoints",
+        ],
+    );
+    assert_stopped_at(&output, &["function_calls.c:18", "function_calls.c:5"]);
+    let listing = output
+        .lines()
+        .find(|line| line.starts_with("2: 0x"))
+        .unwrap();
+    assert!(
+        listing.ends_with("function_calls.c:5), hit 1 time"),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn test_next_reports_watchpoint() {
+    // Line 9 changes second, and the watchpoint goes off just as line 10 is reached
+    let output = run_deet(
+        "globals",
+        &[
+            "break globals.c:9",
+            "run",
+            "watch second",
+            "next",
+            "break globals.c:11",
+            "continue",
+            "info breakpoints",
+        ],
+    );
+    assert_stopped_at(&output, &["globals.c:9", "globals.c:10", "globals.c:11"]);
+    assert_eq!(
+        output.matches("Watchpoint on second hit").count(),
+        1,
+        "{}",
+        output
+    );
+    assert!(output.find("Watchpoint on second hit") < output.find("Set breakpoint 2"));
+    let listing = output
+        .lines()
+        .find(|line| line.starts_with("2: 0x"))
+        .unwrap();
+    assert!(
+        listing.ends_with("globals.c:11), hit 1 time"),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn test_step() {
+    // The printf call on line 12 is stepped over, having no line info, and func3 is stepped into
+    let output = run_deet(
+        "function_calls",
+        &["break function_calls.c:12", "run", "step", "step"],
+    );
+    assert_stopped_at(
+        &output,
+        &[
+            "function_calls.c:12",
+            "function_calls.c:13",
+            "function_calls.c:5",
+        ],
+    );
+}
+
+#[test]
+fn test_finish() {
+    // finish returns to just after the call, which is the start of the next line
+    let output = run_deet("function_calls", &["break func2", "run", "next", "finish"]);
+    assert_stopped_at(
+        &output,
+        &[
+            "function_calls.c:9",
+            "function_calls.c:10",
+            "function_calls.c:19",
+        ],
+    );
+
+    // A breakpoint on the way out stops it early
+    let output = run_deet(
+        "function_calls",
+        &["break func2", "run", "next", "break func3", "finish"],
+    );
+    assert_stopped_at(
+        &output,
+        &[
+            "function_calls.c:9",
+            "function_calls.c:10",
+            "function_calls.c:5",
+        ],
+    );
+}
+
+#[test]
+fn test_backtrace() {
+    let output = run_deet(
+        "function_calls",
+        &["break function_calls.c:10", "run", "backtrace"],
+    );
+    assert_stopped_at(&output, &["function_calls.c:10"]);
+    let frame = |index: &str| {
+        output
+            .lines()
+            .find(|line| line.contains(index))
+            .unwrap_or_else(|| panic!("No frame {}:\n{}", index, output))
+    };
+    assert!(frame(" #0 ").starts_with("\u{2192} #0 "));
+    assert!(frame(" #0 ").contains(" in func2 ("));
+    assert!(frame(" #0 ").ends_with("function_calls.c:10)"));
+    assert!(frame(" #1 ").contains(" in func1 ("));
+    assert!(frame(" #2 ").contains(" in main ("));
+}
+
+#[test]
+fn test_conditional_breakpoint() {
+    let output = run_deet(
+        "loop",
+        &[
+            "break loop.c:7 if i == 3",
+            "run",
+            "x/d &i",
+            "x/d &total",
+            "continue",
+            "info breakpoints",
+        ],
+    );
+    assert_stopped_at(&output, &["loop.c:7"]);
+    assert_eq!(examined(&output), ["3", "6"]);
+    assert!(output.contains("i = 9, total = 45"));
+    assert!(output.contains("Child exited (status 0)"));
+    assert!(output.contains("if i == 3, hit 1 time"));
+
+    let output = run_deet("loop", &["break loop.c:7 if i <= -1", "run"]);
+    assert_stopped_at(&output, &[]);
+    assert!(output.contains("Child exited (status 0)"));
+
+    // total is 0, 1, 3, 6 and then 10 (0xa) as i goes up from 0
+    let output = run_deet(
+        "loop",
+        &[
+            "break loop.c:7 if total >= 0xa",
+            "run",
+            "x/d &i",
+            "delete 1",
+            "continue",
+        ],
+    );
+    assert_stopped_at(&output, &["loop.c:7"]);
+    assert_eq!(examined(&output), ["4"]);
+    assert!(output.contains("Child exited (status 0)"));
+
+    let output = run_deet("loop", &["break loop.c:7 if i <="]);
+    assert!(output.contains("Invalid condition \"i <=\""));
+}
+
+#[test]
+fn test_breakpoint_on_line() {
+    let output = run_deet(
+        "loop",
+        &[
+            "break loop.c:7",
+            "run",
+            "continue",
+            "info breakpoints",
+            "delete 2",
+            "delete 1",
+            "info breakpoints",
+            "continue",
+        ],
+    );
+    assert!(output.contains("Set breakpoint 1 at 0x"), "{}", output);
+    assert_stopped_at(&output, &["loop.c:7", "loop.c:7"]);
+    assert!(output.contains("Child stopped (signal SIGTRAP)"));
+    let listing = output
+        .lines()
+        .find(|line| line.starts_with("1: 0x"))
+        .unwrap();
+    assert!(listing.contains(" in main ("), "{}", listing);
+    assert!(listing.ends_with("loop.c:7), hit 2 times"), "{}", listing);
+    assert!(output.contains("No breakpoint number 2"));
+    assert!(output.contains("Deleted breakpoint 1"));
+    assert!(output.contains("No breakpoints"));
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_disabled_breakpoint() {
+    let output = run_deet(
+        "loop",
+        &[
+            "break loop.c:7",
+            "break loop.c:6",
+            "disable 2",
+            "info breakpoints",
+            "run",
+            "enable 2",
+            "disable 1",
+            "continue",
+            "disable 2",
+            "continue",
+        ],
+    );
+    assert!(
+        output.contains("loop.c:6) (disabled), hit 0 times"),
+        "{}",
+        output
+    );
+    // Breakpoint 2 is only hit once it's enabled, on the second time round the loop
+    assert_stopped_at(&output, &["loop.c:7", "loop.c:6"]);
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_watchpoint() {
+    let output = run_deet(
+        "globals",
+        &["break main", "run", "watch second", "continue", "continue"],
+    );
+    assert!(output.contains("Watching second at 0x"));
+    assert!(output.contains("Watchpoint on second hit"));
+    assert!(output.contains("Old value = -2"));
+    assert!(output.contains("New value = -20"));
+    assert!(output.contains("7 -20 49"));
+}
+
+#[test]
+fn test_examine_memory() {
+    let output = run_deet(
+        "globals",
+        &[
+            "break globals.c:11",
+            "run",
+            "x/d &first",
+            "x/d &second",
+            "x &third",
+        ],
+    );
+    assert_stopped_at(&output, &["globals.c:11"]);
+    assert_eq!(examined(&output), ["7", "-20", "0x00000031"]);
+}
+
+#[test]
+fn test_registers() {
+    let output = run_deet(
+        "registers",
+        &[
+            "break registers.c:5",
+            "run",
+            "regs",
+            "print $r12",
+            "print $nope",
+        ],
+    );
+    assert_stopped_at(&output, &["registers.c:5"]);
+    let rbx = format!("{:<10}{:#018x}  {}", "rbx", 0x1234, 0x1234);
+    assert!(output.lines().any(|line| line == rbx), "{}", output);
+    assert!(output.contains("$r12 = 0xffffffffffffffd6 (-42)"));
+    assert!(output.contains("No register named $nope"));
+}
+
+#[test]
+fn test_list() {
+    let output = run_deet("loop", &["list loop.c:7"]);
+    let listed: Vec<&str> = output.lines().filter(|line| line.contains('\t')).collect();
+    // Five lines either side, cut short by the end of the file
+    assert_eq!(listed.len(), 9, "{}", output);
+    assert_eq!(listed[0], "     2\t");
+    assert_eq!(
+        listed[5],
+        "\u{2192}    7\t        printf(\"i = %d, total = %d\\n\", i, total);"
+    );
+    assert_eq!(listed[8], "    10\t}");
+}
+
+#[test]
+fn test_attach() {
+    let mut sleeper = Command::new(build_sample("sleepy_print"))
+        .arg("60")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Could not run sleepy_print");
+    let pid = sleeper.id().to_string();
+    // Without a target, deet debugs whatever program the process is running
+    let output = run_deet_with_args(&["--pid", &pid], &[]);
+    // Detaching on quit leaves the process running
+    thread::sleep(Duration::from_millis(100));
+    let running = sleeper.try_wait().unwrap().is_none();
+    let _ = sleeper.kill();
+    sleeper.wait().unwrap();
+    assert!(
+        output.contains(&format!("Attached to process {}", pid)),
+        "{}",
+        output
+    );
+    assert!(output.contains(&format!("Detached from process {}", pid)));
+    assert!(running, "sleepy_print stopped after deet detached");
+}
+
+#[test]
+fn test_core_file() {
+    let program = build_sample("segfault");
+    let dir = std::env::temp_dir().join(format!("deet-test-core-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // With the usual core_pattern, the kernel writes the core file to the crashing process's
+    // working directory
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("ulimit -c unlimited && exec {}", program))
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .status()
+        .expect("Could not run sh");
+    assert!(!status.success());
+    let core = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("core")
+        });
+    let core = match core {
+        Some(core) => core,
+        None => {
+            fs::remove_dir_all(&dir).unwrap();
+            eprintln!("No core file was written (see /proc/sys/kernel/core_pattern), skipping");
+            return;
+        }
+    };
+    let output = run_deet_with_args(
+        &["--core", core.to_str().unwrap(), &program],
+        &["backtrace", "continue"],
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.contains("Program terminated with signal SIGSEGV"),
+        "{}",
+        output
+    );
+    assert_stopped_at(&output, &["segfault.c:5"]);
+    let frame = |index: &str| {
+        output
+            .lines()
+            .find(|line| line.contains(index))
+            .unwrap_or_else(|| panic!("No frame {}:\n{}", index, output))
+    };
+    assert!(frame(" #0 ").contains(" in func2 ("));
+    assert!(frame(" #1 ").contains(" in func1 ("));
+    assert!(frame(" #2 ").contains(" in main ("));
+    assert!(output.contains("Cannot run in core mode."));
+}
+
+#[test]
+fn test_disassemble() {
+    let output = run_deet("registers", &["break registers.c:5", "run", "disassemble"]);
+    assert_stopped_at(&output, &["registers.c:5"]);
+    assert!(output.contains("Dump of assembler code for function main:"));
+    assert!(output.contains("End of assembler dump."));
+    // Instructions are shown in AT&T syntax, after their address and bytes
+    let instruction = output
+        .lines()
+        .find(|line| line.ends_with("mov $0x1234,%rbx"))
+        .unwrap_or_else(|| panic!("No mov to rbx:\n{}", output));
+    assert!(
+        instruction.contains(">:\t48 c7 c3 34 12 00 00 "),
+        "{}",
+        instruction
+    );
+    // The current instruction is marked, and it's the first one of line 5
+    let rip = stops(&output)[0].0;
+    let current: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with('\u{2192}'))
+        .collect();
+    assert_eq!(current.len(), 1, "{}", output);
+    assert!(
+        current[0].starts_with(&format!("\u{2192} {:#x} <+", rip)),
+        "{}",
+        current[0]
+    );
+    assert!(current[0].contains("lea "), "{}", current[0]);
+}
+
+#[test]
+fn test_frame_selection() {
+    let output = run_deet(
+        "function_calls",
+        &[
+            "break function_calls.c:10",
+            "run",
+            "up",
+            "up",
+            "down",
+            "frame 0",
+            "down",
+        ],
+    );
+    assert_stopped_at(&output, &["function_calls.c:10"]);
+    // Each command shows the frame it moves to, marked as selected
+    let selected: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("\u{2192} #"))
+        .collect();
+    assert_eq!(selected.len(), 4, "{}", output);
+    assert!(selected[0].starts_with("\u{2192} #1 ") && selected[0].contains(" in func1 ("));
+    assert!(selected[1].starts_with("\u{2192} #2 ") && selected[1].contains(" in main ("));
+    assert!(selected[2].starts_with("\u{2192} #1 ") && selected[2].contains(" in func1 ("));
+    assert!(selected[3].starts_with("\u{2192} #0 ") && selected[3].contains(" in func2 ("));
+    assert!(output.contains("Already at the innermost frame"));
+}
+
+#[test]
+fn test_temporary_breakpoint() {
+    let output = run_deet(
+        "loop",
+        &["tbreak loop.c:7", "run", "info breakpoints", "continue"],
+    );
+    assert!(output.contains("Set temporary breakpoint 1 at 0x"));
+    assert!(output.contains("Deleted temporary breakpoint 1"));
+    // It's gone as soon as it's hit, so the loop runs to the end
+    assert_stopped_at(&output, &["loop.c:7"]);
+    assert!(output.contains("No breakpoints"), "{}", output);
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_breakpoint_commands() {
+    let output = run_deet(
+        "loop",
+        &[
+            "break loop.c:7",
+            "commands",
+            "x/d &i",
+            "continue",
+            "end",
+            "run",
+        ],
+    );
+    assert_eq!(
+        examined(&output),
+        ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+    );
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_signal() {
+    let output = run_deet(
+        "signals",
+        &[
+            "break signals.c:15",
+            "run",
+            "signal SIGUSR1",
+            "delete 1",
+            "continue",
+        ],
+    );
+    assert_eq!(stops(&output)[0].1.rsplit('/').next(), Some("signals.c:15"));
+    assert!(output.contains("Got SIGUSR1"));
+    assert!(output.contains("Tick 2"));
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_generic_function_breakpoint() {
+    let output = run_deet(
+        "generics",
+        &[
+            "break Counter<T>::add",
+            "run",
+            "continue",
+            "continue",
+            "continue",
+        ],
+    );
+    assert!(output.contains("Set breakpoint 1 at 0x"), "{}", output);
+    assert_stopped_at(
+        &output,
+        &["generics.rs:13", "generics.rs:13", "generics.rs:13"],
+    );
+    assert!(output.contains("Some(2)"));
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_threads() {
+    let output = run_deet(
+        "threads",
+        &["break worker", "run", "info threads", "continue"],
+    );
+    assert_stopped_at(&output, &["threads.c:6"]);
+    assert!(output.contains("In thread 1 ("));
+    let current = output
+        .lines()
+        .find(|line| line.starts_with("\u{2192} 1 Thread "))
+        .unwrap();
+    assert!(current.contains(" in worker ("), "{}", current);
+    assert!(output.lines().any(|line| line.starts_with("  0 Thread ")));
+    assert!(output.contains("counter = 5"));
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_hardware_breakpoint() {
+    let output = run_deet(
+        "loop",
+        &["hbreak loop.c:7", "run", "continue", "delete 1", "continue"],
+    );
+    assert!(output.contains("Set hardware breakpoint 1 at 0x"));
+    assert_stopped_at(&output, &["loop.c:7", "loop.c:7"]);
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_set_memory() {
+    let output = run_deet(
+        "globals",
+        &["break globals.c:11", "run", "set second = 5", "continue"],
+    );
+    assert_stopped_at(&output, &["globals.c:11"]);
+    assert!(output.contains("Previous value: -20"));
+    assert!(output.contains("7 5 49"));
+}
+
+#[test]
+fn test_set_register() {
+    let output = run_deet(
+        "store_rax",
+        &[
+            "break store_rax.c:7",
+            "run",
+            "stepi",
+            "set $rax = 99",
+            "continue",
+        ],
+    );
+    assert!(output.contains("result = 99"), "{}", output);
+    assert!(output.contains("Child exited (status 0)"));
+}
+
+#[test]
+fn test_catch_panic() {
+    let output = run_deet("panic", &["catch panic", "run", "continue"]);
+    assert!(output.contains("Set breakpoint 1 at 0x"), "{}", output);
+    assert!(output.contains("Inferior panicked"));
+    // Panicking exits with status 101 once it's allowed to carry on
+    assert!(output.contains("Child exited (status 101)"));
+
+    let output = run_deet("loop", &["catch panic"]);
+    assert!(output.contains("Could not find Rust's panic handler"));
+}
+
+#[test]
+fn test_env() {
+    let output = run_deet(
+        "env",
+        &["set env MY_VAR=a=b", "run", "unset env MY_VAR", "run"],
+    );
+    assert!(output.contains("MY_VAR=a=b"), "{}", output);
+    assert!(output.contains("MY_VAR is not set"), "{}", output);
+    // Neither change was made while the program was running
+    assert!(!output.contains("Environment changes take effect on next 'run'."));
+
+    let output = run_deet("env", &["break main", "run", "set env MY_VAR=1"]);
+    assert!(output.contains("Environment changes take effect on next 'run'."));
+}
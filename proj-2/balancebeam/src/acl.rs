@@ -0,0 +1,12 @@
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// Decides whether a client IP may connect to the proxy. An IP is rejected if it matches any
+/// network in `denies`, even if it also matches an entry in `allows`. If `allows` is empty, every
+/// IP not explicitly denied is accepted; otherwise the IP must match at least one allowed network.
+pub fn ip_allowed(ip: IpAddr, allows: &[IpNet], denies: &[IpNet]) -> bool {
+    if denies.iter().any(|net| net.contains(&ip)) {
+        return false;
+    }
+    allows.is_empty() || allows.iter().any(|net| net.contains(&ip))
+}
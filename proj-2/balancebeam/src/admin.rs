@@ -0,0 +1,110 @@
+use crate::upstream_stats::{StatsSnapshot, UpstreamStats};
+use crate::{request, response, ProxyState};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task;
+
+/// Serves the admin API, which lets operators inspect balancebeam's state over HTTP:
+///
+/// * `GET /upstreams`: every upstream balancebeam has sent traffic to or is proxying to, with
+///   traffic counters and whether it is currently in the pool
+/// * `GET /upstreams/{address}/history`: recent active health check results for an upstream
+///
+/// Admin requests lock the proxy state only while building the response, as client connections do
+/// for each request they proxy.
+pub async fn serve(mut listener: TcpListener, state: Arc<Mutex<ProxyState>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                task::spawn(handle_admin_connection(stream, Arc::clone(&state)));
+            }
+            Err(e) => log::error!("admin listener accept got error {}", e),
+        }
+    }
+}
+
+async fn handle_admin_connection(mut conn: TcpStream, state: Arc<Mutex<ProxyState>>) {
+    loop {
+        let request = match request::read_from_stream(&mut conn).await {
+            Ok(request) => request,
+            Err(request::Error::IncompleteRequest(0)) => return,
+            Err(error) => {
+                log::debug!("Error reading admin request: {:?}", error);
+                return;
+            }
+        };
+        let response = route(&request, &state).await;
+        log::info!(
+            "admin: {} -> {}",
+            request::format_request_line(&request),
+            response::format_response_line(&response)
+        );
+        if let Err(error) = response::write_to_stream(&response, &mut conn).await {
+            log::warn!("Failed to send admin response: {}", error);
+            return;
+        }
+    }
+}
+
+async fn route(
+    request: &http::Request<Vec<u8>>,
+    state: &Mutex<ProxyState>,
+) -> http::Response<Vec<u8>> {
+    if request.method() != http::Method::GET {
+        return response::make_http_error(http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let segments: Vec<&str> = request.uri().path().trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["upstreams"] => {
+            let state = state.lock().await;
+            json_response(&upstreams_summary(&state))
+        }
+        ["upstreams", address, "history"] => {
+            let state = state.lock().await;
+            match state.health_history.get(*address) {
+                Some(history) => json_response(history),
+                None => response::make_http_error(http::StatusCode::NOT_FOUND),
+            }
+        }
+        _ => response::make_http_error(http::StatusCode::NOT_FOUND),
+    }
+}
+
+/// What `GET /upstreams` reports about one upstream.
+#[derive(serde::Serialize)]
+struct UpstreamSummary {
+    /// Whether requests are currently being sent to this upstream
+    in_pool: bool,
+    stats: StatsSnapshot,
+}
+
+fn upstreams_summary(state: &ProxyState) -> BTreeMap<&str, UpstreamSummary> {
+    let mut summary = BTreeMap::new();
+    let addresses = state
+        .upstream_addresses
+        .iter()
+        .chain(state.upstream_stats.keys());
+    for address in addresses {
+        summary.entry(address.as_str()).or_insert_with(|| UpstreamSummary {
+            in_pool: state.upstream_addresses.contains(address),
+            stats: match state.upstream_stats.get(address) {
+                Some(stats) => stats.snapshot(),
+                None => UpstreamStats::default().snapshot(),
+            },
+        });
+    }
+    summary
+}
+
+fn json_response<T: serde::Serialize>(value: &T) -> http::Response<Vec<u8>> {
+    let body = serde_json::to_vec(value).unwrap();
+    http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header("Content-Length", body.len().to_string())
+        .version(http::Version::HTTP_11)
+        .body(body)
+        .unwrap()
+}
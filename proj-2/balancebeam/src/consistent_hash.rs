@@ -0,0 +1,101 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+/// Number of points each upstream server gets on the ring.
+pub const DEFAULT_VIRTUAL_NODES: usize = 150;
+
+/// A hash ring that maps keys onto upstream servers. Each server is placed on the ring at several
+/// points ("virtual nodes") derived from its address, so a server's points do not move when other
+/// servers are added or removed. Removing a server therefore only remaps the keys it owned.
+pub struct ConsistentHash {
+    /// (position on the ring, index of the upstream) pairs, sorted by position
+    ring: Vec<(u64, usize)>,
+}
+
+impl ConsistentHash {
+    /// Builds a ring for the given upstreams. `select` returns indices into `upstreams`.
+    pub fn new(upstreams: &[String], virtual_nodes: usize) -> ConsistentHash {
+        let mut ring = Vec::with_capacity(upstreams.len() * virtual_nodes);
+        for (idx, address) in upstreams.iter().enumerate() {
+            for vnode in 0..virtual_nodes {
+                ring.push((hash(format!("{}#{}", address, vnode).as_bytes()), idx));
+            }
+        }
+        ring.sort();
+        ConsistentHash { ring }
+    }
+}
+
+fn hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Returns the index of the upstream that owns `key`, i.e. the upstream of the first virtual node
+/// at or after the key's position on the ring (wrapping around at the end). Returns None if the
+/// ring is empty.
+pub fn select(key: &[u8], ring: &ConsistentHash) -> Option<usize> {
+    if ring.ring.is_empty() {
+        return None;
+    }
+    let point = hash(key);
+    let pos = match ring.ring.binary_search_by(|(node, _)| node.cmp(&point)) {
+        Ok(pos) => pos,
+        Err(pos) => pos,
+    };
+    Some(ring.ring[pos % ring.ring.len()].1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn upstreams(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("10.0.0.{}:80", i)).collect()
+    }
+
+    #[test]
+    fn test_empty_ring() {
+        let ring = ConsistentHash::new(&[], DEFAULT_VIRTUAL_NODES);
+        assert_eq!(select(b"key", &ring), None);
+    }
+
+    #[test]
+    fn test_same_key_same_upstream() {
+        let ring = ConsistentHash::new(&upstreams(4), DEFAULT_VIRTUAL_NODES);
+        for i in 0..100 {
+            let key = format!("key-{}", i);
+            assert_eq!(select(key.as_bytes(), &ring), select(key.as_bytes(), &ring));
+        }
+    }
+
+    #[test]
+    fn test_removing_upstream_remaps_few_keys() {
+        let num_upstreams = 5;
+        let num_keys = 10000;
+        let all = upstreams(num_upstreams);
+        let mut remaining = all.clone();
+        let removed = remaining.remove(2);
+        let before = ConsistentHash::new(&all, DEFAULT_VIRTUAL_NODES);
+        let after = ConsistentHash::new(&remaining, DEFAULT_VIRTUAL_NODES);
+
+        let mut remapped = 0;
+        for i in 0..num_keys {
+            let key = format!("key-{}", i);
+            let old = &all[select(key.as_bytes(), &before).unwrap()];
+            let new = &remaining[select(key.as_bytes(), &after).unwrap()];
+            if old != new {
+                // Only keys owned by the removed upstream should move
+                assert_eq!(old, &removed);
+                remapped += 1;
+            }
+        }
+        assert!(
+            (remapped as f64) < num_keys as f64 * 2.0 / num_upstreams as f64,
+            "{} of {} keys were remapped",
+            remapped,
+            num_keys
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Number of health check results kept for each upstream.
+pub const MAX_ENTRIES: usize = 100;
+
+/// The most recent active health check results for one upstream, oldest first. Once full, each new
+/// result pushes out the oldest one.
+#[derive(Debug, Default)]
+pub struct HealthHistory {
+    entries: VecDeque<(Instant, bool)>,
+}
+
+/// How a single history entry is written out in JSON. Instants have no absolute meaning, so the
+/// time of each check is given relative to when the history is serialized.
+#[derive(serde::Serialize)]
+struct Entry {
+    seconds_ago: f64,
+    healthy: bool,
+}
+
+impl HealthHistory {
+    /// Records the result of a health check that just finished.
+    pub fn push(&mut self, healthy: bool) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((Instant::now(), healthy));
+    }
+
+    /// Returns the recorded results, oldest first.
+    #[allow(dead_code)]
+    pub fn results(&self) -> Vec<bool> {
+        self.entries.iter().map(|(_, healthy)| *healthy).collect()
+    }
+}
+
+impl Serialize for HealthHistory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = Instant::now();
+        let mut seq = serializer.serialize_seq(Some(self.entries.len()))?;
+        for (checked_at, healthy) in &self.entries {
+            seq.serialize_element(&Entry {
+                seconds_ago: now.duration_since(*checked_at).as_secs_f64(),
+                healthy: *healthy,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut history = HealthHistory::default();
+        for i in 0..MAX_ENTRIES + 10 {
+            history.push(i % 2 == 0);
+        }
+        let results = history.results();
+        assert_eq!(results.len(), MAX_ENTRIES);
+        // The first 10 results were pushed out, so the oldest remaining one is result 10
+        assert!(results[0]);
+        assert!(!results[1]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut history = HealthHistory::default();
+        history.push(true);
+        history.push(false);
+        let json: serde_json::Value = serde_json::to_value(&history).unwrap();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["healthy"], true);
+        assert_eq!(entries[1]["healthy"], false);
+        assert!(entries[0]["seconds_ago"].as_f64().unwrap() >= 0.0);
+    }
+}
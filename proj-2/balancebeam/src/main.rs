@@ -1,17 +1,34 @@
+mod acl;
+mod admin;
+mod consistent_hash;
+mod health_history;
+mod middleware;
+mod proxy_protocol;
 mod request;
+mod resolver;
 mod response;
+mod throttle;
+mod upstream_conn;
+mod upstream_stats;
 
 use std::io::Write;
-use std::borrow::BorrowMut;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use clap::Clap;
 use rand::{Rng, SeedableRng};
 use tokio::net::{TcpListener, TcpStream};
 use async_std::channel::{unbounded};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use consistent_hash::ConsistentHash;
+use health_history::HealthHistory;
+use middleware::{BoxedMiddleware, ClientInfo};
+use throttle::TokenBucket;
+use upstream_conn::UpstreamConn;
+use upstream_stats::UpstreamStats;
 use http::Request;
+use ipnet::IpNet;
 use log::{LevelFilter, log};
 use tokio::sync::{mpsc, Mutex};
 use tokio::sync::mpsc::unbounded_channel;
@@ -30,7 +47,11 @@ struct CmdOptions {
         default_value = "0.0.0.0:1100"
     )]
     bind: String,
-    #[clap(short, long, about = "Upstream host to forward requests to")]
+    #[clap(
+        short,
+        long,
+        about = "Upstream host to forward requests to (host:port, or unix:/path/to/socket)"
+    )]
     upstream: Vec<String>,
     #[clap(
         long,
@@ -50,6 +71,98 @@ struct CmdOptions {
         default_value = "0"
     )]
     max_requests_per_minute: usize,
+    #[clap(long, about = "Only accept connections from this network (CIDR, repeatable)")]
+    allow_ip: Vec<IpNet>,
+    #[clap(long, about = "Reject connections from this network (CIDR, repeatable)")]
+    deny_ip: Vec<IpNet>,
+    #[clap(long, about = "Remove this header from requests before forwarding (repeatable)")]
+    strip_request_header: Vec<String>,
+    #[clap(long, about = "Remove this header from responses before forwarding (repeatable)")]
+    strip_response_header: Vec<String>,
+    #[clap(long, about = "Add this header (as name:value) to every response (repeatable)")]
+    add_response_header: Vec<String>,
+    #[clap(
+        long,
+        about = "Replace headers of the same name sent by the upstream instead of appending"
+    )]
+    override_response_header: bool,
+    #[clap(
+        long,
+        about = "Consecutive failed health checks before an upstream is removed",
+        default_value = "3"
+    )]
+    health_fail_threshold: usize,
+    #[clap(
+        long,
+        about = "Consecutive successful health checks before an upstream is restored",
+        default_value = "2"
+    )]
+    health_recover_threshold: usize,
+    #[clap(
+        long,
+        about = "How to pick an upstream for each request (random or consistent-hash)",
+        default_value = "random"
+    )]
+    strategy: Strategy,
+    #[clap(
+        long,
+        about = "Request header used as the consistent-hash key (defaults to the client IP)"
+    )]
+    hash_header: Option<String>,
+    #[clap(long, about = "Send a copy of every request to this upstream, ignoring its responses")]
+    mirror_upstream: Option<String>,
+    #[clap(
+        long,
+        about = "Percentage of requests (0-100) to copy to the mirror upstream",
+        default_value = "100"
+    )]
+    mirror_percent: u32,
+    #[clap(long, about = "Send a PROXY protocol v1 header on every upstream connection")]
+    upstream_proxy_protocol: bool,
+    #[clap(
+        long,
+        about = "Re-resolve upstream hostnames on this interval (in seconds, 0 = never)",
+        default_value = "0"
+    )]
+    resolve_interval: u64,
+    #[clap(
+        long,
+        about = "Largest upstream response body to forward, in bytes (0 = unlimited)",
+        default_value = "0"
+    )]
+    max_response_body: usize,
+    #[clap(
+        long,
+        about = "Maximum bytes per second sent to each client IP (0 = unlimited)",
+        default_value = "0"
+    )]
+    max_client_bandwidth: usize,
+    #[clap(long, about = "Add an X-Request-Id header to requests that don't have one")]
+    add_request_id: bool,
+    #[clap(long, about = "IP/port to serve the admin API on (disabled if not given)")]
+    admin_bind: Option<String>,
+}
+
+/// How an upstream server is chosen for a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    /// Pick a random upstream for each client connection
+    Random,
+    /// Hash a key from the request onto a ring of upstreams so the same key always reaches the
+    /// same upstream while it is healthy
+    ConsistentHash,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Strategy::Random),
+            "consistent-hash" => Ok(Strategy::ConsistentHash),
+            _ => Err(format!("unknown strategy {:?}", s)),
+        }
+    }
 }
 
 /// Contains information about the state of balancebeam (e.g. what servers we are currently proxying
@@ -68,8 +181,85 @@ struct ProxyState {
     max_requests_per_minute: usize,
     /// Lists of servers that we are proxying to
     upstream_addresses: Vec<String>,
-    /// Request traffic record
-    traffic_record: HashMap<String, u64>,
+    /// Transformations applied to every request and response, in order
+    middlewares: Vec<BoxedMiddleware>,
+    /// Maximum bytes per second sent to an individual IP (0 = unlimited)
+    max_client_bandwidth: usize,
+    /// Bandwidth allowance of each client IP, used when `max_client_bandwidth` is set. Each bucket
+    /// has its own lock so that throttled writes don't hold up the rest of the state.
+    bandwidth_buckets: HashMap<String, Arc<Mutex<TokenBucket>>>,
+    /// Lowercased names of headers removed from client requests before forwarding
+    strip_request_headers: Vec<String>,
+    /// Lowercased names of headers removed from upstream responses before forwarding
+    strip_response_headers: Vec<String>,
+    /// Headers added to every upstream response before it is forwarded to the client
+    add_response_headers: Vec<(http::HeaderName, http::HeaderValue)>,
+    /// Whether added headers replace upstream headers of the same name rather than being appended
+    override_response_headers: bool,
+    /// Consecutive failed active health checks needed to remove an upstream
+    health_fail_threshold: usize,
+    /// Consecutive successful active health checks needed to restore an upstream
+    health_recover_threshold: usize,
+    /// How upstreams are chosen for requests
+    strategy: Strategy,
+    /// Header whose value is used as the consistent-hash key
+    hash_header: Option<String>,
+    /// Hash ring over `upstream_addresses`, used with the consistent-hash strategy
+    ring: ConsistentHash,
+    /// Shadow upstream that receives copies of requests
+    mirror_upstream: Option<String>,
+    /// Percentage of requests copied to the shadow upstream
+    mirror_percent: u32,
+    /// Whether upstream connections start with a PROXY protocol header
+    upstream_proxy_protocol: bool,
+    /// Largest upstream response body we forward, in bytes (0 = unlimited)
+    max_response_body: usize,
+    /// Recent active health check results for each upstream, for the admin API
+    health_history: HashMap<String, HealthHistory>,
+    /// Traffic counters for each upstream, for the admin API
+    upstream_stats: HashMap<String, UpstreamStats>,
+}
+
+impl ProxyState {
+    /// Returns the traffic counters for an upstream, starting them at zero if needed.
+    fn stats_for(&mut self, address: &str) -> &UpstreamStats {
+        self.upstream_stats.entry(address.to_string()).or_default()
+    }
+
+    /// Applies the result of an active health check: records it in the upstream's history, and
+    /// removes or restores the upstream once enough consecutive checks agree. `targets` are the
+    /// upstreams currently being health checked.
+    fn apply_health_report(&mut self, msg: UpStream, targets: &[String]) {
+        self.health_history
+            .entry(msg.address.clone())
+            .or_default()
+            .push(msg.state == UpstreamState::Health);
+        match msg.state {
+            UpstreamState::Ill => {
+                if msg.consecutive < self.health_fail_threshold {
+                    return;
+                }
+                self.upstream_addresses.retain(|f| { f != &msg.address });
+                self.rebuild_ring();
+                log::error!("after retain upstream_addresses {:?}", self.upstream_addresses);
+            }
+            UpstreamState::Health => {
+                if msg.consecutive < self.health_recover_threshold
+                    || self.upstream_addresses.contains(&msg.address)
+                    || !targets.contains(&msg.address)
+                {
+                    return;
+                }
+                self.upstream_addresses.push(msg.address.clone());
+                self.rebuild_ring();
+            }
+        }
+    }
+
+    /// Rebuilds the hash ring. Must be called whenever `upstream_addresses` changes.
+    fn rebuild_ring(&mut self) {
+        self.ring = ConsistentHash::new(&self.upstream_addresses, consistent_hash::DEFAULT_VIRTUAL_NODES);
+    }
 }
 
 /// Represent a upstream server and its health state.
@@ -77,9 +267,11 @@ struct ProxyState {
 struct UpStream {
     address: String,
     state: UpstreamState,
+    /// How many health checks in a row (including this one) have reported `state`
+    consecutive: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum UpstreamState {
     Health,
     Ill,
@@ -113,6 +305,23 @@ async fn main() {
         log::error!("At least one upstream server must be specified using the --upstream option.");
         std::process::exit(1);
     }
+    if options.mirror_percent > 100 {
+        log::error!("--mirror-percent must be between 0 and 100.");
+        std::process::exit(1);
+    }
+
+    let mut add_response_headers = Vec::new();
+    for header in &options.add_response_header {
+        match parse_header(header) {
+            Some(parsed) => add_response_headers.push(parsed),
+            None => {
+                log::error!("Invalid --add-response-header {:?}, expected name:value", header);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let middlewares = build_middlewares(&options);
 
     // Start listening for connections
     let mut listener = match TcpListener::bind(&options.bind).await {
@@ -124,73 +333,149 @@ async fn main() {
     };
     log::info!("Listening for requests on {}", options.bind);
 
+    // If re-resolution is enabled, we proxy to the resolved addresses rather than the configured
+    // hostnames, so that we can tell when the addresses behind a hostname change
+    let upstream_hosts = options.upstream.clone();
+    let upstream_addresses = if options.resolve_interval > 0 {
+        resolver::resolve_upstreams(&upstream_hosts).await
+    } else {
+        options.upstream
+    };
+    // Every upstream we know about, healthy or not. This is what active health checks probe.
+    let health_check_targets = Arc::new(parking_lot::Mutex::new(upstream_addresses.clone()));
+
+    let ring = ConsistentHash::new(&upstream_addresses, consistent_hash::DEFAULT_VIRTUAL_NODES);
     let proxy_state = ProxyState {
-        upstream_addresses: options.upstream,
+        upstream_addresses,
         active_health_check_interval: options.active_health_check_interval,
         active_health_check_path: options.active_health_check_path,
         max_requests_per_minute: options.max_requests_per_minute,
-        traffic_record: HashMap::new(),
+        middlewares,
+        max_client_bandwidth: options.max_client_bandwidth,
+        bandwidth_buckets: HashMap::new(),
+        strip_request_headers: options
+            .strip_request_header
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect(),
+        strip_response_headers: options
+            .strip_response_header
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect(),
+        add_response_headers,
+        override_response_headers: options.override_response_header,
+        health_fail_threshold: options.health_fail_threshold,
+        health_recover_threshold: options.health_recover_threshold,
+        strategy: options.strategy,
+        hash_header: options.hash_header,
+        ring,
+        mirror_upstream: options.mirror_upstream,
+        mirror_percent: options.mirror_percent,
+        upstream_proxy_protocol: options.upstream_proxy_protocol,
+        max_response_body: options.max_response_body,
+        health_history: HashMap::new(),
+        upstream_stats: HashMap::new(),
     };
-    let (sender, mut receiver) = unbounded();
+    let (sender, receiver) = unbounded();
     let mut sender = sender.clone();
 
-    let upstream_addresses = proxy_state.upstream_addresses.clone();
+    let targets = Arc::clone(&health_check_targets);
     let active_health_check_path = proxy_state.active_health_check_path.clone();
     let active_health_check_interval = proxy_state.active_health_check_interval;
+    let upstream_proxy_protocol = proxy_state.upstream_proxy_protocol;
     let state = Arc::new(Mutex::new(proxy_state));
 
     let handler = task::spawn(async move {
+        // Number of consecutive identical results seen for each upstream
+        let mut streaks: HashMap<String, (UpstreamState, usize)> = HashMap::new();
         loop {
+            let upstream_addresses = targets.lock().clone();
             for address in &upstream_addresses {
-                let path = format!("{}{}{}", "http://", address, active_health_check_path);
-                log::info!("health check address {}", &path);
-                let mut conn = match TcpStream::connect(address).await {
-                    Err(err) => {
-                        log::error!("Failed to connect to upstream {}: {}, remove from health servers", address, err);
-                        sender.send(UpStream { address: address.clone(), state: UpstreamState::Ill }).await;
-                        continue;
-                    },
-                    Ok(other) => {
-                        other
-                    }
-                };
-                let request = Request::get(&path).body(vec![]).unwrap();
-                if let Err(error) = request::write_to_stream(&request, &mut conn).await {
-                    log::error!("Failed to send request to upstream {}: {}", address, error);
-                    sender.send(UpStream { address: address.clone(), state: UpstreamState::Ill }).await;
-                    continue;
-                }
-                let response = match response::read_from_stream(&mut conn, request.method()).await {
-                    Ok(response) => response,
-                    Err(error) => {
-                        log::error!("Error reading response from server: {:?}", error);
-                        sender.send(UpStream { address: address.clone(), state: UpstreamState::Ill }).await;
-                        continue;
-                    }
-                };
-                let code = response.status().as_u16();
-                log::info!("health check return status {}, {}", &path, code);
-                if code != 200 {
-                    sender.send(UpStream { address: address.clone(), state: UpstreamState::Ill }).await;
+                let state = check_upstream_health(
+                    address,
+                    &active_health_check_path,
+                    upstream_proxy_protocol,
+                )
+                .await;
+                let streak = streaks.entry(address.clone()).or_insert((state, 0));
+                if streak.0 == state {
+                    streak.1 += 1;
                 } else {
-                    sender.send(UpStream { address: address.clone(), state: UpstreamState::Health }).await;
+                    *streak = (state, 1);
                 }
+                let _ = sender.send(UpStream { address: address.clone(), state, consecutive: streak.1 }).await;
             }
             delay_for(Duration::from_secs(active_health_check_interval as u64)).await;
         }
     });
+    if options.resolve_interval > 0 {
+        let state = Arc::clone(&state);
+        let targets = Arc::clone(&health_check_targets);
+        let resolve_interval = options.resolve_interval;
+        task::spawn(async move {
+            let mut previous = targets.lock().clone();
+            loop {
+                delay_for(Duration::from_secs(resolve_interval)).await;
+                let current = resolver::resolve_upstreams(&upstream_hosts).await;
+                let mut state = state.lock().await;
+                if resolver::apply_resolution(&mut state.upstream_addresses, &previous, &current) {
+                    state.rebuild_ring();
+                }
+                *targets.lock() = current.clone();
+                previous = current;
+            }
+        });
+    }
     let state_clone = Arc::clone(&state);
 
     let _ = task::spawn(async move {
         loop {
             delay_for(Duration::from_secs(60)).await;
             let mut state = state_clone.lock().await;
-            state.traffic_record = HashMap::new();
+            // Forget idle clients: those with no connection using their bucket, and whose bucket
+            // has refilled, so a client that comes back starts with the allowance it would have had
+            state.bandwidth_buckets.retain(|_, bucket| {
+                Arc::strong_count(bucket) > 1
+                    || !bucket.try_lock().map_or(true, |mut bucket| bucket.is_full())
+            });
         }
     });
+    if let Some(admin_bind) = &options.admin_bind {
+        let admin_listener = match TcpListener::bind(admin_bind).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                log::error!("Could not bind admin API to {}: {}", admin_bind, err);
+                std::process::exit(1);
+            }
+        };
+        log::info!("Serving admin API on {}", admin_bind);
+        task::spawn(admin::serve(admin_listener, Arc::clone(&state)));
+    }
     loop {
-        let stream = match listener.accept().await {
-            Ok((stream, _)) => {
+        // Apply health check results as soon as they arrive, and accept connections in between
+        let stream = tokio::select! {
+            msg = receiver.recv() => {
+                match msg {
+                    Ok(msg) => {
+                        log::info!("channel msg {:?}", msg);
+                        let targets = health_check_targets.lock().clone();
+                        state.lock().await.apply_health_report(msg, &targets);
+                    }
+                    Err(e) => log::error!("health check channel closed {}", e),
+                }
+                continue;
+            }
+            accepted = listener.accept() => accepted,
+        };
+        let stream = match stream {
+            Ok((stream, addr)) => {
+                if !acl::ip_allowed(addr.ip(), &options.allow_ip, &options.deny_ip) {
+                    log::info!("Rejecting connection from {}", addr.ip());
+                    // Zero linger makes the close send a RST instead of a graceful FIN
+                    let _ = stream.set_linger(Some(Duration::from_secs(0)));
+                    continue;
+                }
                 stream
             }
             Err(e) => {
@@ -199,55 +484,170 @@ async fn main() {
             }
         };
 
-        loop {
-            let msg = match receiver.try_recv() {
-                Ok(msg) => {
-                    msg
-                }
-                Err(e) => {
-                    log::error!("try_recv fail {}", e);
-                    break;
-                }
-            };
-            let mut state = state.lock().await;
-            log::info!("channel msg {:?}", msg);
-            match msg.state {
-                UpstreamState::Ill => {
-                    state.upstream_addresses.retain(|f| { f != &msg.address });
-                    log::error!("after retain upstream_addresses {:?}", state.upstream_addresses);
-                }
-                UpstreamState::Health => {
-                    if state.upstream_addresses.contains(&msg.address) {
-                        continue;
-                    }
-                    state.upstream_addresses.push(msg.address.clone());
-                }
-            }
-        }
         // Handle the connection!
         let state = Arc::clone(&state);
         task::spawn(handle_connection(stream, state));
     }
 }
 
-async fn connect_to_upstream(state: &mut ProxyState) -> Result<TcpStream, std::io::Error> {
-    log::info!("upstream_addresses {:?}", &state.upstream_addresses);
+/// Builds the middleware every request and response passes through, as configured on the command
+/// line.
+fn build_middlewares(options: &CmdOptions) -> Vec<BoxedMiddleware> {
+    let mut middlewares: Vec<BoxedMiddleware> = Vec::new();
+    if options.max_requests_per_minute != 0 {
+        middlewares.push(Box::new(middleware::RateLimit::new(options.max_requests_per_minute)));
+    }
+    middlewares.push(Box::new(middleware::ForwardedHeaders));
+    if options.add_request_id {
+        middlewares.push(Box::new(middleware::RequestId));
+    }
+    middlewares
+}
+
+/// Parses a header given on the command line as `name:value`. Returns None if the name or value is
+/// not a valid HTTP header name/value.
+fn parse_header(header: &str) -> Option<(http::HeaderName, http::HeaderValue)> {
+    let mut parts = header.splitn(2, ':');
+    let name = http::HeaderName::from_bytes(parts.next()?.trim().as_bytes()).ok()?;
+    let value = http::HeaderValue::from_str(parts.next()?.trim()).ok()?;
+    Some((name, value))
+}
+
+/// Sends a request to `path` on the given upstream and reports whether it answered with HTTP 200.
+/// If `proxy_protocol` is set, the request is preceded by a PROXY header, since upstreams that
+/// expect one will reject connections without it.
+async fn check_upstream_health(address: &str, path: &str, proxy_protocol: bool) -> UpstreamState {
+    log::info!("health check address {}{}", address, path);
+    let mut conn = match upstream_conn::connect(address).await {
+        Err(err) => {
+            log::error!("Failed to connect to upstream {}: {}", address, err);
+            return UpstreamState::Ill;
+        },
+        Ok(other) => {
+            other
+        }
+    };
+    if proxy_protocol {
+        if let Err(error) = proxy_protocol::write_proxy_v1_unknown_header(&mut conn).await {
+            log::error!("Failed to send PROXY header to upstream {}: {}", address, error);
+            return UpstreamState::Ill;
+        }
+    }
+    let host = if upstream_conn::is_unix_address(address) { "localhost" } else { address };
+    let request = Request::get(path).header("Host", host).body(vec![]).unwrap();
+    if let Err(error) = request::write_to_stream(&request, &mut conn).await {
+        log::error!("Failed to send request to upstream {}: {}", address, error);
+        return UpstreamState::Ill;
+    }
+    let response = match response::read_from_stream(&mut conn, request.method(), 0).await {
+        Ok(response) => response,
+        Err(error) => {
+            log::error!("Error reading response from server: {:?}", error);
+            return UpstreamState::Ill;
+        }
+    };
+    let code = response.status().as_u16();
+    log::info!("health check return status {}{}, {}", address, path, code);
+    if code != 200 {
+        UpstreamState::Ill
+    } else {
+        UpstreamState::Health
+    }
+}
+
+/// Connects to an upstream server, returning its address along with the connection. If `key` is
+/// given, the upstream is chosen from the hash ring; otherwise a random upstream is used. Upstreams
+/// that refuse the connection are removed and another one is tried. `client_addr` is announced to
+/// the upstream if PROXY protocol is enabled. The state is not kept locked while connecting.
+async fn connect_to_upstream(
+    state: &Mutex<ProxyState>,
+    key: Option<&[u8]>,
+    client_addr: SocketAddr,
+) -> Result<(String, UpstreamConn), std::io::Error> {
     let mut rng = rand::rngs::StdRng::from_entropy();
     loop {
-        let upstream_idx = rng.gen_range(0, state.upstream_addresses.len());
-        let mut upstream_ip = &state.upstream_addresses[upstream_idx];
-        match TcpStream::connect(upstream_ip).await {
+        let (upstream_ip, proxy_protocol) = {
+            let state = state.lock().await;
+            log::info!("upstream_addresses {:?}", &state.upstream_addresses);
+            if state.upstream_addresses.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "no upstream servers available",
+                ));
+            }
+            let upstream_idx = match key {
+                Some(key) => consistent_hash::select(key, &state.ring).unwrap(),
+                None => rng.gen_range(0, state.upstream_addresses.len()),
+            };
+            (state.upstream_addresses[upstream_idx].clone(), state.upstream_proxy_protocol)
+        };
+        match upstream_conn::connect(&upstream_ip).await {
             Err(err) => {
                 log::error!("Failed to connect to upstream {}: {}, remove from health servers", upstream_ip, err);
-                let removed_upstream = state.upstream_addresses.remove(upstream_idx);
+                // Other connections may have changed the list while we were connecting
+                let mut state = state.lock().await;
+                state.upstream_addresses.retain(|address| address != &upstream_ip);
+                state.rebuild_ring();
             }
-            other => {
-                return other;
+            Ok(mut stream) => {
+                if proxy_protocol {
+                    match stream.peer_addr() {
+                        Some(upstream_addr) => {
+                            proxy_protocol::write_proxy_v1_header(&mut stream, client_addr, upstream_addr).await?
+                        }
+                        None => proxy_protocol::write_proxy_v1_unknown_header(&mut stream).await?,
+                    }
+                }
+                return Ok((upstream_ip, stream));
             }
         }
     }
 }
 
+/// Returns the consistent-hash key for a request: the value of the configured hash header if the
+/// request has one, or the client IP otherwise. Returns None when not using consistent hashing.
+fn routing_key(state: &ProxyState, request: &http::Request<Vec<u8>>, client_ip: &str) -> Option<Vec<u8>> {
+    if state.strategy != Strategy::ConsistentHash {
+        return None;
+    }
+    let header_value = state
+        .hash_header
+        .as_ref()
+        .and_then(|name| request.headers().get(name.as_str()));
+    Some(match header_value {
+        Some(value) => value.as_bytes().to_vec(),
+        None => client_ip.as_bytes().to_vec(),
+    })
+}
+
+/// Sends a copy of a request to the shadow upstream and discards the response. Failures are only
+/// logged, since mirrored traffic must never affect the client.
+async fn mirror_request(mirror_address: String, request: http::Request<Vec<u8>>) {
+    let mut conn = match upstream_conn::connect(&mirror_address).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::warn!("Failed to connect to mirror upstream {}: {}", mirror_address, err);
+            return;
+        }
+    };
+    if let Err(error) = request::write_to_stream(&request, &mut conn).await {
+        log::warn!("Failed to send request to mirror upstream {}: {}", mirror_address, error);
+        return;
+    }
+    match response::read_from_stream(&mut conn, request.method(), 0).await {
+        Ok(response) => log::debug!(
+            "Mirror upstream {} replied {}",
+            mirror_address,
+            response::format_response_line(&response)
+        ),
+        Err(error) => log::warn!(
+            "Error reading response from mirror upstream {}: {:?}",
+            mirror_address,
+            error
+        ),
+    }
+}
+
 async fn send_response(client_conn: &mut TcpStream, response: &http::Response<Vec<u8>>) {
     let client_ip = client_conn.peer_addr().unwrap().ip().to_string();
     log::info!("{} <- {}", client_ip, response::format_response_line(&response));
@@ -257,20 +657,36 @@ async fn send_response(client_conn: &mut TcpStream, response: &http::Response<Ve
     }
 }
 
-async fn handle_connection(mut client_conn: TcpStream, state: Arc<Mutex<ProxyState>>) {
+/// Like `send_response`, but paces the write to the client using `bucket`.
+async fn send_throttled_response(
+    client_conn: &mut TcpStream,
+    response: &http::Response<Vec<u8>>,
+    bucket: &mut TokenBucket,
+) {
     let client_ip = client_conn.peer_addr().unwrap().ip().to_string();
+    log::info!("{} <- {} (throttled)", client_ip, response::format_response_line(&response));
+    let mut data = Vec::new();
+    if let Err(error) = response::write_to_stream(&response, &mut data).await {
+        log::warn!("Failed to serialize response: {}", error);
+        return;
+    }
+    if let Err(error) = throttle::throttled_write(client_conn, &data, bucket).await {
+        log::warn!("Failed to send response to client: {}", error);
+    }
+}
+
+/// Proxies requests from a client to upstream servers.
+///
+/// The state is only locked while it is read or updated, never while waiting on the client or an
+/// upstream, so that other connections, health check results and the admin API aren't held up.
+async fn handle_connection(mut client_conn: TcpStream, state: Arc<Mutex<ProxyState>>) {
+    let client_addr = client_conn.peer_addr().unwrap();
+    let client_ip = client_addr.ip().to_string();
     log::info!("Connection received from {}", client_ip);
-    let mut state = state.lock().await;
-    // Open a connection to a random destination server
-    let mut upstream_conn = match connect_to_upstream(state.borrow_mut()).await {
-        Ok(stream) => stream,
-        Err(_error) => {
-            let response = response::make_http_error(http::StatusCode::BAD_GATEWAY);
-            send_response(&mut client_conn, &response).await;
-            return;
-        }
-    };
-    let upstream_ip = client_conn.peer_addr().unwrap().ip().to_string();
+    // The upstream we are currently connected to and the connection to it. This is opened when
+    // the first request arrives, and is only replaced if consistent hashing routes a later request
+    // to a different upstream.
+    let mut upstream: Option<(String, UpstreamConn)> = None;
 
     // The client may now send us one or more requests. Keep trying to read requests until the
     // client hangs up or we get an error.
@@ -302,45 +718,136 @@ async fn handle_connection(mut client_conn: TcpStream, state: Arc<Mutex<ProxySta
                 continue;
             }
         };
+        request.extensions_mut().insert(ClientInfo { addr: client_addr });
+        let rejection = {
+            let state = state.lock().await;
+            middleware::process_request(&state.middlewares, &mut request).await
+        };
+        if let Some(response) = rejection {
+            send_response(&mut client_conn, &response).await;
+            continue;
+        }
+        let (key, needs_connect) = {
+            let state = state.lock().await;
+            let key = routing_key(&state, &request, &client_ip);
+            let needs_connect = match (&upstream, &key) {
+                (None, _) => true,
+                (Some((current, _)), Some(key)) => consistent_hash::select(key, &state.ring)
+                    .map_or(true, |idx| &state.upstream_addresses[idx] != current),
+                (Some(_), None) => false,
+            };
+            (key, needs_connect)
+        };
+        if needs_connect {
+            upstream = match connect_to_upstream(&state, key.as_deref(), client_addr).await {
+                Ok(upstream) => Some(upstream),
+                Err(_error) => {
+                    let response = response::make_http_error(http::StatusCode::BAD_GATEWAY);
+                    send_response(&mut client_conn, &response).await;
+                    return;
+                }
+            };
+        }
+        let (upstream_ip, upstream_conn) = upstream.as_mut().unwrap();
         log::info!(
             "{} -> {}: {}",
             client_ip,
             upstream_ip,
             request::format_request_line(&request)
         );
-        if state.max_requests_per_minute != 0 {
-            if *state.traffic_record.entry(client_ip.clone()).and_modify(|n| *n+=1).or_insert(1) > state.max_requests_per_minute as u64 {
-                let response = response::make_http_error(http::StatusCode::TOO_MANY_REQUESTS);
-                send_response(&mut client_conn, &response).await;
-                return;
+        let (mirror_upstream, mirror_percent, max_response_body) = {
+            let state = state.lock().await;
+            for name in &state.strip_request_headers {
+                request.headers_mut().remove(name.as_str());
             }
-        }
-        // Add X-Forwarded-For header so that the upstream server knows the client's IP address.
-        // (We're the ones connecting directly to the upstream server, so without this header, the
-        // upstream server will only know our IP, not the client's.)
-        request::extend_header_value(&mut request, "x-forwarded-for", &client_ip);
+            (state.mirror_upstream.clone(), state.mirror_percent, state.max_response_body)
+        };
 
         // Forward the request to the server
-        if let Err(error) = request::write_to_stream(&request, &mut upstream_conn).await {
+        let started_at = Instant::now();
+        if let Err(error) = request::write_to_stream(&request, upstream_conn).await {
             log::error!("Failed to send request to upstream {}: {}", upstream_ip, error);
+            state.lock().await.stats_for(upstream_ip).record_error();
             let response = response::make_http_error(http::StatusCode::BAD_GATEWAY);
             send_response(&mut client_conn, &response).await;
             return;
         }
         log::debug!("Forwarded request to server");
 
+        // Copy a sample of requests to the shadow upstream, if there is one
+        if let Some(mirror_address) = mirror_upstream {
+            if rand::thread_rng().gen_range(0, 100) < mirror_percent {
+                task::spawn(mirror_request(mirror_address, request::clone_request(&request)));
+            }
+        }
+
         // Read the server's response
-        let response = match response::read_from_stream(&mut upstream_conn, request.method()).await {
+        let mut response = match response::read_from_stream(
+            upstream_conn,
+            request.method(),
+            max_response_body,
+        )
+        .await
+        {
             Ok(response) => response,
+            Err(response::Error::ResponseBodyTooLarge) => {
+                log::warn!(
+                    "Response from upstream {} exceeds the maximum body size of {} bytes",
+                    upstream_ip,
+                    max_response_body
+                );
+                state.lock().await.stats_for(upstream_ip).record_error();
+                // The rest of the body is still in flight, so this connection can't be reused
+                upstream = None;
+                let response = response::make_http_error(http::StatusCode::BAD_GATEWAY);
+                send_response(&mut client_conn, &response).await;
+                continue;
+            }
             Err(error) => {
                 log::error!("Error reading response from server: {:?}", error);
+                state.lock().await.stats_for(upstream_ip).record_error();
                 let response = response::make_http_error(http::StatusCode::BAD_GATEWAY);
                 send_response(&mut client_conn, &response).await;
                 return;
             }
         };
+        let max_client_bandwidth = {
+            let mut state = state.lock().await;
+            state.stats_for(upstream_ip).record_success(
+                request.body().len(),
+                response.body().len(),
+                started_at.elapsed(),
+            );
+            for name in &state.strip_response_headers {
+                response.headers_mut().remove(name.as_str());
+            }
+            if state.override_response_headers {
+                for (name, _) in &state.add_response_headers {
+                    response.headers_mut().remove(name);
+                }
+            }
+            for (name, value) in &state.add_response_headers {
+                response.headers_mut().append(name.clone(), value.clone());
+            }
+            middleware::process_response(&state.middlewares, &mut response).await;
+            state.max_client_bandwidth
+        };
         // Forward the response to the client
-        send_response(&mut client_conn, &response).await;
+        if max_client_bandwidth == 0 {
+            send_response(&mut client_conn, &response).await;
+        } else {
+            let bucket = Arc::clone(
+                state
+                    .lock()
+                    .await
+                    .bandwidth_buckets
+                    .entry(client_ip.clone())
+                    .or_insert_with(|| Arc::new(Mutex::new(TokenBucket::new(max_client_bandwidth)))),
+            );
+            // Connections from the same IP share the bucket, so they take turns writing
+            let mut bucket = bucket.lock().await;
+            send_throttled_response(&mut client_conn, &response, &mut bucket).await;
+        }
         log::debug!("Forwarded response to client");
     }
 }
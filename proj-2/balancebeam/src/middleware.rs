@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// A step that every proxied request and response passes through. Middleware runs in order on
+/// requests, after they are read from the client and before they are forwarded, and in reverse
+/// order on responses, before they are sent back to the client.
+#[async_trait]
+pub trait Middleware {
+    /// Inspects or modifies a request. Returning Some stops processing: the request is not
+    /// forwarded and the returned response is sent to the client instead.
+    async fn process_request(&self, req: &mut http::Request<Vec<u8>>)
+        -> Option<http::Response<Vec<u8>>>;
+
+    /// Inspects or modifies a response from the upstream.
+    async fn process_response(&self, _resp: &mut http::Response<Vec<u8>>) {}
+}
+
+/// The boxed form middleware is kept in.
+pub type BoxedMiddleware = Box<dyn Middleware + Send + Sync>;
+
+/// Details about the client connection a request arrived on. handle_connection stores this in
+/// each request's extensions so that middleware can get at it.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientInfo {
+    pub addr: SocketAddr,
+}
+
+/// Runs a request through each middleware in turn, stopping at the first one that answers it.
+pub async fn process_request(
+    chain: &[BoxedMiddleware],
+    req: &mut http::Request<Vec<u8>>,
+) -> Option<http::Response<Vec<u8>>> {
+    for middleware in chain {
+        if let Some(response) = middleware.process_request(req).await {
+            return Some(response);
+        }
+    }
+    None
+}
+
+/// Runs a response through each middleware, last to first.
+pub async fn process_response(chain: &[BoxedMiddleware], resp: &mut http::Response<Vec<u8>>) {
+    for middleware in chain.iter().rev() {
+        middleware.process_response(resp).await;
+    }
+}
+
+fn client_info(req: &http::Request<Vec<u8>>) -> Option<ClientInfo> {
+    req.extensions().get::<ClientInfo>().copied()
+}
+
+/// Answers with 429 Too Many Requests once a client IP has made more than a set number of
+/// requests in the current one-minute window.
+pub struct RateLimit {
+    max_requests_per_minute: u64,
+    /// Start of the current window, and the number of requests seen from each IP during it
+    window: parking_lot::Mutex<(Instant, HashMap<String, u64>)>,
+}
+
+impl RateLimit {
+    pub fn new(max_requests_per_minute: usize) -> RateLimit {
+        RateLimit {
+            max_requests_per_minute: max_requests_per_minute as u64,
+            window: parking_lot::Mutex::new((Instant::now(), HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimit {
+    async fn process_request(
+        &self,
+        req: &mut http::Request<Vec<u8>>,
+    ) -> Option<http::Response<Vec<u8>>> {
+        let client_ip = client_info(req)?.addr.ip().to_string();
+        let mut window = self.window.lock();
+        if window.0.elapsed() >= Duration::from_secs(60) {
+            *window = (Instant::now(), HashMap::new());
+        }
+        let count = window.1.entry(client_ip).or_insert(0);
+        *count += 1;
+        if *count > self.max_requests_per_minute {
+            Some(crate::response::make_http_error(http::StatusCode::TOO_MANY_REQUESTS))
+        } else {
+            None
+        }
+    }
+}
+
+/// Tells the upstream who the client is and how it reached us, via the X-Forwarded-For,
+/// X-Forwarded-Proto and X-Forwarded-Host headers. (We're the ones connecting directly to the
+/// upstream server, so without these headers, the upstream server will only know about us, not
+/// the client.)
+pub struct ForwardedHeaders;
+
+#[async_trait]
+impl Middleware for ForwardedHeaders {
+    async fn process_request(
+        &self,
+        req: &mut http::Request<Vec<u8>>,
+    ) -> Option<http::Response<Vec<u8>>> {
+        let client = client_info(req)?;
+        crate::request::extend_header_value(req, "x-forwarded-for", &client.addr.ip().to_string());
+        // balancebeam only listens for plain HTTP
+        crate::request::extend_header_value(req, "x-forwarded-proto", "http");
+        if let Some(host) = req.headers().get("host").and_then(|host| host.to_str().ok()) {
+            let host = host.to_string();
+            crate::request::extend_header_value(req, "x-forwarded-host", &host);
+        }
+        None
+    }
+}
+
+/// Gives every request an X-Request-Id header, so that it can be traced through the upstream's
+/// logs. Requests that already carry one keep it.
+pub struct RequestId;
+
+#[async_trait]
+impl Middleware for RequestId {
+    async fn process_request(
+        &self,
+        req: &mut http::Request<Vec<u8>>,
+    ) -> Option<http::Response<Vec<u8>>> {
+        if !req.headers().contains_key("x-request-id") {
+            let id = format!("{:032x}", rand::random::<u128>());
+            req.headers_mut()
+                .insert("x-request-id", http::HeaderValue::from_str(&id).unwrap());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Sets a header on requests and responses.
+    struct SetHeader(&'static str);
+
+    #[async_trait]
+    impl Middleware for SetHeader {
+        async fn process_request(
+            &self,
+            req: &mut http::Request<Vec<u8>>,
+        ) -> Option<http::Response<Vec<u8>>> {
+            req.headers_mut()
+                .insert("x-test", http::HeaderValue::from_static(self.0));
+            None
+        }
+
+        async fn process_response(&self, resp: &mut http::Response<Vec<u8>>) {
+            resp.headers_mut()
+                .insert("x-test", http::HeaderValue::from_static(self.0));
+        }
+    }
+
+    fn request_from(addr: &str) -> http::Request<Vec<u8>> {
+        let mut req = http::Request::get("/").header("Host", "example.com").body(Vec::new()).unwrap();
+        req.extensions_mut().insert(ClientInfo { addr: addr.parse().unwrap() });
+        req
+    }
+
+    #[tokio::test]
+    async fn test_chain_order() {
+        let chain: Vec<BoxedMiddleware> = vec![Box::new(SetHeader("first")), Box::new(SetHeader("second"))];
+        let mut req = request_from("10.0.0.1:5000");
+        assert!(process_request(&chain, &mut req).await.is_none());
+        assert_eq!(req.headers()["x-test"], "second");
+
+        let mut resp = http::Response::new(Vec::new());
+        process_response(&chain, &mut resp).await;
+        assert_eq!(resp.headers()["x-test"], "first");
+    }
+
+    #[tokio::test]
+    async fn test_short_circuit() {
+        let chain: Vec<BoxedMiddleware> = vec![Box::new(RateLimit::new(1)), Box::new(SetHeader("set"))];
+        let mut req = request_from("10.0.0.1:5000");
+        assert!(process_request(&chain, &mut req).await.is_none());
+        let mut req = request_from("10.0.0.1:5001");
+        let response = process_request(&chain, &mut req).await.expect("Second request should be limited");
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert!(!req.headers().contains_key("x-test"));
+        // Other clients have their own allowance
+        let mut req = request_from("10.0.0.2:5000");
+        assert!(process_request(&chain, &mut req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_headers() {
+        let chain: Vec<BoxedMiddleware> = vec![Box::new(ForwardedHeaders), Box::new(RequestId)];
+        let mut req = request_from("10.0.0.1:5000");
+        process_request(&chain, &mut req).await;
+        assert_eq!(req.headers()["x-forwarded-for"], "10.0.0.1");
+        assert_eq!(req.headers()["x-forwarded-proto"], "http");
+        assert_eq!(req.headers()["x-forwarded-host"], "example.com");
+        assert_eq!(req.headers()["x-request-id"].len(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_headers_without_host() {
+        let chain: Vec<BoxedMiddleware> = vec![Box::new(ForwardedHeaders)];
+        let mut req = http::Request::get("/").body(Vec::new()).unwrap();
+        req.extensions_mut().insert(ClientInfo { addr: "10.0.0.1:5000".parse().unwrap() });
+        process_request(&chain, &mut req).await;
+        assert_eq!(req.headers()["x-forwarded-for"], "10.0.0.1");
+        // Requests without a Host header don't get an X-Forwarded-Host
+        assert!(!req.headers().contains_key("x-forwarded-host"));
+    }
+}
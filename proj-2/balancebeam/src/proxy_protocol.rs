@@ -0,0 +1,74 @@
+use std::net::SocketAddr;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Header sent on connections that do not relay a client connection (e.g. health checks).
+const UNKNOWN_HEADER: &str = "PROXY UNKNOWN\r\n";
+
+/// Formats a PROXY protocol v1 header describing a connection from `src` to `dst`. The protocol
+/// requires both addresses to belong to the same family; if they don't, the connection is
+/// described as UNKNOWN.
+pub fn format_proxy_v1_header(src: SocketAddr, dst: SocketAddr) -> String {
+    let family = match (src, dst) {
+        (SocketAddr::V4(_), SocketAddr::V4(_)) => "TCP4",
+        (SocketAddr::V6(_), SocketAddr::V6(_)) => "TCP6",
+        _ => return UNKNOWN_HEADER.to_string(),
+    };
+    format!(
+        "PROXY {} {} {} {} {}\r\n",
+        family,
+        src.ip(),
+        dst.ip(),
+        src.port(),
+        dst.port()
+    )
+}
+
+/// Writes a PROXY protocol v1 header to a freshly opened upstream connection, so the upstream
+/// learns the client's real address. This must be sent before any other data.
+pub async fn write_proxy_v1_header<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    src: SocketAddr,
+    dst: SocketAddr,
+) -> Result<(), std::io::Error> {
+    stream
+        .write_all(format_proxy_v1_header(src, dst).as_bytes())
+        .await
+}
+
+/// Writes a PROXY protocol v1 header for a connection that balancebeam makes on its own behalf, or
+/// for which there is no TCP destination address to report (e.g. Unix socket upstreams).
+pub async fn write_proxy_v1_unknown_header<S: AsyncWrite + Unpin>(stream: &mut S) -> Result<(), std::io::Error> {
+    stream.write_all(UNKNOWN_HEADER.as_bytes()).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_tcp4() {
+        let src = "192.168.0.1:56324".parse().unwrap();
+        let dst = "10.0.0.2:8080".parse().unwrap();
+        assert_eq!(
+            format_proxy_v1_header(src, dst),
+            "PROXY TCP4 192.168.0.1 10.0.0.2 56324 8080\r\n"
+        );
+    }
+
+    #[test]
+    fn test_format_tcp6() {
+        let src = "[::1]:56324".parse().unwrap();
+        let dst = "[2001:db8::2]:443".parse().unwrap();
+        assert_eq!(
+            format_proxy_v1_header(src, dst),
+            "PROXY TCP6 ::1 2001:db8::2 56324 443\r\n"
+        );
+    }
+
+    #[test]
+    fn test_format_mixed_families() {
+        let src = "192.168.0.1:56324".parse().unwrap();
+        let dst = "[::1]:443".parse().unwrap();
+        assert_eq!(format_proxy_v1_header(src, dst), "PROXY UNKNOWN\r\n");
+    }
+}
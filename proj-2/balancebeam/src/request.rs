@@ -1,6 +1,5 @@
 use std::cmp::min;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const MAX_HEADERS_SIZE: usize = 8000;
 const MAX_BODY_SIZE: usize = 10000000;
@@ -19,7 +18,7 @@ pub enum Error {
     ContentLengthMismatch,
     /// The request body is bigger than MAX_BODY_SIZE
     RequestBodyTooLarge,
-    /// Encountered an I/O error when reading/writing a TcpStream
+    /// Encountered an I/O error when reading/writing a stream
     ConnectionError(std::io::Error),
 }
 
@@ -66,6 +65,19 @@ pub fn extend_header_value(
         .insert(name, http::HeaderValue::from_bytes(&new_value).unwrap());
 }
 
+/// Makes a copy of a request, including its headers and body. (http::Request does not implement
+/// Clone, since its extensions may not be cloneable; we never use extensions.)
+pub fn clone_request(request: &http::Request<Vec<u8>>) -> http::Request<Vec<u8>> {
+    let mut copy = http::Request::builder()
+        .method(request.method().clone())
+        .uri(request.uri().clone())
+        .version(request.version())
+        .body(request.body().clone())
+        .unwrap();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
 /// Attempts to parse the data in the supplied buffer as an HTTP request. Returns one of the
 /// following:
 ///
@@ -101,7 +113,7 @@ fn parse_request(buffer: &[u8]) -> Result<Option<(http::Request<Vec<u8>>, usize)
 /// Returns Ok(http::Request) if a valid request is received, or Error if not.
 ///
 /// You will need to modify this function in Milestone 2.
-async fn read_headers(stream: &mut TcpStream) -> Result<http::Request<Vec<u8>>, Error> {
+async fn read_headers<S: AsyncRead + Unpin>(stream: &mut S) -> Result<http::Request<Vec<u8>>, Error> {
     // Try reading the headers from the request. We may not receive all the headers in one shot
     // (e.g. we might receive the first few bytes of a request, and then the rest follows later).
     // Try parsing repeatedly until we read a valid HTTP request
@@ -137,8 +149,8 @@ async fn read_headers(stream: &mut TcpStream) -> Result<http::Request<Vec<u8>>,
 /// returns Ok(()) if successful, or Err(Error) if Content-Length bytes couldn't be read.
 ///
 /// You will need to modify this function in Milestone 2.
-async fn read_body(
-    stream: &mut TcpStream,
+async fn read_body<S: AsyncRead + Unpin>(
+    stream: &mut S,
     request: &mut http::Request<Vec<u8>>,
     content_length: usize,
 ) -> Result<(), Error> {
@@ -178,7 +190,7 @@ async fn read_body(
 /// closes the connection prematurely or sends an invalid request.
 ///
 /// You will need to modify this function in Milestone 2.
-pub async fn read_from_stream(stream: &mut TcpStream) -> Result<http::Request<Vec<u8>>, Error> {
+pub async fn read_from_stream<S: AsyncRead + Unpin>(stream: &mut S) -> Result<http::Request<Vec<u8>>, Error> {
     // Read headers
     let mut request = read_headers(stream).await?;
     // Read body if the client supplied the Content-Length header (which it does for POST requests)
@@ -195,9 +207,9 @@ pub async fn read_from_stream(stream: &mut TcpStream) -> Result<http::Request<Ve
 /// This function serializes a request to bytes and writes those bytes to the provided stream.
 ///
 /// You will need to modify this function in Milestone 2.
-pub async fn write_to_stream(
+pub async fn write_to_stream<S: AsyncWrite + Unpin>(
     request: &http::Request<Vec<u8>>,
-    stream: &mut TcpStream,
+    stream: &mut S,
 ) -> Result<(), std::io::Error> {
     stream.write(&format_request_line(request).into_bytes()).await?;
     stream.write(&['\r' as u8, '\n' as u8]).await?; // \r\n
@@ -0,0 +1,85 @@
+use crate::upstream_conn;
+use tokio::net::lookup_host;
+
+/// Resolves each configured upstream to the socket addresses it currently points at. Unix socket
+/// upstreams are passed through unchanged. Upstreams that fail to resolve are logged and
+/// contribute no addresses.
+pub async fn resolve_upstreams(upstreams: &[String]) -> Vec<String> {
+    let mut resolved = Vec::new();
+    for upstream in upstreams {
+        if upstream_conn::is_unix_address(upstream) {
+            resolved.push(upstream.clone());
+            continue;
+        }
+        match lookup_host(upstream.as_str()).await {
+            Ok(addrs) => {
+                for addr in addrs {
+                    let addr = addr.to_string();
+                    if !resolved.contains(&addr) {
+                        resolved.push(addr);
+                    }
+                }
+            }
+            Err(err) => log::error!("Failed to resolve upstream {}: {}", upstream, err),
+        }
+    }
+    resolved
+}
+
+/// Brings `pool` in line with a new resolution. Addresses in `current` that were not in `previous`
+/// are added to the pool, and addresses in `previous` that are no longer in `current` are removed.
+/// Addresses present in both are left alone, so an upstream that health checks have taken out of
+/// the pool is not put back just because DNS still lists it. Returns true if the pool changed.
+pub fn apply_resolution(pool: &mut Vec<String>, previous: &[String], current: &[String]) -> bool {
+    let mut changed = false;
+    for stale in previous.iter().filter(|addr| !current.contains(addr)) {
+        if pool.contains(stale) {
+            log::info!("Upstream {} no longer resolves, removing it", stale);
+            pool.retain(|addr| addr != stale);
+            changed = true;
+        }
+    }
+    for new in current.iter().filter(|addr| !previous.contains(addr)) {
+        if !pool.contains(new) {
+            log::info!("Upstream {} newly resolved, adding it", new);
+            pool.push(new.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn addrs(list: &[&str]) -> Vec<String> {
+        list.iter().map(|addr| addr.to_string()).collect()
+    }
+
+    #[test]
+    fn test_new_address_is_added_and_stale_removed() {
+        let previous = addrs(&["10.0.0.1:80", "10.0.0.2:80"]);
+        let current = addrs(&["10.0.0.2:80", "10.0.0.3:80"]);
+        let mut pool = previous.clone();
+        assert!(apply_resolution(&mut pool, &previous, &current));
+        assert_eq!(pool, addrs(&["10.0.0.2:80", "10.0.0.3:80"]));
+    }
+
+    #[test]
+    fn test_unhealthy_address_is_not_readded() {
+        // 10.0.0.1 was removed from the pool by health checks but still resolves
+        let previous = addrs(&["10.0.0.1:80", "10.0.0.2:80"]);
+        let current = previous.clone();
+        let mut pool = addrs(&["10.0.0.2:80"]);
+        assert!(!apply_resolution(&mut pool, &previous, &current));
+        assert_eq!(pool, addrs(&["10.0.0.2:80"]));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_localhost() {
+        let resolved = resolve_upstreams(&addrs(&["localhost:8080", "unix:/tmp/upstream.sock"])).await;
+        assert!(resolved.contains(&"127.0.0.1:8080".to_string()));
+        assert!(resolved.contains(&"unix:/tmp/upstream.sock".to_string()));
+    }
+}
@@ -1,5 +1,4 @@
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const MAX_HEADERS_SIZE: usize = 8000;
 const MAX_BODY_SIZE: usize = 10000000;
@@ -15,9 +14,9 @@ pub enum Error {
     InvalidContentLength,
     /// The Content-Length header does not match the size of the request body that was sent
     ContentLengthMismatch,
-    /// The request body is bigger than MAX_BODY_SIZE
+    /// The response body is bigger than MAX_BODY_SIZE or the configured maximum body size
     ResponseBodyTooLarge,
-    /// Encountered an I/O error when reading/writing a TcpStream
+    /// Encountered an I/O error when reading/writing a stream
     ConnectionError(std::io::Error),
 }
 
@@ -80,7 +79,7 @@ fn parse_response(buffer: &[u8]) -> Result<Option<(http::Response<Vec<u8>>, usiz
 /// Returns Ok(http::Response) if a valid response is received, or Error if not.
 ///
 /// You will need to modify this function in Milestone 2.
-async fn read_headers(stream: &mut TcpStream) -> Result<http::Response<Vec<u8>>, Error> {
+async fn read_headers<S: AsyncRead + Unpin>(stream: &mut S) -> Result<http::Response<Vec<u8>>, Error> {
     // Try reading the headers from the response. We may not receive all the headers in one shot
     // (e.g. we might receive the first few bytes of a response, and then the rest follows later).
     // Try parsing repeatedly until we read a valid HTTP response
@@ -112,13 +111,23 @@ async fn read_headers(stream: &mut TcpStream) -> Result<http::Response<Vec<u8>>,
 
 /// This function reads the body for a response from the stream. If the Content-Length header is
 /// present, it reads that many bytes; otherwise, it reads bytes until the connection is closed.
+/// Bodies longer than `max_body_size` bytes are rejected (0 means no limit beyond MAX_BODY_SIZE).
 ///
 /// You will need to modify this function in Milestone 2.
-async fn read_body(stream: &mut TcpStream, response: &mut http::Response<Vec<u8>>) -> Result<(), Error> {
+async fn read_body<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    response: &mut http::Response<Vec<u8>>,
+    max_body_size: usize,
+) -> Result<(), Error> {
+    let max_body_size = if max_body_size == 0 { MAX_BODY_SIZE } else { max_body_size.min(MAX_BODY_SIZE) };
     // The response may or may not supply a Content-Length header. If it provides the header, then
     // we want to read that number of bytes; if it does not, we want to keep reading bytes until
     // the connection is closed.
     let content_length = get_content_length(response)?;
+    // No need to read a body we already know we won't accept
+    if content_length.unwrap_or(0) > max_body_size || response.body().len() > max_body_size {
+        return Err(Error::ResponseBodyTooLarge);
+    }
 
     while content_length.is_none() || response.body().len() < content_length.unwrap() {
         let mut buffer = [0_u8; 512];
@@ -144,7 +153,7 @@ async fn read_body(stream: &mut TcpStream, response: &mut http::Response<Vec<u8>
         }
 
         // Make sure server doesn't send more bytes than we allow
-        if response.body().len() + bytes_read > MAX_BODY_SIZE {
+        if response.body().len() + bytes_read > max_body_size {
             return Err(Error::ResponseBodyTooLarge);
         }
 
@@ -155,12 +164,14 @@ async fn read_body(stream: &mut TcpStream, response: &mut http::Response<Vec<u8>
 }
 
 /// This function reads and returns an HTTP response from a stream, returning an Error if the server
-/// closes the connection prematurely or sends an invalid response.
+/// closes the connection prematurely, sends an invalid response, or sends a body longer than
+/// `max_body_size` bytes (0 = no limit).
 ///
 /// You will need to modify this function in Milestone 2.
-pub async fn read_from_stream(
-    stream: &mut TcpStream,
+pub async fn read_from_stream<S: AsyncRead + Unpin>(
+    stream: &mut S,
     request_method: &http::Method,
+    max_body_size: usize,
 ) -> Result<http::Response<Vec<u8>>, Error> {
     let mut response = read_headers(stream).await?;
     // A response may have a body as long as it is not responding to a HEAD request and as long as
@@ -170,7 +181,7 @@ pub async fn read_from_stream(
         || response.status() == http::StatusCode::NO_CONTENT
         || response.status() == http::StatusCode::NOT_MODIFIED)
     {
-        read_body(stream, &mut response).await?;
+        read_body(stream, &mut response, max_body_size).await?;
     }
     Ok(response)
 }
@@ -178,9 +189,9 @@ pub async fn read_from_stream(
 /// This function serializes a response to bytes and writes those bytes to the provided stream.
 ///
 /// You will need to modify this function in Milestone 2.
-pub async fn write_to_stream(
+pub async fn write_to_stream<S: AsyncWrite + Unpin>(
     response: &http::Response<Vec<u8>>,
-    stream: &mut TcpStream,
+    stream: &mut S,
 ) -> Result<(), std::io::Error> {
     stream.write(&format_response_line(response).into_bytes()).await?;
     stream.write(&['\r' as u8, '\n' as u8]).await?; // \r\n
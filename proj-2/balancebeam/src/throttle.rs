@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::delay_for;
+
+/// Largest number of bytes written to the client in one go while throttling.
+const MAX_CHUNK_SIZE: usize = 4096;
+
+/// Limits a byte stream to a steady rate. Tokens (bytes) accumulate at `rate` per second, up to one
+/// second's worth, and each byte written spends one token.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Bytes allowed per second
+    rate: usize,
+    /// Bytes that may be written right now
+    tokens: f64,
+    /// When `tokens` was last topped up
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket allowing `rate` bytes per second.
+    pub fn new(rate: usize) -> TokenBucket {
+        TokenBucket {
+            rate,
+            tokens: rate as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        self.last_refill = now;
+    }
+
+    /// Takes up to `wanted` tokens, returning how many were taken (possibly 0).
+    pub fn take(&mut self, wanted: usize) -> usize {
+        self.refill(Instant::now());
+        let taken = (self.tokens.floor() as usize).min(wanted);
+        self.tokens -= taken as f64;
+        taken
+    }
+
+    /// Whether the bucket has refilled completely, so forgetting it and starting a new one later
+    /// would give the client nothing extra.
+    pub fn is_full(&mut self) -> bool {
+        self.refill(Instant::now());
+        self.tokens >= self.rate as f64
+    }
+
+    /// How long until `wanted` tokens will be available (capped at the bucket's size).
+    pub fn time_until(&self, wanted: usize) -> Duration {
+        let missing = wanted.min(self.rate) as f64 - self.tokens;
+        if missing <= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(missing / self.rate as f64)
+        }
+    }
+}
+
+/// Writes `data` to the client no faster than `bucket` allows, sleeping whenever the bucket runs
+/// dry so that other tasks can run in the meantime.
+pub async fn throttled_write(
+    stream: &mut TcpStream,
+    data: &[u8],
+    bucket: &mut TokenBucket,
+) -> Result<(), std::io::Error> {
+    let mut written = 0;
+    while written < data.len() {
+        let wanted = (data.len() - written).min(MAX_CHUNK_SIZE);
+        let allowed = bucket.take(wanted);
+        if allowed == 0 {
+            delay_for(bucket.time_until(wanted)).await;
+            continue;
+        }
+        stream.write_all(&data[written..written + allowed]).await?;
+        written += allowed;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_starts_full() {
+        let mut bucket = TokenBucket::new(1000);
+        assert_eq!(bucket.take(600), 600);
+        assert_eq!(bucket.take(600), 400);
+        assert_eq!(bucket.take(600), 0);
+    }
+
+    #[test]
+    fn test_is_full() {
+        let mut bucket = TokenBucket::new(1000);
+        assert!(bucket.is_full());
+        bucket.take(1);
+        assert!(!bucket.is_full());
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(bucket.is_full());
+    }
+
+    #[test]
+    fn test_time_until() {
+        let mut bucket = TokenBucket::new(1000);
+        bucket.take(1000);
+        let wait = bucket.time_until(500);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+        // Asking for more than the bucket holds only waits for a full bucket
+        assert!(bucket.time_until(5000) <= Duration::from_secs(1));
+    }
+}
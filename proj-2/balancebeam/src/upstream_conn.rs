@@ -0,0 +1,84 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
+
+/// Prefix marking an upstream address as a Unix domain socket path rather than a TCP host:port.
+const UNIX_PREFIX: &str = "unix:";
+
+/// A connection to an upstream server, either over TCP or over a Unix domain socket.
+pub enum UpstreamConn {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl UpstreamConn {
+    /// Returns the upstream's address for TCP connections, or None for Unix sockets.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            UpstreamConn::Tcp(stream) => stream.peer_addr().ok(),
+            UpstreamConn::Unix(_) => None,
+        }
+    }
+}
+
+/// Returns true if the address names a Unix domain socket (`unix:/path/to/sock`).
+pub fn is_unix_address(address: &str) -> bool {
+    address.starts_with(UNIX_PREFIX)
+}
+
+/// Opens a connection to an upstream. Addresses of the form `unix:/path/to/sock` connect to a
+/// Unix domain socket; anything else is treated as a TCP host:port.
+pub async fn connect(address: &str) -> Result<UpstreamConn, std::io::Error> {
+    if is_unix_address(address) {
+        Ok(UpstreamConn::Unix(
+            UnixStream::connect(&address[UNIX_PREFIX.len()..]).await?,
+        ))
+    } else {
+        Ok(UpstreamConn::Tcp(TcpStream::connect(address).await?))
+    }
+}
+
+impl AsyncRead for UpstreamConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        match self.get_mut() {
+            UpstreamConn::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            UpstreamConn::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for UpstreamConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        match self.get_mut() {
+            UpstreamConn::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            UpstreamConn::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        match self.get_mut() {
+            UpstreamConn::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            UpstreamConn::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        match self.get_mut() {
+            UpstreamConn::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            UpstreamConn::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters describing the traffic balancebeam has proxied to one upstream.
+#[derive(Debug, Default)]
+pub struct UpstreamStats {
+    /// Requests forwarded to the upstream, including ones that failed
+    requests_total: AtomicU64,
+    /// Requests for which the upstream could not be written to or sent back a bad response
+    errors_total: AtomicU64,
+    /// Bytes of request bodies sent to the upstream
+    bytes_sent: AtomicU64,
+    /// Bytes of response bodies received from the upstream
+    bytes_received: AtomicU64,
+    /// Mean time from sending a request to receiving the full response, over successful requests
+    avg_latency_ms: AtomicU64,
+}
+
+/// A point-in-time copy of `UpstreamStats`, as reported by the admin API.
+#[derive(Debug, serde::Serialize)]
+pub struct StatsSnapshot {
+    pub requests_total: u64,
+    pub errors_total: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub avg_latency_ms: u64,
+}
+
+impl UpstreamStats {
+    /// Records a request that was forwarded and answered successfully.
+    pub fn record_success(&self, bytes_sent: usize, bytes_received: usize, latency: Duration) {
+        let requests = self.requests_total.fetch_add(1, Ordering::Relaxed) + 1;
+        let successes = requests - self.errors_total.load(Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes_sent as u64, Ordering::Relaxed);
+        self.bytes_received.fetch_add(bytes_received as u64, Ordering::Relaxed);
+        // Fold this request into the running mean: avg += (sample - avg) / n
+        let sample = latency.as_millis() as i64;
+        let avg = self.avg_latency_ms.load(Ordering::Relaxed) as i64;
+        let avg = avg + (sample - avg) / successes.max(1) as i64;
+        self.avg_latency_ms.store(avg as u64, Ordering::Relaxed);
+    }
+
+    /// Records a request that failed because of the upstream.
+    pub fn record_error(&self) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+        self.errors_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            requests_total: self.requests_total.load(Ordering::Relaxed),
+            errors_total: self.errors_total.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            avg_latency_ms: self.avg_latency_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let stats = UpstreamStats::default();
+        stats.record_success(10, 100, Duration::from_millis(10));
+        stats.record_error();
+        stats.record_success(20, 200, Duration::from_millis(30));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests_total, 3);
+        assert_eq!(snapshot.errors_total, 1);
+        assert_eq!(snapshot.bytes_sent, 30);
+        assert_eq!(snapshot.bytes_received, 300);
+        // Errors don't count towards the latency average
+        assert_eq!(snapshot.avg_latency_ms, 20);
+    }
+}
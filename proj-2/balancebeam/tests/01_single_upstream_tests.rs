@@ -99,3 +99,112 @@ async fn test_multiple_requests_per_connection() {
 
     log::info!("All done :)");
 }
+
+/// With --resolve-interval, an upstream given by hostname should be resolved and keep working as
+/// the resolution is refreshed.
+#[tokio::test]
+async fn test_upstream_hostname_re_resolution() {
+    init_logging();
+    let upstream = EchoServer::new().await;
+    let port = upstream.address.split(':').nth(1).unwrap();
+    let hostname_upstream = format!("localhost:{}", port);
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&hostname_upstream],
+        None,
+        None,
+        &["--resolve-interval", "1"],
+    )
+    .await;
+
+    for i in 0..3 {
+        let path = format!("/resolved-{}", i);
+        let response_text = balancebeam
+            .get(&path)
+            .await
+            .expect("Error sending request to balancebeam");
+        assert!(response_text.contains(&format!("GET {} HTTP/1.1", path)));
+        // Let a re-resolution happen between requests
+        tokio::time::delay_for(std::time::Duration::from_millis(700)).await;
+    }
+
+    let num_requests_received = Box::new(upstream).stop().await;
+    assert_eq!(num_requests_received, 3);
+
+    log::info!("All done :)");
+}
+
+/// With --max-response-body, responses up to the limit should be forwarded and larger ones should
+/// be replaced with a 502. The echo server's response contains the request body, so the size of
+/// the response is controlled by the size of the request.
+#[tokio::test]
+async fn test_max_response_body() {
+    init_logging();
+    let upstream = EchoServer::new().await;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream.address],
+        None,
+        None,
+        &["--max-response-body", "1024"],
+    )
+    .await;
+
+    log::info!("Sending a request with a small response");
+    let small_body = "a".repeat(512);
+    let response_text = balancebeam
+        .post("/small", &small_body)
+        .await
+        .expect("Error sending request to balancebeam");
+    assert!(response_text.contains("POST /small HTTP/1.1"));
+    assert!(response_text.contains(&small_body));
+
+    log::info!("Sending a request with a response over the limit");
+    let large_body = "a".repeat(2048);
+    let response_text = balancebeam
+        .post("/large", &large_body)
+        .await
+        .expect("Error sending request to balancebeam");
+    assert_eq!(response_text, "HTTP 502 Bad Gateway");
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// With --max-client-bandwidth, a large response should take at least as long as the limit
+/// implies. The client is allowed one second's worth of data up front, so 30 KB at 10 KB/s should
+/// take at least two seconds.
+#[tokio::test]
+async fn test_max_client_bandwidth() {
+    init_logging();
+    let upstream = EchoServer::new().await;
+    let bytes_per_second = 10000;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream.address],
+        None,
+        None,
+        &["--max-client-bandwidth", &bytes_per_second.to_string()],
+    )
+    .await;
+
+    let body = "c".repeat(30000);
+    let start = std::time::Instant::now();
+    let response_text = balancebeam
+        .post("/throttled", &body)
+        .await
+        .expect("Error sending request to balancebeam");
+    let elapsed = start.elapsed().as_secs_f64();
+    assert!(response_text.contains(&body));
+
+    // One second's worth is allowed as a burst, the rest has to be paced
+    let max_bytes = bytes_per_second as f64 * (elapsed + 1.0);
+    log::info!("Received {} bytes in {:.2}s", response_text.len(), elapsed);
+    assert!(
+        (response_text.len() as f64) <= max_bytes,
+        "Received {} bytes in {:.2}s, faster than {} bytes/s allows",
+        response_text.len(),
+        elapsed,
+        bytes_per_second
+    );
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
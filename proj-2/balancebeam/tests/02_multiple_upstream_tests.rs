@@ -9,6 +9,21 @@ async fn setup_with_params(
     n_upstreams: usize,
     active_health_check_interval: Option<usize>,
     max_requests_per_minute: Option<usize>,
+) -> (BalanceBeam, Vec<Box<dyn Server>>) {
+    setup_with_args(
+        n_upstreams,
+        active_health_check_interval,
+        max_requests_per_minute,
+        &[],
+    )
+    .await
+}
+
+async fn setup_with_args(
+    n_upstreams: usize,
+    active_health_check_interval: Option<usize>,
+    max_requests_per_minute: Option<usize>,
+    extra_args: &[&str],
 ) -> (BalanceBeam, Vec<Box<dyn Server>>) {
     init_logging();
     let mut upstreams: Vec<Box<dyn Server>> = Vec::new();
@@ -23,10 +38,11 @@ async fn setup_with_params(
         .iter()
         .map(|addr| addr.as_str())
         .collect();
-    let balancebeam = BalanceBeam::new(
+    let balancebeam = BalanceBeam::new_with_args(
         &upstream_addresses,
         active_health_check_interval,
         max_requests_per_minute,
+        extra_args,
     )
     .await;
     (balancebeam, upstreams)
@@ -240,6 +256,100 @@ async fn test_active_health_checks_restore_failed_upstream() {
     log::info!("All done :)");
 }
 
+/// Make sure a single failed health check does not remove an upstream when the failure threshold
+/// is higher than one:
+///
+/// * Take one upstream down for roughly one health check interval, then bring it back
+/// * Send some requests, and make sure the upstream that briefly failed still receives some
+///
+/// The recovery threshold is set high enough that the upstream could not have been removed and
+/// restored during the test.
+#[tokio::test]
+async fn test_transient_health_check_failure_is_tolerated() {
+    let n_upstreams = 2;
+    let (balancebeam, mut upstreams) = setup_with_args(
+        n_upstreams,
+        Some(1),
+        None,
+        &["--health-fail-threshold", "3", "--health-recover-threshold", "100"],
+    )
+    .await;
+    let flaky_ip = upstreams[upstreams.len() - 1].address();
+
+    log::info!("Taking one upstream down for a single health check...");
+    upstreams.pop().unwrap().stop().await;
+    delay_for(Duration::from_millis(1200)).await;
+    upstreams.push(Box::new(EchoServer::new_at_address(flaky_ip).await));
+    delay_for(Duration::from_millis(1200)).await;
+
+    log::info!("Sending some requests");
+    for i in 0..10 {
+        let path = format!("/after-blip-{}", i);
+        let response_text = balancebeam
+            .get(&path)
+            .await
+            .expect("Error sending request to balancebeam");
+        assert!(response_text.contains(&format!("GET {} HTTP/1.1", path)));
+    }
+
+    let flaky_upstream_req_count = upstreams.pop().unwrap().stop().await;
+    assert!(
+        flaky_upstream_req_count > 0,
+        "An upstream that failed a single health check was removed from the pool"
+    );
+
+    while let Some(upstream) = upstreams.pop() {
+        upstream.stop().await;
+    }
+
+    log::info!("All done :)");
+}
+
+/// With consistent hashing, requests carrying the same hash key should all reach the same upstream
+#[tokio::test]
+async fn test_consistent_hash_routes_same_key_to_same_upstream() {
+    let n_upstreams = 3;
+    let n_requests = 15;
+    let (balancebeam, mut upstreams) = setup_with_args(
+        n_upstreams,
+        None,
+        None,
+        &["--strategy", "consistent-hash", "--hash-header", "x-user-id"],
+    )
+    .await;
+
+    for i in 0..n_requests {
+        let client = reqwest::Client::new();
+        let path = format!("/hashed-{}", i);
+        let response_text = client
+            .get(&format!("http://{}{}", balancebeam.address, path))
+            .header("x-sent-by", "balancebeam-tests")
+            .header("x-user-id", "user-42")
+            .send()
+            .await
+            .expect("Error sending request to balancebeam")
+            .text()
+            .await
+            .expect("Balancebeam replied with a malformed response");
+        assert!(response_text.contains(&format!("GET {} HTTP/1.1", path)));
+    }
+
+    let mut request_counters = Vec::new();
+    while let Some(upstream) = upstreams.pop() {
+        request_counters.insert(0, upstream.stop().await);
+    }
+    log::info!(
+        "Number of requests received by each upstream: {:?}",
+        request_counters
+    );
+    assert!(
+        request_counters.contains(&n_requests),
+        "Requests with the same hash key were spread across upstreams"
+    );
+
+    log::info!("All done :)");
+}
+
 /// Enable rate limiting and ensure that requests fail after sending more than the threshold
 #[tokio::test]
 async fn test_rate_limiting() {
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{init_logging, BalanceBeam, EchoServer, Server};
+
+async fn setup_with_args(extra_args: &[&str]) -> (BalanceBeam, EchoServer) {
+    init_logging();
+    let upstream = EchoServer::new().await;
+    let balancebeam = BalanceBeam::new_with_args(&[&upstream.address], None, None, extra_args).await;
+    (balancebeam, upstream)
+}
+
+/// Connections from a denied network should be closed before any request is forwarded.
+#[tokio::test]
+async fn test_denied_ip_is_rejected() {
+    let (balancebeam, upstream) = setup_with_args(&["--deny-ip", "127.0.0.0/8"]).await;
+
+    log::info!("Sending a request from a denied IP");
+    let result = balancebeam.get("/denied").await;
+    assert!(
+        result.is_err(),
+        "Expected the connection to be reset, but got a response: {:?}",
+        result
+    );
+
+    let num_requests_received = Box::new(upstream).stop().await;
+    assert_eq!(
+        num_requests_received, 0,
+        "Upstream server received a request from a denied IP"
+    );
+
+    log::info!("All done :)");
+}
+
+/// Connections from an allowed network should be proxied as usual.
+#[tokio::test]
+async fn test_allowed_ip_proceeds() {
+    let (balancebeam, upstream) = setup_with_args(&["--allow-ip", "127.0.0.1/32"]).await;
+
+    log::info!("Sending a request from an allowed IP");
+    let response_text = balancebeam
+        .get("/allowed")
+        .await
+        .expect("Error sending request to balancebeam");
+    assert!(response_text.contains("GET /allowed HTTP/1.1"));
+
+    let num_requests_received = Box::new(upstream).stop().await;
+    assert_eq!(
+        num_requests_received, 1,
+        "Upstream server did not receive the expected number of requests"
+    );
+
+    log::info!("All done :)");
+}
+
+/// An IP that is not in a non-empty allow list should be rejected, and deny should win when an IP
+/// appears in both lists.
+#[tokio::test]
+async fn test_deny_takes_precedence_over_allow() {
+    let (balancebeam, upstream) = setup_with_args(&[
+        "--allow-ip",
+        "127.0.0.0/8",
+        "--deny-ip",
+        "127.0.0.1/32",
+    ])
+    .await;
+
+    log::info!("Sending a request from an IP that is both allowed and denied");
+    assert!(balancebeam.get("/both").await.is_err());
+
+    let num_requests_received = Box::new(upstream).stop().await;
+    assert_eq!(num_requests_received, 0);
+
+    log::info!("All done :)");
+}
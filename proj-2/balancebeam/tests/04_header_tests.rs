@@ -0,0 +1,153 @@
+mod common;
+
+use common::{init_logging, BalanceBeam, EchoServer, Server};
+
+async fn setup_with_args(extra_args: &[&str]) -> (BalanceBeam, EchoServer) {
+    init_logging();
+    let upstream = EchoServer::new().await;
+    let balancebeam = BalanceBeam::new_with_args(&[&upstream.address], None, None, extra_args).await;
+    (balancebeam, upstream)
+}
+
+async fn get_with_authorization(balancebeam: &BalanceBeam, path: &str) -> reqwest::Response {
+    let client = reqwest::Client::new();
+    client
+        .get(&format!("http://{}{}", balancebeam.address, path))
+        .header("x-sent-by", "balancebeam-tests")
+        .header("Authorization", "Bearer secret-token")
+        .send()
+        .await
+        .expect("Error sending request to balancebeam")
+}
+
+/// Without --strip-request-header, client headers should reach the upstream untouched.
+#[tokio::test]
+async fn test_request_header_forwarded_by_default() {
+    let (balancebeam, upstream) = setup_with_args(&[]).await;
+
+    log::info!("Sending a request with an Authorization header");
+    let response_text = get_with_authorization(&balancebeam, "/auth")
+        .await
+        .text()
+        .await
+        .expect("Balancebeam replied with a malformed response");
+    assert!(response_text.contains("authorization: Bearer secret-token"));
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// Headers named with --strip-request-header should be removed before forwarding, regardless of
+/// the case used on the command line.
+#[tokio::test]
+async fn test_strip_request_header() {
+    let (balancebeam, upstream) =
+        setup_with_args(&["--strip-request-header", "AUTHORIZATION"]).await;
+
+    log::info!("Sending a request with an Authorization header");
+    let response_text = get_with_authorization(&balancebeam, "/auth")
+        .await
+        .text()
+        .await
+        .expect("Balancebeam replied with a malformed response");
+    assert!(response_text.contains("GET /auth HTTP/1.1"));
+    assert!(response_text.contains("x-sent-by: balancebeam-tests"));
+    assert!(
+        !response_text.contains("authorization"),
+        "Authorization header was forwarded to the upstream even though it should be stripped"
+    );
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// Headers named with --strip-response-header should be removed from the upstream response.
+#[tokio::test]
+async fn test_strip_response_header() {
+    let (balancebeam, upstream) = setup_with_args(&["--strip-response-header", "Date"]).await;
+
+    let response = get_with_authorization(&balancebeam, "/date").await;
+    assert!(response.status().is_success());
+    assert!(
+        response.headers().get("date").is_none(),
+        "Date header was forwarded to the client even though it should be stripped"
+    );
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// Headers given with --add-response-header should appear in every response. By default they are
+/// added alongside any header of the same name that the upstream sent.
+#[tokio::test]
+async fn test_add_response_header() {
+    let (balancebeam, upstream) = setup_with_args(&[
+        "--add-response-header",
+        "X-Content-Type-Options: nosniff",
+        "--add-response-header",
+        "Date:injected",
+    ])
+    .await;
+
+    let response = get_with_authorization(&balancebeam, "/add").await;
+    assert_eq!(
+        response.headers().get("x-content-type-options").unwrap(),
+        "nosniff"
+    );
+    let dates: Vec<_> = response.headers().get_all("date").iter().collect();
+    assert_eq!(dates.len(), 2, "Injected Date header should be appended to the upstream's");
+    assert!(dates.iter().any(|value| *value == "injected"));
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// With --override-response-header, added headers replace those sent by the upstream.
+#[tokio::test]
+async fn test_override_response_header() {
+    let (balancebeam, upstream) = setup_with_args(&[
+        "--add-response-header",
+        "Date:injected",
+        "--override-response-header",
+    ])
+    .await;
+
+    let response = get_with_authorization(&balancebeam, "/override").await;
+    let dates: Vec<_> = response.headers().get_all("date").iter().collect();
+    assert_eq!(dates, vec!["injected"]);
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// The upstream should be told the scheme and Host the client used. balancebeam only accepts plain
+/// HTTP connections, so the scheme is always http.
+#[tokio::test]
+async fn test_forwarded_proto_and_host_added() {
+    let (balancebeam, upstream) = setup_with_args(&[]).await;
+
+    let response_text = balancebeam
+        .get("/forwarded")
+        .await
+        .expect("Error sending request to balancebeam");
+    assert!(response_text.contains("x-forwarded-proto: http\n"));
+    assert!(response_text.contains(&format!("x-forwarded-host: {}", balancebeam.address)));
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// With --add-request-id, the upstream should see an X-Request-Id header on every request.
+#[tokio::test]
+async fn test_request_id_added() {
+    let (balancebeam, upstream) = setup_with_args(&["--add-request-id"]).await;
+
+    let response_text = balancebeam
+        .get("/request-id")
+        .await
+        .expect("Error sending request to balancebeam");
+    assert!(response_text.contains("x-request-id: "));
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
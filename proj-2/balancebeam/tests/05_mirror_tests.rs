@@ -0,0 +1,94 @@
+mod common;
+
+use common::{init_logging, BalanceBeam, EchoServer, Server};
+use std::time::Duration;
+use tokio::time::delay_for;
+
+async fn send_requests(balancebeam: &BalanceBeam, n_requests: usize) {
+    for i in 0..n_requests {
+        let path = format!("/mirrored-{}", i);
+        let response_text = balancebeam
+            .post(&path, "Hello mirror!")
+            .await
+            .expect("Error sending request to balancebeam");
+        assert!(response_text.contains(&format!("POST {} HTTP/1.1", path)));
+        assert!(response_text.contains("\n\nHello mirror!"));
+    }
+}
+
+/// Every request should be delivered to both the primary and the shadow upstream.
+#[tokio::test]
+async fn test_mirror_receives_requests() {
+    init_logging();
+    let n_requests = 5;
+    let upstream = EchoServer::new().await;
+    let mirror = EchoServer::new().await;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream.address],
+        None,
+        None,
+        &["--mirror-upstream", &mirror.address],
+    )
+    .await;
+
+    send_requests(&balancebeam, n_requests).await;
+
+    log::info!("Giving mirrored requests a moment to arrive");
+    delay_for(Duration::from_millis(500)).await;
+    assert_eq!(Box::new(upstream).stop().await, n_requests);
+    assert_eq!(
+        Box::new(mirror).stop().await,
+        n_requests,
+        "Shadow upstream did not receive a copy of every request"
+    );
+
+    log::info!("All done :)");
+}
+
+/// A mirror that cannot be reached must not affect responses to the client.
+#[tokio::test]
+async fn test_mirror_errors_are_swallowed() {
+    init_logging();
+    let n_requests = 5;
+    let upstream = EchoServer::new().await;
+    // Nothing is listening on this port
+    let dead_mirror = EchoServer::new().await;
+    let dead_mirror_address = dead_mirror.address.clone();
+    Box::new(dead_mirror).stop().await;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream.address],
+        None,
+        None,
+        &["--mirror-upstream", &dead_mirror_address],
+    )
+    .await;
+
+    send_requests(&balancebeam, n_requests).await;
+    assert_eq!(Box::new(upstream).stop().await, n_requests);
+
+    log::info!("All done :)");
+}
+
+/// With --mirror-percent 0, nothing should be copied to the shadow upstream.
+#[tokio::test]
+async fn test_mirror_percent_zero() {
+    init_logging();
+    let n_requests = 5;
+    let upstream = EchoServer::new().await;
+    let mirror = EchoServer::new().await;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream.address],
+        None,
+        None,
+        &["--mirror-upstream", &mirror.address, "--mirror-percent", "0"],
+    )
+    .await;
+
+    send_requests(&balancebeam, n_requests).await;
+
+    delay_for(Duration::from_millis(500)).await;
+    Box::new(upstream).stop().await;
+    assert_eq!(Box::new(mirror).stop().await, 0);
+
+    log::info!("All done :)");
+}
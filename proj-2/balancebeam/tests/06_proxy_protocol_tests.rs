@@ -0,0 +1,116 @@
+mod common;
+
+use common::{init_logging, BalanceBeam};
+use rand::Rng;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves a single connection the way a PROXY-protocol-aware backend would: the connection must
+/// start with a PROXY line, which is echoed back as the body of every response. Connections
+/// without the header are rejected with a 400.
+async fn serve_connection(stream: TcpStream) {
+    let mut stream = BufReader::new(stream);
+    let mut proxy_line = String::new();
+    if stream.read_line(&mut proxy_line).await.unwrap_or(0) == 0 {
+        return;
+    }
+    let proxy_line = proxy_line.trim_end().to_string();
+    loop {
+        // Read the request line and headers (these requests have no bodies)
+        let mut request_line = String::new();
+        if stream.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        loop {
+            let mut header = String::new();
+            if stream.read_line(&mut header).await.unwrap_or(0) == 0 {
+                return;
+            }
+            if header == "\r\n" {
+                break;
+            }
+        }
+        let (status, body) = if proxy_line.starts_with("PROXY ") {
+            ("200 OK", proxy_line.clone())
+        } else {
+            ("400 Bad Request", "missing PROXY header".to_string())
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        if stream.get_mut().write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn start_proxy_protocol_server() -> String {
+    let mut rng = rand::thread_rng();
+    let address = format!("127.0.0.1:{}", rng.gen_range(1024, 65535));
+    let mut listener = TcpListener::bind(&address)
+        .await
+        .expect("Could not bind PROXY protocol server");
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream));
+        }
+    });
+    address
+}
+
+/// With --upstream-proxy-protocol, the upstream should receive a PROXY line naming the client and
+/// upstream addresses before the HTTP request.
+#[tokio::test]
+async fn test_upstream_receives_proxy_header() {
+    init_logging();
+    let upstream_address = start_proxy_protocol_server().await;
+    let balancebeam = BalanceBeam::new_with_args(
+        &[&upstream_address],
+        None,
+        None,
+        &["--upstream-proxy-protocol"],
+    )
+    .await;
+
+    let response_text = balancebeam
+        .get("/proxied")
+        .await
+        .expect("Error sending request to balancebeam");
+    log::info!("Upstream saw PROXY header {:?}", response_text);
+
+    let fields: Vec<&str> = response_text.split(' ').collect();
+    assert_eq!(fields.len(), 6, "Malformed PROXY header {:?}", response_text);
+    assert_eq!(fields[0], "PROXY");
+    assert_eq!(fields[1], "TCP4");
+    assert_eq!(fields[2], "127.0.0.1");
+    assert_eq!(fields[3], "127.0.0.1");
+    fields[4]
+        .parse::<u16>()
+        .expect("Client port in PROXY header is not a valid port");
+    assert_eq!(
+        fields[5],
+        upstream_address.split(':').nth(1).unwrap(),
+        "Upstream port in PROXY header does not match the upstream's address"
+    );
+
+    log::info!("All done :)");
+}
+
+/// Without the flag, no PROXY line should be sent.
+#[tokio::test]
+async fn test_no_proxy_header_by_default() {
+    init_logging();
+    let upstream_address = start_proxy_protocol_server().await;
+    let balancebeam = BalanceBeam::new(&[&upstream_address], None, None).await;
+
+    let response_text = balancebeam
+        .get("/not-proxied")
+        .await
+        .expect("Error sending request to balancebeam");
+    assert_eq!(response_text, "missing PROXY header");
+
+    log::info!("All done :)");
+}
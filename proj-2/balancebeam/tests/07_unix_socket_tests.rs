@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{init_logging, BalanceBeam};
+use rand::Rng;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Answers every request on the connection with its request line, like a (very) minimal echo
+/// server.
+async fn serve_connection(stream: UnixStream) {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if stream.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        loop {
+            let mut header = String::new();
+            if stream.read_line(&mut header).await.unwrap_or(0) == 0 {
+                return;
+            }
+            if header == "\r\n" {
+                break;
+            }
+        }
+        let body = request_line.trim_end();
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        if stream.get_mut().write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Starts a server on a fresh Unix socket and returns the socket's path.
+fn start_unix_server() -> std::path::PathBuf {
+    let mut rng = rand::thread_rng();
+    let path = std::env::temp_dir().join(format!(
+        "balancebeam-test-{}.sock",
+        rng.gen_range(0, u32::MAX)
+    ));
+    let mut listener = UnixListener::bind(&path).expect("Could not bind Unix socket");
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream));
+        }
+    });
+    path
+}
+
+/// Requests should be proxied to an upstream given as unix:/path/to/socket.
+#[tokio::test]
+async fn test_unix_socket_upstream() {
+    init_logging();
+    let socket_path = start_unix_server();
+    let upstream = format!("unix:{}", socket_path.to_str().unwrap());
+    let balancebeam = BalanceBeam::new(&[&upstream], None, None).await;
+
+    for i in 0..3 {
+        let path = format!("/unix-{}", i);
+        let response_text = balancebeam
+            .get(&path)
+            .await
+            .expect("Error sending request to balancebeam");
+        assert_eq!(response_text, format!("GET {} HTTP/1.1", path));
+    }
+
+    let _ = std::fs::remove_file(&socket_path);
+    log::info!("All done :)");
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{init_logging, BalanceBeam, EchoServer, Server};
+use rand::Rng;
+use std::time::Duration;
+use tokio::time::delay_for;
+
+/// Starts balancebeam with the admin API on a random port, returning it along with the admin API
+/// address.
+async fn setup_with_admin(upstreams: &[&str]) -> (BalanceBeam, String) {
+    init_logging();
+    let mut rng = rand::thread_rng();
+    let admin_address = format!("127.0.0.1:{}", rng.gen_range(1024, 65535));
+    let balancebeam = BalanceBeam::new_with_args(
+        upstreams,
+        Some(1),
+        None,
+        &["--admin-bind", &admin_address],
+    )
+    .await;
+    (balancebeam, admin_address)
+}
+
+async fn admin_get(admin_address: &str, path: &str) -> reqwest::Response {
+    reqwest::get(&format!("http://{}{}", admin_address, path))
+        .await
+        .expect("Error sending request to the admin API")
+}
+
+/// Take an upstream down and bring it back while health checks run, and make sure its history
+/// shows it healthy, then unhealthy, then healthy again.
+#[tokio::test]
+async fn test_health_history_follows_upstream_state() {
+    let upstream = EchoServer::new().await;
+    let upstream_address = upstream.address.clone();
+    let (_balancebeam, admin_address) = setup_with_admin(&[&upstream_address]).await;
+
+    log::info!("Letting a few health checks pass...");
+    delay_for(Duration::from_millis(2500)).await;
+    log::info!("Taking the upstream down...");
+    Box::new(upstream).stop().await;
+    delay_for(Duration::from_millis(2500)).await;
+    log::info!("Bringing the upstream back...");
+    let upstream = EchoServer::new_at_address(upstream_address.clone()).await;
+    delay_for(Duration::from_millis(2500)).await;
+
+    let response = admin_get(
+        &admin_address,
+        &format!("/upstreams/{}/history", upstream_address),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.expect("Admin API sent a malformed response");
+    log::info!("Health history: {}", body);
+    let history: serde_json::Value =
+        serde_json::from_str(&body).expect("Health history is not valid JSON");
+    let mut results: Vec<bool> = history
+        .as_array()
+        .expect("Health history is not a JSON array")
+        .iter()
+        .map(|entry| entry["healthy"].as_bool().expect("Entry is missing \"healthy\""))
+        .collect();
+    assert!(results.len() >= 6, "Expected at least 6 health checks, got {:?}", results);
+    results.dedup();
+    assert_eq!(results, vec![true, false, true]);
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// Asking for the history of an upstream balancebeam doesn't know about should give a 404.
+#[tokio::test]
+async fn test_health_history_unknown_upstream() {
+    let upstream = EchoServer::new().await;
+    let (_balancebeam, admin_address) = setup_with_admin(&[&upstream.address]).await;
+
+    let response = admin_get(&admin_address, "/upstreams/127.0.0.1:1/history").await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
+
+/// After a known number of requests, the upstream's counters should add up.
+#[tokio::test]
+async fn test_upstream_stats() {
+    let upstream = EchoServer::new().await;
+    let (balancebeam, admin_address) = setup_with_admin(&[&upstream.address]).await;
+
+    let mut bytes_sent = 0;
+    let mut bytes_received = 0;
+    for i in 0..5 {
+        let response_text = balancebeam
+            .get(&format!("/stats-{}", i))
+            .await
+            .expect("Error sending request to balancebeam");
+        bytes_received += response_text.len();
+    }
+    for i in 0..3 {
+        let body = "b".repeat(100 * (i + 1));
+        let response_text = balancebeam
+            .post(&format!("/stats-post-{}", i), &body)
+            .await
+            .expect("Error sending request to balancebeam");
+        bytes_sent += body.len();
+        bytes_received += response_text.len();
+    }
+
+    let response = admin_get(&admin_address, "/upstreams").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.expect("Admin API sent a malformed response");
+    log::info!("Upstreams: {}", body);
+    let upstreams: serde_json::Value =
+        serde_json::from_str(&body).expect("Upstream summary is not valid JSON");
+    let summary = &upstreams[upstream.address.as_str()];
+    assert_eq!(summary["in_pool"], true);
+    let stats = &summary["stats"];
+    assert_eq!(stats["requests_total"], 8);
+    assert_eq!(stats["errors_total"], 0);
+    assert_eq!(stats["bytes_sent"], bytes_sent);
+    assert_eq!(stats["bytes_received"], bytes_received);
+    assert!(stats["avg_latency_ms"].is_u64());
+
+    Box::new(upstream).stop().await;
+    log::info!("All done :)");
+}
@@ -19,10 +19,27 @@ impl BalanceBeam {
         path
     }
 
+    #[allow(dead_code)]
     pub async fn new(
         upstreams: &[&str],
         active_health_check_interval: Option<usize>,
         max_requests_per_minute: Option<usize>,
+    ) -> BalanceBeam {
+        BalanceBeam::new_with_args(
+            upstreams,
+            active_health_check_interval,
+            max_requests_per_minute,
+            &[],
+        )
+        .await
+    }
+
+    /// Like `new`, but passes `extra_args` through to the balancebeam command line as-is.
+    pub async fn new_with_args(
+        upstreams: &[&str],
+        active_health_check_interval: Option<usize>,
+        max_requests_per_minute: Option<usize>,
+        extra_args: &[&str],
     ) -> BalanceBeam {
         let mut rng = rand::thread_rng();
         let address = format!("127.0.0.1:{}", rng.gen_range(1024, 65535) as i64);
@@ -39,6 +56,7 @@ impl BalanceBeam {
             cmd.arg("--max-requests-per-minute")
                 .arg(max_requests_per_minute.to_string());
         }
+        cmd.args(extra_args);
         cmd.kill_on_drop(true);
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::piped());
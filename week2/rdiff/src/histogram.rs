@@ -0,0 +1,180 @@
+// Histogram diff, as git diff --histogram does it
+use std::collections::HashMap;
+use std::hash::Hash;
+use {edit_script, lcs, DiffOp};
+
+/// Lines that appear more often than this in the first file aren't used to split the files, since
+/// matching them up is as likely to be wrong as right.
+const MAX_CHAIN: usize = 64;
+
+/// Diffs two sequences by repeatedly splitting them at the run of common lines that's least
+/// common in the first sequence. Matching up rare lines (like a function's signature) rather than
+/// common ones (like a closing brace or a blank line) keeps changes to code in one piece.
+pub fn histogram_diff<T: Eq + Hash>(seq1: &[T], seq2: &[T]) -> Vec<DiffOp> {
+    let mut ops = Vec::new();
+    diff_range(seq1, seq2, 0, 0, &mut ops);
+    ops
+}
+
+/// Diffs part of each sequence, which start at offset1 and offset2 in the whole sequences,
+/// pushing the edits onto ops.
+fn diff_range<T: Eq + Hash>(seq1: &[T], seq2: &[T], offset1: usize, offset2: usize, ops: &mut Vec<DiffOp>) {
+    let prefix = seq1.iter().zip(seq2).take_while(|(a, b)| a == b).count();
+    ops.extend((0..prefix).map(|k| DiffOp::Equal(offset1 + k, offset2 + k)));
+    let (seq1, seq2) = (&seq1[prefix..], &seq2[prefix..]);
+    let (offset1, offset2) = (offset1 + prefix, offset2 + prefix);
+    let suffix = seq1.iter().rev().zip(seq2.iter().rev()).take_while(|(a, b)| a == b).count();
+    let (seq1, seq2) = (&seq1[..seq1.len() - suffix], &seq2[..seq2.len() - suffix]);
+
+    if seq1.is_empty() || seq2.is_empty() {
+        ops.extend((0..seq1.len()).map(|i| DiffOp::Delete(offset1 + i)));
+        ops.extend((0..seq2.len()).map(|j| DiffOp::Insert(offset2 + j)));
+    } else if let Some((i, j, len)) = find_split(seq1, seq2) {
+        diff_range(&seq1[..i], &seq2[..j], offset1, offset2, ops);
+        ops.extend((0..len).map(|k| DiffOp::Equal(offset1 + i + k, offset2 + j + k)));
+        diff_range(&seq1[i + len..], &seq2[j + len..], offset1 + i + len, offset2 + j + len, ops);
+    } else {
+        // Nothing rare enough in common, so fall back on the LCS
+        ops.extend(edit_script(&lcs(seq1, seq2), seq1, seq2).into_iter().map(|op| match op {
+            DiffOp::Equal(i, j) => DiffOp::Equal(offset1 + i, offset2 + j),
+            DiffOp::Delete(i) => DiffOp::Delete(offset1 + i),
+            DiffOp::Insert(j) => DiffOp::Insert(offset2 + j),
+        }));
+    }
+
+    ops.extend((0..suffix).map(|k| DiffOp::Equal(offset1 + seq1.len() + k, offset2 + seq2.len() + k)));
+}
+
+/// Finds the run of lines common to both sequences whose rarest line occurs least often in seq1,
+/// preferring longer runs between equally rare ones. Returns where the run starts in each
+/// sequence and how long it is.
+fn find_split<T: Eq + Hash>(seq1: &[T], seq2: &[T]) -> Option<(usize, usize, usize)> {
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, line) in seq1.iter().enumerate() {
+        occurrences.entry(line).or_default().push(i);
+    }
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut best_count = MAX_CHAIN + 1;
+    for j in 0..seq2.len() {
+        let positions = match occurrences.get(&seq2[j]) {
+            Some(positions) if positions.len() <= best_count => positions,
+            _ => continue,
+        };
+        for &i in positions {
+            let (mut start1, mut start2) = (i, j);
+            while start1 > 0 && start2 > 0 && seq1[start1 - 1] == seq2[start2 - 1] {
+                start1 -= 1;
+                start2 -= 1;
+            }
+            let (mut end1, mut end2) = (i + 1, j + 1);
+            while end1 < seq1.len() && end2 < seq2.len() && seq1[end1] == seq2[end2] {
+                end1 += 1;
+                end2 += 1;
+            }
+            let count = (start1..end1).map(|k| occurrences[&seq1[k]].len()).min().unwrap();
+            let len = end1 - start1;
+            if count < best_count || (count == best_count && best.is_some_and(|(_, _, best_len)| len > best_len)) {
+                best = Some((start1, start2, len));
+                best_count = count;
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    /// Counts the separate runs of changes in a diff.
+    fn change_runs(ops: &[DiffOp]) -> usize {
+        let mut runs = 0;
+        for (k, op) in ops.iter().enumerate() {
+            let changed = |op: &DiffOp| !matches!(op, DiffOp::Equal(..));
+            if changed(op) && (k == 0 || !changed(&ops[k - 1])) {
+                runs += 1;
+            }
+        }
+        runs
+    }
+
+    /// Checks that ops turns seq1 into seq2.
+    fn check(ops: &[DiffOp], seq1: &[String], seq2: &[String]) {
+        let (mut i, mut j) = (0, 0);
+        for op in ops {
+            match *op {
+                DiffOp::Equal(a, b) => {
+                    assert_eq!((a, b), (i, j));
+                    assert_eq!(seq1[a], seq2[b]);
+                    i += 1;
+                    j += 1;
+                }
+                DiffOp::Delete(a) => {
+                    assert_eq!(a, i);
+                    i += 1;
+                }
+                DiffOp::Insert(b) => {
+                    assert_eq!(b, j);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (seq1.len(), seq2.len()));
+    }
+
+    #[test]
+    fn test_histogram_diff() {
+        let seq1 = lines("a\nb\nc\nd");
+        let seq2 = lines("a\nx\nc\nd\ne");
+        let ops = histogram_diff(&seq1, &seq2);
+        check(&ops, &seq1, &seq2);
+        let expected = [
+            DiffOp::Equal(0, 0),
+            DiffOp::Delete(1),
+            DiffOp::Insert(1),
+            DiffOp::Equal(2, 2),
+            DiffOp::Equal(3, 3),
+            DiffOp::Insert(4),
+        ];
+        assert_eq!(ops, expected);
+        check(&histogram_diff(&seq1, &[]), &seq1, &[]);
+        check(&histogram_diff(&[], &seq2), &[], &seq2);
+    }
+
+    /// Returns the lines that ops inserts.
+    fn inserted<'a>(ops: &[DiffOp], seq2: &'a [String]) -> Vec<&'a str> {
+        ops.iter()
+            .filter_map(|op| match *op {
+                DiffOp::Insert(j) => Some(seq2[j].as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_code_changes() {
+        // A new function between two others: LCS starts the insertion at the first function's
+        // closing brace, but histogram inserts the new function as it was written
+        let seq1 = lines("void func1() {\n    x += 1\n}\n\nvoid func2() {\n    x += 2\n}\n");
+        let seq2 = lines(
+            "void func1() {\n    x += 1\n}\n\nvoid functhreehalves() {\n    x += 1.5\n}\n\nvoid func2() {\n    x += 2\n}\n",
+        );
+        let histogram = histogram_diff(&seq1, &seq2);
+        let lcs_ops = edit_script(&lcs(&seq1, &seq2), &seq1, &seq2);
+        check(&histogram, &seq1, &seq2);
+        assert_eq!(change_runs(&histogram), 1);
+        assert_eq!(inserted(&histogram, &seq2), ["void functhreehalves() {", "    x += 1.5", "}", ""]);
+        assert_eq!(inserted(&lcs_ops, &seq2), ["}", "", "void functhreehalves() {", "    x += 1.5"]);
+
+        // A function moved to the end of the file
+        let seq1 = lines("fn first() {\n    one();\n}\n\nfn second() {\n    two();\n}\n\nfn third() {\n    three();\n}\n");
+        let seq2 = lines("fn second() {\n    two();\n}\n\nfn third() {\n    three();\n}\n\nfn first() {\n    one();\n}\n");
+        let histogram = histogram_diff(&seq1, &seq2);
+        check(&histogram, &seq1, &seq2);
+        assert_eq!(change_runs(&histogram), 2);
+    }
+}
@@ -1,23 +1,60 @@
+extern crate atty;
+extern crate regex;
+extern crate serde;
+extern crate serde_json;
+
 use grid::Grid; // For lcs()
-use std::{env, fs};
-use std::fs::File; // For read_file_lines()
+use regex::Regex;
+use serde::Serialize; // For DiffLine
+use std::env;
+use std::fmt::Display;
+use std::fmt::Write; // For unified_diff() and context_diff()
+use std::fs;
+use std::hash::{Hash, Hasher}; // For Line
 use std::io::{self, BufRead}; // For read_file_lines()
+use std::io::Write as IoWrite; // For ColorWriter
+use std::path::Path;
 use std::process;
 
 pub mod grid;
+pub mod histogram;
+pub mod merge;
+pub mod patch;
 
 /// Reads the file at the supplied path, and returns a vector of strings.
 fn read_file_lines(filename: &String) -> Result<Vec<String>, io::Error> {
-    let file = File::open(filename)?;
+    split_lines(&fs::read(filename)?)
+}
+
+/// Splits a file's contents into lines. Fails if the contents aren't valid UTF-8.
+fn split_lines(content: &[u8]) -> Result<Vec<String>, io::Error> {
     let mut v = Vec::<String>::new();
-    for line in io::BufReader::new(file).lines() {
+    for line in content.lines() {
         let line_str = line?;
         v.push(line_str);
     };
     Ok(v)
 }
 
-fn lcs(seq1: &Vec<String>, seq2: &Vec<String>) -> Grid {
+/// Guesses whether a file is binary rather than text, the way GNU diff does: by looking for a null
+/// byte near the start.
+fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8 * 1024).any(|&byte| byte == 0)
+}
+
+/// If either file is binary, returns what to print instead of a diff: whether they differ, or
+/// nothing if they're identical.
+fn compare_binary(filename1: &str, content1: &[u8], filename2: &str, content2: &[u8]) -> Option<String> {
+    if !is_binary(content1) && !is_binary(content2) {
+        return None;
+    }
+    if content1 == content2 {
+        return Some(String::new());
+    }
+    Some(format!("Binary files {} and {} differ\n", filename1, filename2))
+}
+
+fn lcs<T: PartialEq>(seq1: &[T], seq2: &[T]) -> Grid {
     // Note: Feel free to use unwrap() in this code, as long as you're basically certain it'll
     // never happen. Conceptually, unwrap() is justified here, because there's not really any error
     // condition you're watching out for (i.e. as long as your code is written correctly, nothing
@@ -36,10 +73,10 @@ fn lcs(seq1: &Vec<String>, seq2: &Vec<String>) -> Grid {
     for (i, s1) in seq1.iter().enumerate() {
         for (j, s2) in seq2.iter().enumerate() {
             if s1 == s2 {
-                grid.set(i+1, j+1, grid.get(i, j).unwrap()+1);
+                grid.set(i+1, j+1, grid.get(i, j).unwrap()+1).unwrap();
             } else {
                 let m = std::cmp::max(grid.get(i+1, j).unwrap(), grid.get(i, j+1).unwrap());
-                grid.set(i+1, j+1, m);
+                grid.set(i+1, j+1, m).unwrap();
             }
         }
     }
@@ -47,51 +84,1177 @@ fn lcs(seq1: &Vec<String>, seq2: &Vec<String>) -> Grid {
     // Be sure to delete the #[allow(unused)] line above
 }
 
-// if i > 0 and j > 0 and X[i-1] = Y[j-1]
-// print_diff(C, X, Y, i-1, j-1)
-// print "  " + X[i-1]
-// else if j > 0 and (i = 0 or C[i,j-1] ≥ C[i-1,j])
-// print_diff(C, X, Y, i, j-1)
-// print "> " + Y[j-1]
-// else if i > 0 and (j = 0 or C[i,j-1] < C[i-1,j])
-// print_diff(C, X, Y, i-1, j)
-// print "< " + X[i-1]
-// else
-// print ""
-fn print_diff(lcs_table: &Grid, lines1: &Vec<String>, lines2: &Vec<String>, i: usize, j: usize) {
-    if i > 0 && j > 0 && lines1[i-1] == lines2[j-1] {
-        print_diff(lcs_table, lines1, lines2, i-1, j-1);
-        println!(" {}", lines1[i-1]);
-    } else if j > 0 && (i==0 || lcs_table.get(i, j-1).unwrap() >= lcs_table.get(i-1, j).unwrap()) {
-        print_diff(lcs_table, lines1, lines2, i, j-1);
-        println!("> {}", lines2[j-1]);
-    } else if i > 0 && (j==0 || lcs_table.get(i, j-1).unwrap() < lcs_table.get(i-1, j).unwrap()) {
-        print_diff(lcs_table, lines1, lines2, i-1, j);
-        println!("< {}", lines1[i-1]);
-    }
-}
-
-#[allow(unused)] // TODO: delete this line when you implement this function
+/// Writes every line of both files, each marked as kept, removed or added.
+fn print_diff<T: Display, W: IoWrite>(
+    out: &mut ColorWriter<W>,
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+) -> io::Result<()> {
+    for (k, edit) in edits.iter().enumerate() {
+        match *edit {
+            DiffOp::Equal(i, _) => out.unchanged(&lines1[i])?,
+            DiffOp::Delete(i) if is_ignored(ignored, k) => out.unchanged(&lines1[i])?,
+            DiffOp::Insert(j) if is_ignored(ignored, k) => out.unchanged(&lines2[j])?,
+            DiffOp::Delete(i) => out.removed(&lines1[i])?,
+            DiffOp::Insert(j) => out.added(&lines2[j])?,
+        }
+    }
+    Ok(())
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Writes the lines of a diff, with removed lines in red and added lines in green if color is on.
+struct ColorWriter<W: IoWrite> {
+    out: W,
+    color: bool,
+}
+
+impl<W: IoWrite> ColorWriter<W> {
+    fn new(out: W, color: bool) -> ColorWriter<W> {
+        ColorWriter { out, color }
+    }
+
+    fn unchanged(&mut self, line: &dyn Display) -> io::Result<()> {
+        writeln!(self.out, " {}", line)
+    }
+
+    fn removed(&mut self, line: &dyn Display) -> io::Result<()> {
+        self.colored(RED, '<', line)
+    }
+
+    fn added(&mut self, line: &dyn Display) -> io::Result<()> {
+        self.colored(GREEN, '>', line)
+    }
+
+    fn colored(&mut self, color: &str, marker: char, line: &dyn Display) -> io::Result<()> {
+        if self.color {
+            writeln!(self.out, "{}{} {}{}", color, marker, line, RESET)
+        } else {
+            writeln!(self.out, "{} {}", marker, line)
+        }
+    }
+}
+
+/// One step in turning the first file into the second, with the indices of the lines involved.
+#[derive(Debug, PartialEq)]
+pub enum DiffOp {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// How to match up the lines of two files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    /// Keep as many lines as possible
+    Lcs,
+    /// Split the files at lines that are rare in them, like git diff --histogram
+    Histogram,
+}
+
+/// Works out how to turn lines1 into lines2.
+fn diff_ops<T: Eq + Hash>(lines1: &[T], lines2: &[T], algorithm: Algorithm) -> Vec<DiffOp> {
+    match algorithm {
+        Algorithm::Lcs => edit_script(&lcs(lines1, lines2), lines1, lines2),
+        Algorithm::Histogram => histogram::histogram_diff(lines1, lines2),
+    }
+}
+
+/// Walks back through the LCS table and returns the edits in order.
+fn edit_script<T: PartialEq>(lcs_table: &Grid, lines1: &[T], lines2: &[T]) -> Vec<DiffOp> {
+    let (mut i, mut j) = (lines1.len(), lines2.len());
+    let mut edits = Vec::new();
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && lines1[i-1] == lines2[j-1] {
+            edits.push(DiffOp::Equal(i-1, j-1));
+            i -= 1;
+            j -= 1;
+        } else if j > 0 && (i == 0 || lcs_table.get(i, j-1).unwrap() >= lcs_table.get(i-1, j).unwrap()) {
+            edits.push(DiffOp::Insert(j-1));
+            j -= 1;
+        } else {
+            edits.push(DiffOp::Delete(i-1));
+            i -= 1;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// A run of edits containing changes, with up to `context` unchanged lines around them.
+struct Hunk {
+    /// Range of the hunk's edits in the edit script
+    start: usize,
+    end: usize,
+    /// Index of the first line of each file in the hunk, and how many lines of it the hunk covers
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+}
+
+/// Finds the changes that -I says to overlook: runs of changes (between unchanged lines) in which
+/// every removed and added line matches the pattern. Like diff -I, a hunk isn't made for these,
+/// though they're still shown as changes when a hunk of other changes takes them in.
+fn ignored_changes(edits: &[DiffOp], lines1: &[Line], lines2: &[Line]) -> Vec<bool> {
+    let mut ignored = vec![false; edits.len()];
+    let mut run_start = 0;
+    for i in 0..=edits.len() {
+        if i < edits.len() && !matches!(edits[i], DiffOp::Equal(..)) {
+            continue;
+        }
+        let all_ignored = edits[run_start..i].iter().all(|edit| match *edit {
+            DiffOp::Equal(..) => true,
+            DiffOp::Delete(line) => lines1[line].key.is_none(),
+            DiffOp::Insert(line) => lines2[line].key.is_none(),
+        });
+        if all_ignored {
+            ignored[run_start..i].iter_mut().for_each(|c| *c = true);
+        }
+        run_start = i + 1;
+    }
+    ignored
+}
+
+/// Whether the change at index k of an edit script is one that ignored_changes found. An empty
+/// mask overlooks nothing.
+fn is_ignored(ignored: &[bool], k: usize) -> bool {
+    ignored.get(k).copied().unwrap_or(false)
+}
+
+/// Groups the changes in an edit script into hunks, leaving out the ignored ones. Changes
+/// separated by no more than 2 * context unchanged lines share a hunk, since their context would
+/// overlap.
+fn collect_hunks(edits: &[DiffOp], ignored: &[bool], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| !matches!(edits[i], DiffOp::Equal(..)) && !is_ignored(ignored, i))
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        match ranges.last_mut() {
+            Some(range) if change - range.1 <= 2 * context => range.1 = change + 1,
+            _ => ranges.push((change, change + 1)),
+        }
+    }
+    let old_lines = |range: &[DiffOp]| range.iter().filter(|e| !matches!(e, DiffOp::Insert(_))).count();
+    let new_lines = |range: &[DiffOp]| range.iter().filter(|e| !matches!(e, DiffOp::Delete(_))).count();
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            let start = first.saturating_sub(context);
+            let end = std::cmp::min(last + context, edits.len());
+            Hunk {
+                start,
+                end,
+                old_start: old_lines(&edits[..start]),
+                old_len: old_lines(&edits[start..end]),
+                new_start: new_lines(&edits[..start]),
+                new_len: new_lines(&edits[start..end]),
+            }
+        })
+        .collect()
+}
+
+/// Formats a hunk's line range the way diff -u does: the count is left out when it's 1, and an
+/// empty range is given by the line before it.
+fn unified_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// What -p looks for to label hunks: the start of a Rust (or similar) item.
+const DEFAULT_FUNCTION_LINE: &str = "^(pub )?(fn |impl |struct |class )";
+
+/// Finds the last line before from that matches pattern, which is usually the start of the
+/// function a hunk is in.
+fn find_context_label<'a>(lines: &'a [String], from: usize, pattern: &Regex) -> Option<&'a str> {
+    lines[..from].iter().rev().find(|line| pattern.is_match(line)).map(|line| line.trim())
+}
+
+/// Returns the hunks of a unified diff (everything after the ---/+++ header lines). Given a
+/// function_line pattern, each hunk header ends with the last line before the hunk that matches
+/// it, like git diff does.
+fn unified_diff<T: Display>(
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+    context: usize,
+    function_line: Option<&Regex>,
+) -> String {
+    let text1: Vec<String> = match function_line {
+        Some(_) => lines1.iter().map(|line| line.to_string()).collect(),
+        None => Vec::new(),
+    };
+    let mut diff = String::new();
+    for hunk in collect_hunks(edits, ignored, context) {
+        write!(
+            diff,
+            "@@ -{} +{} @@",
+            unified_range(hunk.old_start, hunk.old_len),
+            unified_range(hunk.new_start, hunk.new_len)
+        ).unwrap();
+        match function_line.and_then(|pattern| find_context_label(&text1, hunk.old_start, pattern)) {
+            Some(label) => writeln!(diff, " {}", label).unwrap(),
+            None => diff.push('\n'),
+        }
+        for edit in &edits[hunk.start..hunk.end] {
+            match *edit {
+                DiffOp::Equal(i, _) => writeln!(diff, " {}", lines1[i]),
+                DiffOp::Delete(i) => writeln!(diff, "-{}", lines1[i]),
+                DiffOp::Insert(j) => writeln!(diff, "+{}", lines2[j]),
+            }.unwrap();
+        }
+    }
+    diff
+}
+
+fn print_unified_diff<T: Display>(
+    out: &mut dyn IoWrite,
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+    context: usize,
+    function_line: Option<&Regex>,
+) -> io::Result<()> {
+    write!(out, "{}", unified_diff(edits, ignored, lines1, lines2, context, function_line))
+}
+
+/// Formats a hunk's line range the way diff -c does: first and last line numbers, or just one
+/// number when the range has one line, or is empty (and then it's the line before).
+fn context_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{}", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, start + len),
+    }
+}
+
+/// Returns the hunks of a context diff (everything after the ***/--- header lines).
+fn context_diff<T: Display>(
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+    context: usize,
+) -> String {
+    // A run of changes with both deletions and insertions is a change: its lines are marked with
+    // ! on both sides, rather than - and +
+    let mut changed = vec![false; edits.len()];
+    let mut run_start = 0;
+    for i in 0..=edits.len() {
+        if i < edits.len() && !matches!(edits[i], DiffOp::Equal(..)) {
+            continue;
+        }
+        let run = &edits[run_start..i];
+        if run.iter().any(|e| matches!(e, DiffOp::Delete(_))) && run.iter().any(|e| matches!(e, DiffOp::Insert(_))) {
+            changed[run_start..i].iter_mut().for_each(|c| *c = true);
+        }
+        run_start = i + 1;
+    }
+    let marker = |i: usize, plain: char| if changed[i] { '!' } else { plain };
+
+    let mut diff = String::new();
+    for hunk in collect_hunks(edits, ignored, context) {
+        let hunk_edits = hunk.start..hunk.end;
+        writeln!(diff, "***************").unwrap();
+        writeln!(diff, "*** {} ****", context_range(hunk.old_start, hunk.old_len)).unwrap();
+        // Each side's lines are only shown if it has changes of its own
+        if edits[hunk_edits.clone()].iter().any(|e| matches!(e, DiffOp::Delete(_))) {
+            for i in hunk_edits.clone() {
+                match edits[i] {
+                    DiffOp::Equal(i, _) => writeln!(diff, "  {}", lines1[i]).unwrap(),
+                    DiffOp::Delete(line) => writeln!(diff, "{} {}", marker(i, '-'), lines1[line]).unwrap(),
+                    DiffOp::Insert(_) => {}
+                }
+            }
+        }
+        writeln!(diff, "--- {} ----", context_range(hunk.new_start, hunk.new_len)).unwrap();
+        if edits[hunk_edits.clone()].iter().any(|e| matches!(e, DiffOp::Insert(_))) {
+            for i in hunk_edits {
+                match edits[i] {
+                    DiffOp::Equal(_, j) => writeln!(diff, "  {}", lines2[j]).unwrap(),
+                    DiffOp::Insert(line) => writeln!(diff, "{} {}", marker(i, '+'), lines2[line]).unwrap(),
+                    DiffOp::Delete(_) => {}
+                }
+            }
+        }
+    }
+    diff
+}
+
+fn print_context_diff<T: Display>(
+    out: &mut dyn IoWrite,
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+    context: usize,
+) -> io::Result<()> {
+    write!(out, "{}", context_diff(edits, ignored, lines1, lines2, context))
+}
+
+/// Shortens a line to fit in a column of the given width, marking where it was cut off.
+fn fit_column(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_string();
+    }
+    let mut fitted: String = line.chars().take(width.saturating_sub(1)).collect();
+    fitted.push('\u{2026}');
+    fitted
+}
+
+/// Returns the two files side by side, in columns that fit within the given width. Between the
+/// columns is | for a changed line, < for a line only on the left, > for a line only on the right,
+/// and nothing for an unchanged line (as diff -y does).
+fn side_by_side<T: Display>(edits: &[DiffOp], ignored: &[bool], lines1: &[T], lines2: &[T], width: usize) -> String {
+    let column = width.saturating_sub(3) / 2;
+    let mut diff = String::new();
+    let mut row = |left: Option<&T>, separator: char, right: Option<&T>| {
+        let left = left.map_or(String::new(), |line| fit_column(&line.to_string(), column));
+        let right = right.map_or(String::new(), |line| fit_column(&line.to_string(), column));
+        let line = format!("{:<width$} {} {}", left, separator, right, width = column);
+        writeln!(diff, "{}", line.trim_end()).unwrap();
+    };
+    // Removed and added lines in the same run of changes are paired up as changed lines
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    for k in 0..=edits.len() {
+        match edits.get(k) {
+            Some(&DiffOp::Delete(i)) if !is_ignored(ignored, k) => removed.push(&lines1[i]),
+            Some(&DiffOp::Insert(j)) if !is_ignored(ignored, k) => added.push(&lines2[j]),
+            edit => {
+                for n in 0..std::cmp::max(removed.len(), added.len()) {
+                    match (removed.get(n), added.get(n)) {
+                        (Some(&left), Some(&right)) => row(Some(left), '|', Some(right)),
+                        (Some(&left), None) => row(Some(left), '<', None),
+                        (None, right) => row(None, '>', right.cloned()),
+                    }
+                }
+                removed.clear();
+                added.clear();
+                // Ignored changes are shown unmarked, like unchanged lines
+                match edit {
+                    Some(&DiffOp::Equal(i, j)) => row(Some(&lines1[i]), ' ', Some(&lines2[j])),
+                    Some(&DiffOp::Delete(i)) => row(Some(&lines1[i]), ' ', None),
+                    Some(&DiffOp::Insert(j)) => row(None, ' ', Some(&lines2[j])),
+                    None => {}
+                }
+            }
+        }
+    }
+    diff
+}
+
+fn print_side_by_side<T: Display>(
+    out: &mut dyn IoWrite,
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+    width: usize,
+) -> io::Result<()> {
+    write!(out, "{}", side_by_side(edits, ignored, lines1, lines2, width))
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A line of a diff, for tools that want it as JSON. Lines are numbered from 1, and a line that
+/// is only in one file has no number in the other.
+#[derive(Debug, Serialize)]
+struct DiffLine {
+    #[serde(rename = "type")]
+    kind: LineKind,
+    line_num_a: Option<usize>,
+    line_num_b: Option<usize>,
+    content: String,
+}
+
+fn json_diff<T: Display>(edits: &[DiffOp], ignored: &[bool], lines1: &[T], lines2: &[T]) -> Vec<DiffLine> {
+    edits
+        .iter()
+        .enumerate()
+        .map(|(k, edit)| match *edit {
+            DiffOp::Equal(i, j) => DiffLine {
+                kind: LineKind::Context,
+                line_num_a: Some(i + 1),
+                line_num_b: Some(j + 1),
+                content: lines1[i].to_string(),
+            },
+            // Ignored changes are context that's only in one file
+            DiffOp::Delete(i) => DiffLine {
+                kind: if is_ignored(ignored, k) { LineKind::Context } else { LineKind::Removed },
+                line_num_a: Some(i + 1),
+                line_num_b: None,
+                content: lines1[i].to_string(),
+            },
+            DiffOp::Insert(j) => DiffLine {
+                kind: if is_ignored(ignored, k) { LineKind::Context } else { LineKind::Added },
+                line_num_a: None,
+                line_num_b: Some(j + 1),
+                content: lines2[j].to_string(),
+            },
+        })
+        .collect()
+}
+
+fn print_json_diff<T: Display>(
+    out: &mut dyn IoWrite,
+    edits: &[DiffOp],
+    ignored: &[bool],
+    lines1: &[T],
+    lines2: &[T],
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &json_diff(edits, ignored, lines1, lines2))?;
+    writeln!(out)
+}
+
+/// How many lines a diff adds and removes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct DiffStats {
+    insertions: usize,
+    deletions: usize,
+    /// Whether the files were binary, so their lines weren't counted
+    binary: bool,
+}
+
+/// Counts the changes in an edit script, other than the ignored ones.
+fn collect_stats(edits: &[DiffOp], ignored: &[bool]) -> DiffStats {
+    let mut stats = DiffStats::default();
+    for (k, edit) in edits.iter().enumerate() {
+        match *edit {
+            _ if is_ignored(ignored, k) => {}
+            DiffOp::Equal(..) => {}
+            DiffOp::Delete(_) => stats.deletions += 1,
+            DiffOp::Insert(_) => stats.insertions += 1,
+        }
+    }
+    stats
+}
+
+/// Draws a bar of +s and -s for a file's insertions and deletions, like git diff --stat does.
+/// Bars longer than width are scaled down, keeping at least one of each kind of change.
+fn format_bar(insertions: usize, deletions: usize, width: usize) -> String {
+    let total = insertions + deletions;
+    let (mut plus, mut minus) = (insertions, deletions);
+    if total > width {
+        plus = (insertions * width + total / 2) / total;
+        if insertions > 0 && plus == 0 {
+            plus = 1;
+        }
+        if deletions > 0 && plus == width {
+            plus = width - 1;
+        }
+        minus = width - plus;
+    }
+    format!("{}{}", "+".repeat(plus), "-".repeat(minus))
+}
+
+/// Adds an s to a word unless there's one of the thing it names.
+fn plural(count: usize, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+/// Formats a summary of the changes to some files: a line with a bar for each file, and then
+/// the totals.
+fn format_stats(files: &[(String, DiffStats)]) -> String {
+    let name_width = files.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let count_width = files
+        .iter()
+        .map(|(_, stats)| (stats.insertions + stats.deletions).to_string().len())
+        .max()
+        .unwrap_or(0);
+    let mut summary = String::new();
+    let mut total = DiffStats::default();
+    for (name, stats) in files {
+        if stats.binary {
+            writeln!(summary, " {:<nw$} | Bin", name, nw = name_width).unwrap();
+            continue;
+        }
+        writeln!(
+            summary,
+            " {:<nw$} | {:>cw$} {}",
+            name,
+            stats.insertions + stats.deletions,
+            format_bar(stats.insertions, stats.deletions, 50),
+            nw = name_width,
+            cw = count_width
+        ).unwrap();
+        total.insertions += stats.insertions;
+        total.deletions += stats.deletions;
+    }
+    write!(summary, " {} changed", plural(files.len(), "file")).unwrap();
+    if total.insertions > 0 {
+        write!(summary, ", {}(+)", plural(total.insertions, "insertion")).unwrap();
+    }
+    if total.deletions > 0 {
+        write!(summary, ", {}(-)", plural(total.deletions, "deletion")).unwrap();
+    }
+    summary.push('\n');
+    summary
+}
+
+/// Splits text into words and the runs of whitespace between them, so that joining the tokens
+/// gives back the text.
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices().skip(1) {
+        let prev = text[..i].chars().next_back().unwrap();
+        if c.is_whitespace() != prev.is_whitespace() {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// Returns the second file's text with the words that changed marked inline: [-removed-] and
+/// {+added+}.
+fn word_diff(lines1: &[String], lines2: &[String]) -> String {
+    let (text1, text2) = (lines1.join("\n"), lines2.join("\n"));
+    let (words1, words2) = (tokenize(&text1), tokenize(&text2));
+    let lcs_table = lcs(&words1, &words2);
+    let mut diff = String::new();
+    let (mut removed, mut added) = (String::new(), String::new());
+    for edit in edit_script(&lcs_table, &words1, &words2) {
+        match edit {
+            DiffOp::Delete(i) => removed.push_str(words1[i]),
+            DiffOp::Insert(j) => added.push_str(words2[j]),
+            DiffOp::Equal(i, _) => {
+                flush_word_changes(&mut diff, &mut removed, &mut added);
+                diff.push_str(words1[i]);
+            }
+        }
+    }
+    flush_word_changes(&mut diff, &mut removed, &mut added);
+    diff
+}
+
+/// Adds a run of changed words to a word diff.
+fn flush_word_changes(diff: &mut String, removed: &mut String, added: &mut String) {
+    if !removed.is_empty() {
+        write!(diff, "[-{}-]", removed).unwrap();
+        removed.clear();
+    }
+    if !added.is_empty() {
+        write!(diff, "{{+{}+}}", added).unwrap();
+        added.clear();
+    }
+}
+
+/// Which differences in whitespace to overlook when comparing lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WhitespaceMode {
+    None,
+    /// Treat any run of whitespace as a single space
+    IgnoreChange,
+    /// Leave out whitespace altogether
+    IgnoreAll,
+}
+
+/// Returns the text that stands for a line when comparing it with others.
+fn normalise_line(s: &str, mode: WhitespaceMode) -> String {
+    match mode {
+        WhitespaceMode::None => s.to_string(),
+        WhitespaceMode::IgnoreChange => {
+            let mut line = String::new();
+            for token in tokenize(s) {
+                if token.starts_with(char::is_whitespace) {
+                    line.push(' ');
+                } else {
+                    line.push_str(token);
+                }
+            }
+            line
+        }
+        WhitespaceMode::IgnoreAll => s.chars().filter(|c| !c.is_whitespace()).collect(),
+    }
+}
+
+fn case_fold(s: &str) -> String {
+    s.to_lowercase()
+}
+
+/// A line of a file, which is shown as it is but compared in its normalised form.
+struct Line {
+    text: String,
+    /// None for a line that matches the -I pattern: those all compare equal to each other, so
+    /// they line up as common context rather than showing up as changes
+    key: Option<String>,
+}
+
+impl Line {
+    fn new(text: &str, whitespace: WhitespaceMode, ignore_case: bool) -> Line {
+        let mut key = normalise_line(text, whitespace);
+        if ignore_case {
+            key = case_fold(&key);
+        }
+        Line { text: text.to_string(), key: Some(key) }
+    }
+
+    /// Makes a line that compares the way opts says to.
+    fn with_opts(text: &str, opts: &DiffOptions) -> Line {
+        let mut line = Line::new(text, opts.whitespace, opts.ignore_case);
+        if opts.ignore.as_ref().is_some_and(|pattern| pattern.is_match(text)) {
+            line.key = None;
+        }
+        line
+    }
+}
+
+impl Hash for Line {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl Eq for Line {}
+
+impl PartialEq for Line {
+    fn eq(&self, other: &Line) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// How to show the differences between the files.
+#[derive(Clone, Copy)]
+enum Format {
+    /// Every line, each marked as kept, removed (<) or added (>)
+    Plain,
+    Unified(usize),
+    Context(usize),
+    Words,
+    SideBySide,
+    /// A summary of how many lines changed in each file
+    Stat,
+    Json,
+    /// Only say whether the files differ
+    Brief,
+}
+
+/// Everything the command line says about how to compare files.
+struct DiffOptions {
+    format: Format,
+    whitespace: WhitespaceMode,
+    ignore_case: bool,
+    color: bool,
+    /// Total width of side by side output
+    width: usize,
+    /// Whether to compare directories file by file
+    recursive: bool,
+    /// Lines matching this all compare equal, so changes to them don't count
+    ignore: Option<Regex>,
+    /// Unified diff hunks are labelled with the last line before them that matches this
+    function_line: Option<Regex>,
+    algorithm: Algorithm,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            format: Format::Plain,
+            whitespace: WhitespaceMode::None,
+            ignore_case: false,
+            color: false,
+            width: 80,
+            recursive: false,
+            ignore: None,
+            function_line: None,
+            algorithm: Algorithm::Lcs,
+        }
+    }
+}
+
+/// Reads a file, saying which file it was if that fails.
+fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+/// Returns the next line, ready to compare.
+fn next_line<B: BufRead>(lines: &mut io::Lines<B>, opts: &DiffOptions) -> io::Result<Option<Line>> {
+    match lines.next() {
+        Some(line) => Ok(Some(Line::with_opts(&line?, opts))),
+        None => Ok(None),
+    }
+}
+
+/// Checks whether two files have the same lines, as opts compares them. Unlike a diff, this
+/// goes through the files in step, stopping at the first line that differs, unless lines are
+/// ignored with -I: then the files have to be lined up to find the changes that only add or
+/// remove ignored lines.
+fn same_lines(content1: &[u8], content2: &[u8], opts: &DiffOptions) -> io::Result<bool> {
+    if is_binary(content1) || is_binary(content2) {
+        return Ok(content1 == content2);
+    }
+    if opts.ignore.is_some() {
+        let to_lines = |content: &[u8]| -> io::Result<Vec<Line>> {
+            let lines = content.lines().collect::<io::Result<Vec<String>>>()?;
+            Ok(lines.iter().map(|line| Line::with_opts(line, opts)).collect())
+        };
+        let (lines1, lines2) = (to_lines(content1)?, to_lines(content2)?);
+        let edits = diff_ops(&lines1, &lines2, opts.algorithm);
+        let ignored = ignored_changes(&edits, &lines1, &lines2);
+        return Ok(collect_stats(&edits, &ignored) == DiffStats::default());
+    }
+    let (mut lines1, mut lines2) = (content1.lines(), content2.lines());
+    loop {
+        match (next_line(&mut lines1, opts)?, next_line(&mut lines2, opts)?) {
+            (None, None) => return Ok(true),
+            (Some(line1), Some(line2)) if line1 == line2 => {}
+            _ => return Ok(false),
+        }
+    }
+}
+
+/// The exit status for brief mode, given the files that differ: 0 if there are none, otherwise
+/// 1, like diff -q.
+fn brief_status(changed: &[(String, DiffStats)]) -> i32 {
+    if changed.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Writes the differences between two files to out, in the format opts asks for, and returns
+/// how many lines changed, or None if the files are the same. Stat format writes nothing, leaving
+/// the caller to summarise the changes.
+fn diff_files(
+    out: &mut dyn IoWrite,
+    path1: &Path,
+    path2: &Path,
+    opts: &DiffOptions,
+) -> io::Result<Option<DiffStats>> {
+    let (filename1, filename2) = (path1.display().to_string(), path2.display().to_string());
+    let bytes1 = read_bytes(path1)?;
+    let bytes2 = read_bytes(path2)?;
+    if let Format::Brief = opts.format {
+        if same_lines(&bytes1, &bytes2, opts)? {
+            writeln!(out, "Files {} and {} are identical", filename1, filename2)?;
+            return Ok(None);
+        }
+        writeln!(out, "Files {} and {} differ", filename1, filename2)?;
+        return Ok(Some(DiffStats::default()));
+    }
+    if let Some(message) = compare_binary(&filename1, &bytes1, &filename2, &bytes2) {
+        if message.is_empty() {
+            return Ok(None);
+        }
+        if !matches!(opts.format, Format::Stat) {
+            write!(out, "{}", message)?;
+        }
+        return Ok(Some(DiffStats { binary: true, ..DiffStats::default() }));
+    }
+    let invalid = |filename: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: not valid UTF-8", filename));
+    let contents1 = split_lines(&bytes1).map_err(|_| invalid(&filename1))?;
+    let contents2 = split_lines(&bytes2).map_err(|_| invalid(&filename2))?;
+    let lines1: Vec<Line> = contents1.iter().map(|line| Line::with_opts(line, opts)).collect();
+    let lines2: Vec<Line> = contents2.iter().map(|line| Line::with_opts(line, opts)).collect();
+    let edits = diff_ops(&lines1, &lines2, opts.algorithm);
+    let ignored = ignored_changes(&edits, &lines1, &lines2);
+    if let Format::Words = opts.format {
+        // Ignored lines that line up are shown as they are in the first file, like the other
+        // formats show context, and ignored changes are given to both files so they don't show
+        let (mut words1, mut words2) = (Vec::new(), Vec::new());
+        for (k, edit) in edits.iter().enumerate() {
+            match *edit {
+                DiffOp::Equal(i, j) => {
+                    words1.push(contents1[i].clone());
+                    words2.push(if lines1[i].key.is_none() { &contents1[i] } else { &contents2[j] }.clone());
+                }
+                DiffOp::Delete(_) if ignored[k] => {}
+                DiffOp::Insert(j) if ignored[k] => {
+                    words1.push(contents2[j].clone());
+                    words2.push(contents2[j].clone());
+                }
+                DiffOp::Delete(i) => words1.push(contents1[i].clone()),
+                DiffOp::Insert(j) => words2.push(contents2[j].clone()),
+            }
+        }
+        writeln!(out, "{}", word_diff(&words1, &words2))?;
+        let stats = collect_stats(&diff_ops(&words1, &words2, opts.algorithm), &[]);
+        return Ok(Some(stats).filter(|stats| *stats != DiffStats::default()));
+    }
+    let stats = collect_stats(&edits, &ignored);
+    let changed = stats != DiffStats::default();
+    match opts.format {
+        Format::Plain => {
+            let mut out = ColorWriter::new(out, opts.color);
+            print_diff(&mut out, &edits, &ignored, &lines1, &lines2)?;
+        }
+        // Like diff, print nothing at all, not even the headers, when there are no hunks
+        Format::Unified(_) | Format::Context(_) if !changed => {}
+        Format::Unified(context) => {
+            writeln!(out, "--- a/{}", filename1)?;
+            writeln!(out, "+++ b/{}", filename2)?;
+            print_unified_diff(out, &edits, &ignored, &lines1, &lines2, context, opts.function_line.as_ref())?;
+        }
+        Format::Context(context) => {
+            writeln!(out, "*** {}", filename1)?;
+            writeln!(out, "--- {}", filename2)?;
+            print_context_diff(out, &edits, &ignored, &lines1, &lines2, context)?;
+        }
+        Format::SideBySide => print_side_by_side(out, &edits, &ignored, &lines1, &lines2, opts.width)?,
+        Format::Stat => {}
+        Format::Json => print_json_diff(out, &edits, &ignored, &lines1, &lines2)?,
+        Format::Brief => unreachable!(),
+        Format::Words => unreachable!(),
+    }
+    Ok(Some(stats).filter(|_| changed))
+}
+
+/// Returns the names of the entries in a directory, sorted.
+fn dir_entries(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Compares two directory trees, pairing up files by their paths within the trees. Each pair of
+/// files that differ gets a header line and then its diff; files (or directories) that are only
+/// in one tree are just listed. Returns how many lines changed in each file that differs.
+fn diff_dirs(
+    out: &mut dyn IoWrite,
+    dir1: &Path,
+    dir2: &Path,
+    opts: &DiffOptions,
+) -> io::Result<Vec<(String, DiffStats)>> {
+    let mut changed = Vec::new();
+    let names1 = dir_entries(dir1)?;
+    let names2 = dir_entries(dir2)?;
+    let mut names: Vec<&String> = names1.iter().chain(names2.iter()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let (path1, path2) = (dir1.join(name), dir2.join(name));
+        if !names2.contains(name) {
+            writeln!(out, "Only in {}: {}", dir1.display(), name)?;
+        } else if !names1.contains(name) {
+            writeln!(out, "Only in {}: {}", dir2.display(), name)?;
+        } else if path1.is_dir() && path2.is_dir() {
+            changed.extend(diff_dirs(out, &path1, &path2, opts)?);
+        } else if path1.is_dir() || path2.is_dir() {
+            let kind = |path: &Path| if path.is_dir() { "directory" } else { "regular file" };
+            writeln!(
+                out,
+                "File {} is a {} while file {} is a {}",
+                path1.display(),
+                kind(&path1),
+                path2.display(),
+                kind(&path2)
+            )?;
+        } else {
+            // Only show the header for files that differ, so diff into a buffer first
+            let mut diff = Vec::new();
+            let stats = diff_files(&mut diff, &path1, &path2, opts)?;
+            if stats.is_some() && !diff.is_empty() && !matches!(opts.format, Format::Brief) {
+                writeln!(out, "diff rdiff {} {}", path1.display(), path2.display())?;
+            }
+            if stats.is_some() || matches!(opts.format, Format::Brief) {
+                out.write_all(&diff)?;
+            }
+            changed.extend(stats.map(|stats| (path1.display().to_string(), stats)));
+        }
+    }
+    Ok(changed)
+}
+
+/// Compiles a pattern given on the command line, exiting if it's not a valid regex.
+fn parse_regex(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap_or_else(|err| {
+        eprintln!("rdiff: {}", err);
+        process::exit(2);
+    })
+}
+
+fn usage(program: &str) -> ! {
+    println!("Usage: {} [options] <file1> <file2>", program);
+    println!("       {} diff [-u | --unified <context>] <file1> <file2> [-o <output>]", program);
+    println!("       {} apply <patch> [target]", program);
+    println!("       {} merge <base> <ours> <theirs> [-o <output>]", program);
+    println!("  -u, --unified <context>     unified diff, with 3 (or <context>) lines of context");
+    println!("  -p, --show-function         label unified diff hunks with the function they're in");
+    println!("  -F, --function-line <regex> label hunks with the last line before them matching regex");
+    println!("  -c, --context <context>     context diff, with 3 (or <context>) lines of context");
+    println!("  --word-diff                 mark changed words within the text");
+    println!("  -y, --side-by-side          show the files in two columns");
+    println!("  --width <width>             total width of side by side output (default 80)");
+    println!("  --algorithm=lcs|histogram   how to match up lines (default lcs)");
+    println!("  -r, --recursive             compare the files in two directories");
+    println!("  --stat                      summarise how many lines changed in each file");
+    println!("  -q, --brief                 only say whether the files differ (exit status 1 if so)");
+    println!("  --format=json               write the diff as a JSON array of lines");
+    println!("  -o <output>                 write the diff to output rather than printing it");
+    println!("  -b, --ignore-space-change   treat runs of whitespace as a single space");
+    println!("  -w, --ignore-all-space      ignore whitespace when comparing lines");
+    println!("  -i, --ignore-case           ignore differences in case when comparing lines");
+    println!("  -I, --ignore-matching-lines <regex>");
+    println!("                              treat lines matching regex as all the same");
+    println!("  --color, --no-color         whether to color removed and added lines (by default,");
+    println!("                              only when writing to a terminal)");
+    process::exit(1);
+}
+
+/// Runs `rdiff merge <base> <ours> <theirs> [-o <output>]`, which merges the changes ours and
+/// theirs made to base, printing the result (or writing it to output). Exits with status 1 if
+/// there were conflicts.
+fn merge_main(args: &[String]) {
+    let mut output = None;
+    let mut filenames = Vec::new();
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "-o" {
+            i += 1;
+            output = args.get(i);
+        } else {
+            filenames.push(&args[i]);
+        }
+        i += 1;
+    }
+    if filenames.len() != 3 || (output.is_none() && args.iter().any(|arg| arg == "-o")) {
+        println!("Usage: {} merge <base> <ours> <theirs> [-o <output>]", args[0]);
+        process::exit(1);
+    }
+    let files: Vec<Vec<String>> = filenames
+        .iter()
+        .map(|filename| read_file_lines(filename).unwrap_or_else(|_| panic!("read file {} fail", filename)))
+        .collect();
+    let result = merge::three_way_merge(&files[0], &files[1], &files[2]);
+    let merged: String = result.lines().iter().map(|line| format!("{}\n", line)).collect();
+    match output {
+        Some(output) => fs::write(output, merged).unwrap_or_else(|_| panic!("write file {} fail", output)),
+        None => print!("{}", merged),
+    }
+    if let merge::MergeResult::Conflicts(_) = result {
+        process::exit(1);
+    }
+}
+
+/// Runs `rdiff diff [-u | --unified <context>] <file1> <file2> [-o <output>]`, which writes a
+/// unified diff that apply (or patch) can use to turn file1 into file2.
+fn diff_main(args: &[String]) {
+    let usage = || -> ! {
+        println!("Usage: {} diff [-u | --unified <context>] <file1> <file2> [-o <output>]", args[0]);
+        process::exit(1);
+    };
+    let mut context = 3;
+    let mut output = None;
+    let mut filenames = Vec::new();
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-u" => context = 3,
+            "--unified" => {
+                i += 1;
+                context = args.get(i).and_then(|context| context.parse().ok()).unwrap_or_else(|| usage());
+            }
+            "-o" => {
+                i += 1;
+                output = Some(args.get(i).unwrap_or_else(|| usage()));
+            }
+            _ => filenames.push(&args[i]),
+        }
+        i += 1;
+    }
+    if filenames.len() != 2 {
+        usage();
+    }
+    let (filename1, filename2) = (filenames[0], filenames[1]);
+    let lines1 = read_file_lines(filename1).unwrap_or_else(|_| panic!("read file {} fail", filename1));
+    let lines2 = read_file_lines(filename2).unwrap_or_else(|_| panic!("read file {} fail", filename2));
+    let diff = format!(
+        "--- a/{}\n+++ b/{}\n{}",
+        filename1,
+        filename2,
+        unified_diff(&diff_ops(&lines1, &lines2, Algorithm::Lcs), &[], &lines1, &lines2, context, None)
+    );
+    match output {
+        Some(output) => fs::write(output, diff).unwrap_or_else(|_| panic!("write file {} fail", output)),
+        None => print!("{}", diff),
+    }
+}
+
+/// Runs `rdiff apply <patch> [target]`, which applies a unified diff to target, by default the
+/// file named on the patch's --- line.
+fn apply_main(args: &[String]) {
+    if args.len() < 3 || args.len() > 4 {
+        println!("Usage: {} apply <patch> [target]", args[0]);
+        process::exit(1);
+    }
+    let patch_text = fs::read_to_string(&args[2]).unwrap_or_else(|_| panic!("read file {} fail", args[2]));
+    let target = match args.get(3) {
+        Some(target) => target.clone(),
+        None => {
+            let header = patch_text.lines().find(|line| line.starts_with("--- "));
+            match header {
+                Some(header) => {
+                    let path = header["--- ".len()..].split('\t').next().unwrap();
+                    path.strip_prefix("a/").unwrap_or(path).to_string()
+                }
+                None => {
+                    println!("{} does not say which file it patches; give the target", args[2]);
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    let original = read_file_lines(&target).unwrap_or_else(|_| panic!("read file {} fail", target));
+    match patch::apply_patch(&original, &patch_text) {
+        Ok(patched) => {
+            let patched: String = patched.iter().map(|line| format!("{}\n", line)).collect();
+            fs::write(&target, patched).unwrap_or_else(|_| panic!("write file {} fail", target));
+        }
+        Err(err) => {
+            println!("Could not patch {}: {}", target, err);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
+    match args.get(1).map(String::as_str) {
+        Some("merge") => return merge_main(&args),
+        Some("diff") => return diff_main(&args),
+        Some("apply") => return apply_main(&args),
+        _ => {}
+    }
+    let mut opts = DiffOptions::default();
+    let mut color = None;
+    let mut output = None;
+    let mut filenames = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-u" => opts.format = Format::Unified(3),
+            "-c" => opts.format = Format::Context(3),
+            "--word-diff" => opts.format = Format::Words,
+            "-y" | "--side-by-side" => opts.format = Format::SideBySide,
+            "--width" => {
+                i += 1;
+                opts.width = match args.get(i).and_then(|width| width.parse().ok()) {
+                    Some(width) => width,
+                    None => usage(&args[0]),
+                };
+            }
+            "-b" | "--ignore-space-change" => opts.whitespace = WhitespaceMode::IgnoreChange,
+            "-w" | "--ignore-all-space" => opts.whitespace = WhitespaceMode::IgnoreAll,
+            "-i" | "--ignore-case" => opts.ignore_case = true,
+            "-I" | "--ignore-matching-lines" => {
+                i += 1;
+                opts.ignore = Some(parse_regex(args.get(i).unwrap_or_else(|| usage(&args[0]))));
+            }
+            "-p" | "--show-function" => opts.function_line = Some(parse_regex(DEFAULT_FUNCTION_LINE)),
+            "-F" | "--function-line" => {
+                i += 1;
+                opts.function_line = Some(parse_regex(args.get(i).unwrap_or_else(|| usage(&args[0]))));
+            }
+            "-r" | "--recursive" => opts.recursive = true,
+            "--stat" => opts.format = Format::Stat,
+            "--format=json" => opts.format = Format::Json,
+            "-q" | "--brief" => opts.format = Format::Brief,
+            "--algorithm=lcs" => opts.algorithm = Algorithm::Lcs,
+            "--algorithm=histogram" => opts.algorithm = Algorithm::Histogram,
+            "-o" => {
+                i += 1;
+                output = Some(args.get(i).unwrap_or_else(|| usage(&args[0])));
+            }
+            "--color" => color = Some(true),
+            "--no-color" => color = Some(false),
+            flag @ "--unified" | flag @ "--context" => {
+                i += 1;
+                let context = match args.get(i).and_then(|context| context.parse().ok()) {
+                    Some(context) => context,
+                    None => usage(&args[0]),
+                };
+                opts.format = if flag == "--unified" {
+                    Format::Unified(context)
+                } else {
+                    Format::Context(context)
+                };
+            }
+            _ => filenames.push(&args[i]),
+        }
+        i += 1;
+    }
+    if filenames.len() < 2 {
         println!("Too few arguments.");
-        process::exit(1);
+        usage(&args[0]);
+    }
+    let (path1, path2) = (Path::new(filenames[0]), Path::new(filenames[1]));
+    opts.color = color.unwrap_or_else(|| output.is_none() && atty::is(atty::Stream::Stdout));
+    let stdout = io::stdout();
+    let mut out: Box<dyn IoWrite> = match output {
+        Some(output) => match fs::File::create(output) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("rdiff: {}: {}", output, err);
+                process::exit(2);
+            }
+        },
+        None => Box::new(stdout.lock()),
+    };
+    let result = if opts.recursive && path1.is_dir() && path2.is_dir() {
+        diff_dirs(&mut *out, path1, path2, &opts)
+    } else {
+        diff_files(&mut *out, path1, path2, &opts)
+            .map(|stats| stats.into_iter().map(|stats| (path1.display().to_string(), stats)).collect())
+    };
+    let changed = match result {
+        Ok(changed) => changed,
+        Err(err) => {
+            eprintln!("rdiff: {}", err);
+            process::exit(2);
+        }
+    };
+    let result = match opts.format {
+        Format::Stat if !changed.is_empty() => write!(out, "{}", format_stats(&changed)),
+        _ => Ok(()),
+    };
+    if let Err(err) = result.and_then(|_| out.flush()) {
+        eprintln!("rdiff: {}", err);
+        process::exit(2);
+    }
+    if let Format::Brief = opts.format {
+        process::exit(brief_status(&changed));
     }
-    let filename1 = &args[1];
-    let filename2 = &args[2];
-
-    let contents1 = read_file_lines(filename1).expect(&*format!("read file {} fail", filename1));
-    let contents2 = read_file_lines(filename2).expect(&*format!("read file {} fail", filename2));
-    let grid = lcs(&contents1, &contents2);
-    print_diff(&grid, &contents1, &contents2, contents1.len(), contents2.len());
-    // Be sure to delete the #[allow(unused)] line above
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn ops<T: PartialEq>(lines1: &[T], lines2: &[T]) -> Vec<DiffOp> {
+        edit_script(&lcs(lines1, lines2), lines1, lines2)
+    }
+
     #[test]
     fn test_read_file_lines() {
         let lines_result = read_file_lines(&String::from("handout-a.txt"));
@@ -123,8 +1286,8 @@ mod test {
         println!("Expected:");
         expected.display();
         let result = lcs(
-            &"abcd".chars().map(|c| c.to_string()).collect(),
-            &"adb".chars().map(|c| c.to_string()).collect(),
+            &"abcd".chars().map(|c| c.to_string()).collect::<Vec<String>>(),
+            &"adb".chars().map(|c| c.to_string()).collect::<Vec<String>>(),
         );
         println!("Got:");
         result.display();
@@ -135,4 +1298,468 @@ mod test {
             }
         }
     }
+
+    fn unified_diff_of(lines1: &[String], lines2: &[String], context: usize) -> String {
+        unified_diff(&ops(lines1, lines2), &[], lines1, lines2, context, None)
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let lines1 = read_file_lines(&String::from("handout-a.txt")).unwrap();
+        let lines2 = read_file_lines(&String::from("handout-b.txt")).unwrap();
+        // Output of diff -u handout-a.txt handout-b.txt, without the header
+        let expected = "\
+@@ -1,8 +1,6 @@
+-This week's exercises will continue easing you into Rust and will feature some
+-components of object-oriented Rust that we're covering this week. You'll be
++You'll be learning and practicing a lot of new Rust concepts this week by
+ writing some programs that have more sophisticated logic that what you saw last
+ with last week's exercises. The first exercise is a warm-up: to implement the wc
+ command line utility in Rust. The second exercise is more challenging: to
+ implement the diff utility. In order to do so, you'll first find the longest
+-common subsequence (LCS) of lines between the two files and use this to inform
+-how you display your diff.
++subsequence that is common.
+";
+        assert_eq!(unified_diff_of(&lines1, &lines2, 3), expected);
+        assert_eq!(unified_diff_of(&lines1, &lines1, 3), "");
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let lines1: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut lines2 = lines1.clone();
+        lines2[1] = String::from("two");
+        lines2.remove(14);
+        lines2.push(String::from("21"));
+        // Output of diff -u and diff -U1, without the header
+        let expected = "\
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -12,9 +12,9 @@
+ 12
+ 13
+ 14
+-15
+ 16
+ 17
+ 18
+ 19
+ 20
++21
+";
+        assert_eq!(unified_diff_of(&lines1, &lines2, 3), expected);
+        let expected = "\
+@@ -1,3 +1,3 @@
+ 1
+-2
++two
+ 3
+@@ -14,3 +14,2 @@
+ 14
+-15
+ 16
+@@ -20 +19,2 @@
+ 20
++21
+";
+        assert_eq!(unified_diff_of(&lines1, &lines2, 1), expected);
+        assert_eq!(unified_diff_of(&[], &lines1[..1], 3), "@@ -0,0 +1 @@\n+1\n");
+    }
+
+    #[test]
+    fn test_context_diff() {
+        let lines1 = read_file_lines(&String::from("handout-a.txt")).unwrap();
+        let lines2 = read_file_lines(&String::from("handout-b.txt")).unwrap();
+        // Output of diff -c handout-a.txt handout-b.txt, without the header
+        let expected = "\
+***************
+*** 1,8 ****
+! This week's exercises will continue easing you into Rust and will feature some
+! components of object-oriented Rust that we're covering this week. You'll be
+  writing some programs that have more sophisticated logic that what you saw last
+  with last week's exercises. The first exercise is a warm-up: to implement the wc
+  command line utility in Rust. The second exercise is more challenging: to
+  implement the diff utility. In order to do so, you'll first find the longest
+! common subsequence (LCS) of lines between the two files and use this to inform
+! how you display your diff.
+--- 1,6 ----
+! You'll be learning and practicing a lot of new Rust concepts this week by
+  writing some programs that have more sophisticated logic that what you saw last
+  with last week's exercises. The first exercise is a warm-up: to implement the wc
+  command line utility in Rust. The second exercise is more challenging: to
+  implement the diff utility. In order to do so, you'll first find the longest
+! subsequence that is common.
+";
+        assert_eq!(context_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 3), expected);
+    }
+
+    #[test]
+    fn test_context_diff_hunks() {
+        let lines1: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut lines2 = lines1.clone();
+        lines2[1] = String::from("two");
+        lines2.remove(14);
+        lines2.push(String::from("21"));
+        // Output of diff -C1, without the header
+        let expected = "\
+***************
+*** 1,3 ****
+  1
+! 2
+  3
+--- 1,3 ----
+  1
+! two
+  3
+***************
+*** 14,16 ****
+  14
+- 15
+  16
+--- 14,15 ----
+***************
+*** 20 ****
+--- 19,20 ----
+  20
++ 21
+";
+        assert_eq!(context_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 1), expected);
+        let empty = ops(&[], &lines1[..1]);
+        assert_eq!(context_diff(&empty, &[], &[], &lines1[..1], 3), "***************\n*** 0 ****\n--- 1 ----\n+ 1\n");
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("a  bc\td "), vec!["a", "  ", "bc", "\t", "d", " "]);
+        assert_eq!(tokenize(" x"), vec![" ", "x"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn test_word_diff() {
+        let lines1 = vec![String::from("the quick brown fox"), String::from("jumps  over")];
+        let lines2 = vec![String::from("the quick red fox"), String::from("jumps  over")];
+        assert_eq!(word_diff(&lines1, &lines2), "the quick [-brown-]{+red+} fox\njumps  over");
+        assert_eq!(word_diff(&lines1, &lines1), "the quick brown fox\njumps  over");
+    }
+
+    #[test]
+    fn test_normalise_line() {
+        assert_eq!(normalise_line("  a \t b ", WhitespaceMode::None), "  a \t b ");
+        assert_eq!(normalise_line("  a \t b ", WhitespaceMode::IgnoreChange), " a b ");
+        assert_eq!(normalise_line("  a \t b ", WhitespaceMode::IgnoreAll), "ab");
+    }
+
+    #[test]
+    fn test_ignore_whitespace() {
+        let lines = |text: &[&str], mode| text.iter().map(|line| Line::new(line, mode, false)).collect::<Vec<Line>>();
+        let text1 = ["fn main() {", "    let x = 1;", "}"];
+        let text2 = ["fn main() {", "\tlet x  =  1;", "}"];
+        let diff = |mode| {
+            let (lines1, lines2) = (lines(&text1, mode), lines(&text2, mode));
+            unified_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 3, None)
+        };
+        assert_eq!(diff(WhitespaceMode::IgnoreAll), "");
+        // The indentation changed from spaces to a tab, which is still a change of whitespace
+        assert_eq!(diff(WhitespaceMode::IgnoreChange), "");
+        assert_eq!(
+            diff(WhitespaceMode::None),
+            "@@ -1,3 +1,3 @@\n fn main() {\n-    let x = 1;\n+\tlet x  =  1;\n }\n"
+        );
+        let (lines1, lines2) = (lines(&["ab"], WhitespaceMode::IgnoreChange), lines(&["a b"], WhitespaceMode::IgnoreChange));
+        assert!(lines1 != lines2);
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let diff = |ignore_case| {
+            let lines1 = vec![Line::new("Hello World", WhitespaceMode::None, ignore_case)];
+            let lines2 = vec![Line::new("hello world", WhitespaceMode::None, ignore_case)];
+            unified_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 3, None)
+        };
+        assert_eq!(diff(true), "");
+        assert_eq!(diff(false), "@@ -1 +1 @@\n-Hello World\n+hello world\n");
+    }
+
+    fn plain_diff(lines1: &[&str], lines2: &[&str], color: bool) -> String {
+        let mut out = ColorWriter::new(Vec::new(), color);
+        print_diff(&mut out, &ops(lines1, lines2), &[], lines1, lines2).unwrap();
+        String::from_utf8(out.out).unwrap()
+    }
+
+    #[test]
+    fn test_color() {
+        let (lines1, lines2) = (["a", "b", "c"], ["a", "d", "c"]);
+        assert_eq!(
+            plain_diff(&lines1, &lines2, true),
+            " a\n\x1b[31m< b\x1b[0m\n\x1b[32m> d\x1b[0m\n c\n"
+        );
+        assert_eq!(plain_diff(&lines1, &lines2, false), " a\n< b\n> d\n c\n");
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let lines1: Vec<String> = ["same", "old", "gone", "same again", "a rather long line"]
+            .iter().map(|s| s.to_string()).collect();
+        let lines2: Vec<String> = ["same", "new", "same again", "a rather long line", "added"]
+            .iter().map(|s| s.to_string()).collect();
+        // Columns of (21 - 3) / 2 = 9 characters
+        let expected = "\
+same        same
+old       | new
+gone      <
+same aga\u{2026}   same aga\u{2026}
+a rather\u{2026}   a rather\u{2026}
+          > added
+";
+        assert_eq!(side_by_side(&ops(&lines1, &lines2), &[], &lines1, &lines2, 21), expected);
+    }
+
+    #[test]
+    fn test_binary_files() {
+        let path = env::temp_dir().join(format!("rdiff-test-binary-{}", process::id()));
+        fs::write(&path, b"text\0more text\n").unwrap();
+        let binary = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(is_binary(&binary));
+        assert!(!is_binary(b"just text\n"));
+
+        assert_eq!(
+            compare_binary("a.bin", &binary, "b.txt", b"just text\n"),
+            Some(String::from("Binary files a.bin and b.txt differ\n"))
+        );
+        assert_eq!(compare_binary("a.bin", &binary, "b.bin", &binary), Some(String::new()));
+        assert_eq!(compare_binary("a.txt", b"one\n", "b.txt", b"two\n"), None);
+    }
+
+    #[test]
+    fn test_diff_dirs() {
+        let root = env::temp_dir().join(format!("rdiff-test-dirs-{}", process::id()));
+        let (dir1, dir2) = (root.join("a"), root.join("b"));
+        for dir in [&dir1, &dir2] {
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("same.txt"), "one\ntwo\n").unwrap();
+        }
+        fs::write(dir1.join("sub/changed.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir2.join("sub/changed.txt"), "one\n2\n").unwrap();
+        fs::write(dir1.join("old.txt"), "old\n").unwrap();
+        fs::write(dir2.join("new.txt"), "new\n").unwrap();
+        fs::create_dir(dir2.join("extra")).unwrap();
+
+        let opts = DiffOptions { format: Format::Unified(3), recursive: true, ..DiffOptions::default() };
+        let mut out = Vec::new();
+        let result = diff_dirs(&mut out, &dir1, &dir2, &opts);
+        fs::remove_dir_all(&root).unwrap();
+        result.unwrap();
+
+        let (dir1, dir2) = (dir1.display(), dir2.display());
+        let expected = format!(
+            "\
+Only in {dir2}: extra
+Only in {dir2}: new.txt
+Only in {dir1}: old.txt
+diff rdiff {dir1}/sub/changed.txt {dir2}/sub/changed.txt
+--- a/{dir1}/sub/changed.txt
++++ b/{dir2}/sub/changed.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++2
+",
+            dir1 = dir1,
+            dir2 = dir2
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stats() {
+        let lines1: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        let lines2: Vec<String> = ["a", "B", "c", "e", "f"].iter().map(|s| s.to_string()).collect();
+        let stats = collect_stats(&ops(&lines1, &lines2), &[]);
+        assert_eq!(stats, DiffStats { insertions: 3, deletions: 2, binary: false });
+
+        assert_eq!(format_bar(3, 2, 50), "+++--");
+        assert_eq!(format_bar(75, 25, 50), format!("{}{}", "+".repeat(38), "-".repeat(12)));
+        assert_eq!(format_bar(1000, 1, 50), format!("{}-", "+".repeat(49)));
+        assert_eq!(format_bar(0, 200, 50), "-".repeat(50));
+
+        let files = vec![
+            (String::from("one.txt"), stats),
+            (String::from("two"), DiffStats { insertions: 2, deletions: 10, binary: false }),
+        ];
+        let expected = concat!(
+            " one.txt |  5 +++--\n",
+            " two     | 12 ++----------\n",
+            " 2 files changed, 5 insertions(+), 12 deletions(-)\n",
+        );
+        assert_eq!(format_stats(&files), expected);
+        let files = vec![(String::from("one.txt"), DiffStats { insertions: 1, deletions: 0, binary: false })];
+        assert_eq!(format_stats(&files), " one.txt | 1 +\n 1 file changed, 1 insertion(+)\n");
+    }
+
+    #[test]
+    fn test_json_diff() {
+        let lines1: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let lines2: Vec<String> = ["a", "d", "c", "e"].iter().map(|s| s.to_string()).collect();
+        let mut out = Vec::new();
+        print_json_diff(&mut out, &ops(&lines1, &lines2), &[], &lines1, &lines2).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&String::from_utf8(out).unwrap()).unwrap();
+        let entries = json.as_array().unwrap();
+        let kinds: Vec<&str> = entries.iter().map(|entry| entry["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["context", "removed", "added", "context", "added"]);
+        assert_eq!(entries[1]["line_num_a"], 2);
+        assert!(entries[1]["line_num_b"].is_null());
+        assert!(entries[4]["line_num_a"].is_null());
+        assert_eq!(entries[4]["line_num_b"], 4);
+        assert_eq!(entries[4]["content"], "e");
+    }
+
+    #[test]
+    fn test_ignore_matching_lines() {
+        let root = env::temp_dir().join(format!("rdiff-test-ignore-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let (path1, path2) = (root.join("one.py"), root.join("two.py"));
+        fs::write(&path1, "# generated at 10:00\nx = 1\ny = 2\n").unwrap();
+        fs::write(&path2, "  # generated at 10:05\nx = 1\ny = 3\n").unwrap();
+
+        let diff = |opts: &DiffOptions| {
+            let mut out = Vec::new();
+            let stats = diff_files(&mut out, &path1, &path2, opts).unwrap();
+            (String::from_utf8(out).unwrap(), stats)
+        };
+        let mut opts = DiffOptions { format: Format::Unified(3), ..DiffOptions::default() };
+        let (out, stats) = diff(&opts);
+        assert!(out.contains("generated"));
+        assert_eq!(stats, Some(DiffStats { insertions: 2, deletions: 2, binary: false }));
+
+        opts.ignore = Some(Regex::new(r"^\s*#").unwrap());
+        let (out, stats) = diff(&opts);
+        // The ignored line is kept as context, as it is in the first file
+        assert!(out.ends_with("@@ -1,3 +1,3 @@\n # generated at 10:00\n x = 1\n-y = 2\n+y = 3\n"));
+        assert_eq!(stats, Some(DiffStats { insertions: 1, deletions: 1, binary: false }));
+        opts.format = Format::Plain;
+        let (out, _) = diff(&opts);
+        assert!(out.contains("# generated at 10:00"));
+        opts.format = Format::Brief;
+        assert_eq!(diff(&opts).1, Some(DiffStats::default()));
+
+        // Ignoring lines doesn't throw off the line numbers of the changes after them
+        let (old, new) = ("# ts 1\na\nb\nc\nd\ne\nf\ng\n", "# ts 2\na\nb\nc\nd\ne\nF\ng\n");
+        fs::write(&path1, old).unwrap();
+        fs::write(&path2, new).unwrap();
+        opts.format = Format::Unified(3);
+        opts.ignore = Some(Regex::new("^#").unwrap());
+        let (out, _) = diff(&opts);
+        assert!(out.contains("@@ -4,5 +4,5 @@\n"), "{}", out);
+        let patch = out.lines().skip(2).collect::<Vec<&str>>().join("\n");
+        // The patch applies, leaving the ignored line as it was
+        let old: Vec<String> = old.lines().map(String::from).collect();
+        let new: Vec<String> = new.replace("# ts 2", "# ts 1").lines().map(String::from).collect();
+        assert_eq!(patch::apply_patch(&old, &patch), Ok(new));
+
+        // An ignored line that's only in one file isn't a change either
+        fs::write(&path1, "x = 1\ny = 2\n").unwrap();
+        fs::write(&path2, "# stamp\nx = 1\ny = 2\n").unwrap();
+        assert_eq!(diff(&opts), (String::new(), None));
+        opts.format = Format::Stat;
+        assert_eq!(diff(&opts), (String::new(), None));
+        opts.format = Format::Plain;
+        assert_eq!(diff(&opts), (String::from(" # stamp\n x = 1\n y = 2\n"), None));
+        opts.format = Format::Brief;
+        assert_eq!(diff(&opts).1, None);
+        // But it's still shown when it's next to a change that isn't ignored
+        fs::write(&path2, "# stamp\nx = 0\ny = 2\n").unwrap();
+        opts.format = Format::Unified(3);
+        let (out, stats) = diff(&opts);
+        fs::remove_dir_all(&root).unwrap();
+        assert!(out.ends_with("@@ -1,2 +1,3 @@\n-x = 1\n+# stamp\n+x = 0\n y = 2\n"), "{}", out);
+        assert_eq!(stats, Some(DiffStats { insertions: 2, deletions: 1, binary: false }));
+    }
+
+    #[test]
+    fn test_identical_files() {
+        let root = env::temp_dir().join(format!("rdiff-test-identical-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let (path1, path2) = (root.join("one.txt"), root.join("two.txt"));
+        fs::write(&path1, "a\nb\n").unwrap();
+        fs::write(&path2, "a\nb\n").unwrap();
+        for format in [Format::Unified(3), Format::Context(3)] {
+            let mut out = Vec::new();
+            let opts = DiffOptions { format, ..DiffOptions::default() };
+            assert_eq!(diff_files(&mut out, &path1, &path2, &opts).unwrap(), None);
+            assert!(out.is_empty());
+        }
+        let mut out = Vec::new();
+        let opts = DiffOptions { format: Format::Unified(3), ..DiffOptions::default() };
+        fs::write(&path2, "a\nc\n").unwrap();
+        assert!(diff_files(&mut out, &path1, &path2, &opts).unwrap().is_some());
+        fs::remove_dir_all(&root).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("--- a/"));
+    }
+
+    #[test]
+    fn test_function_context() {
+        let source = "\
+use std::io;
+
+pub fn foo() {
+    let a = 1;
+    let b = 2;
+    let c = 3;
+    println!(\"{}\", a + b + c);
+}
+
+impl Bar {
+    fn baz(&self) {
+        let x = 1;
+        let y = 2;
+        let z = 3;
+        println!(\"{}\", x + y + z);
+    }
+}
+";
+        let lines1: Vec<String> = source.lines().map(|line| line.to_string()).collect();
+        let mut lines2 = lines1.clone();
+        lines2[6] = String::from("    println!(\"{}\", a * b * c);");
+        lines2[15] = String::from("        println!(\"{}\", x * y * z);");
+        let pattern = Regex::new(DEFAULT_FUNCTION_LINE).unwrap();
+        assert_eq!(find_context_label(&lines1, 6, &pattern), Some("pub fn foo() {"));
+        assert_eq!(find_context_label(&lines1, 2, &pattern), None);
+
+        let diff = unified_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 1, Some(&pattern));
+        let headers: Vec<&str> = diff.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, ["@@ -6,3 +6,3 @@ pub fn foo() {", "@@ -15,3 +15,3 @@ impl Bar {"]);
+        let diff = unified_diff(&ops(&lines1, &lines2), &[], &lines1, &lines2, 1, None);
+        assert!(diff.starts_with("@@ -6,3 +6,3 @@\n"));
+    }
+
+    #[test]
+    fn test_brief() {
+        let root = env::temp_dir().join(format!("rdiff-test-brief-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let (one, same, other, spaced) = (root.join("one"), root.join("same"), root.join("other"), root.join("spaced"));
+        fs::write(&one, "a\nb\nc\n").unwrap();
+        fs::write(&same, "a\nb\nc\n").unwrap();
+        fs::write(&other, "a\nx\nc\n").unwrap();
+        fs::write(&spaced, "a\nb  \nc\n").unwrap();
+
+        let status = |path1: &Path, path2: &Path, opts: &DiffOptions| {
+            let stats = diff_files(&mut Vec::new(), path1, path2, opts).unwrap();
+            brief_status(&stats.into_iter().map(|stats| (String::new(), stats)).collect::<Vec<_>>())
+        };
+        let mut opts = DiffOptions { format: Format::Brief, ..DiffOptions::default() };
+        let statuses = [status(&one, &same, &opts), status(&one, &other, &opts), status(&one, &spaced, &opts)];
+        opts.whitespace = WhitespaceMode::IgnoreAll;
+        let ignoring_space = status(&one, &spaced, &opts);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(statuses, [0, 1, 1]);
+        assert_eq!(ignoring_space, 0);
+    }
 }
@@ -0,0 +1,137 @@
+// Three-way merge, built on the LCS diff
+use {edit_script, lcs, DiffOp};
+
+/// A line of a merge that had conflicts: either a line both sides agree on, or a region that the
+/// two sides changed in different ways.
+#[derive(Debug, PartialEq)]
+pub enum MergeLine {
+    Line(String),
+    Conflict { ours: Vec<String>, theirs: Vec<String> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MergeResult {
+    Clean(Vec<String>),
+    Conflicts(Vec<MergeLine>),
+}
+
+impl MergeResult {
+    /// Returns the merged file, with conflicts marked the way git marks them.
+    pub fn lines(&self) -> Vec<String> {
+        let merged = match self {
+            MergeResult::Clean(lines) => return lines.clone(),
+            MergeResult::Conflicts(merged) => merged,
+        };
+        let mut lines = Vec::new();
+        for line in merged {
+            match line {
+                MergeLine::Line(line) => lines.push(line.clone()),
+                MergeLine::Conflict { ours, theirs } => {
+                    lines.push(String::from("<<<<<<< ours"));
+                    lines.extend(ours.iter().cloned());
+                    lines.push(String::from("======="));
+                    lines.extend(theirs.iter().cloned());
+                    lines.push(String::from(">>>>>>> theirs"));
+                }
+            }
+        }
+        lines
+    }
+}
+
+/// For each line of base, returns the index of the same line in other, if diffing them kept it.
+fn matches(base: &[String], other: &[String]) -> Vec<Option<usize>> {
+    let mut matched = vec![None; base.len()];
+    for edit in edit_script(&lcs(base, other), base, other) {
+        if let DiffOp::Equal(i, j) = edit {
+            matched[i] = Some(j);
+        }
+    }
+    matched
+}
+
+/// Merges the changes that ours and theirs each made to base. Base lines that both sides kept
+/// divide the files into regions; in each region, a change made by only one side is taken, and
+/// different changes made by both sides are a conflict.
+pub fn three_way_merge(base: &[String], ours: &[String], theirs: &[String]) -> MergeResult {
+    let ours_matches = matches(base, ours);
+    let theirs_matches = matches(base, theirs);
+    let mut merged = Vec::new();
+    let mut conflicts = false;
+    let (mut i, mut o, mut t) = (0, 0, 0);
+    loop {
+        // Find the next base line that both sides kept, or the ends of the files if there is none
+        let stable = (i..base.len()).find_map(|k| match (ours_matches[k], theirs_matches[k]) {
+            (Some(o), Some(t)) => Some((k, o, t)),
+            _ => None,
+        });
+        let (k, next_o, next_t) = stable.unwrap_or((base.len(), ours.len(), theirs.len()));
+        let (base_region, ours_region, theirs_region) = (&base[i..k], &ours[o..next_o], &theirs[t..next_t]);
+        if ours_region == base_region || ours_region == theirs_region {
+            merged.extend(theirs_region.iter().map(|line| MergeLine::Line(line.clone())));
+        } else if theirs_region == base_region {
+            merged.extend(ours_region.iter().map(|line| MergeLine::Line(line.clone())));
+        } else {
+            conflicts = true;
+            merged.push(MergeLine::Conflict { ours: ours_region.to_vec(), theirs: theirs_region.to_vec() });
+        }
+        if stable.is_none() {
+            break;
+        }
+        merged.push(MergeLine::Line(base[k].clone()));
+        i = k + 1;
+        o = next_o + 1;
+        t = next_t + 1;
+    }
+    if conflicts {
+        return MergeResult::Conflicts(merged);
+    }
+    MergeResult::Clean(
+        merged
+            .into_iter()
+            .map(|line| match line {
+                MergeLine::Line(line) => line,
+                MergeLine::Conflict { .. } => unreachable!(),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_clean_merge() {
+        let base = lines("a b c d e");
+        let ours = lines("a B c d e");
+        let theirs = lines("a b c d e f");
+        assert_eq!(three_way_merge(&base, &ours, &theirs), MergeResult::Clean(lines("a B c d e f")));
+        // Both sides making the same change isn't a conflict
+        assert_eq!(three_way_merge(&base, &ours, &ours), MergeResult::Clean(ours.clone()));
+        assert_eq!(three_way_merge(&base, &base, &base), MergeResult::Clean(base.clone()));
+    }
+
+    #[test]
+    fn test_conflict() {
+        let base = lines("a b c d");
+        let ours = lines("a x c d");
+        let theirs = lines("a y c D");
+        let result = three_way_merge(&base, &ours, &theirs);
+        assert_eq!(
+            result,
+            MergeResult::Conflicts(vec![
+                MergeLine::Line(String::from("a")),
+                MergeLine::Conflict { ours: lines("x"), theirs: lines("y") },
+                MergeLine::Line(String::from("c")),
+                MergeLine::Line(String::from("D")),
+            ])
+        );
+        let expected = ["a", "<<<<<<< ours", "x", "=======", "y", ">>>>>>> theirs", "c", "D"];
+        assert_eq!(result.lines(), expected.iter().map(|s| s.to_string()).collect::<Vec<String>>());
+    }
+}
@@ -0,0 +1,181 @@
+// Applying unified diffs, like patch does
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    /// A hunk's context or removed lines aren't in the file being patched. Hunks are numbered
+    /// from 1, and so are lines.
+    HunkMismatch { hunk: usize, line: usize },
+    /// The patch isn't a well-formed unified diff
+    ParseError(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::HunkMismatch { hunk, line } => {
+                write!(f, "hunk {} does not match the file at line {}", hunk, line)
+            }
+            PatchError::ParseError(message) => write!(f, "malformed patch: {}", message),
+        }
+    }
+}
+
+/// One hunk of a unified diff: where it applies, and its lines with their -/+/space prefixes.
+struct Hunk<'a> {
+    old_start: usize,
+    old_len: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Parses a hunk range like "12,3" or "12" (which means one line).
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let len = match parts.next() {
+        Some(len) => len.parse().ok()?,
+        None => 1,
+    };
+    Some((start, len))
+}
+
+/// Parses a hunk header like "@@ -12,3 +12,4 @@", returning both ranges.
+fn parse_header(header: &str) -> Option<((usize, usize), (usize, usize))> {
+    let mut fields = header.split_whitespace();
+    if fields.next()? != "@@" {
+        return None;
+    }
+    let old = parse_range(fields.next()?.strip_prefix('-')?)?;
+    let new = parse_range(fields.next()?.strip_prefix('+')?)?;
+    if fields.next()? != "@@" {
+        return None;
+    }
+    Some((old, new))
+}
+
+fn parse_hunks(patch: &str) -> Result<Vec<Hunk<'_>>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = patch.lines().peekable();
+    // Skip the ---/+++ header (and anything else before the first hunk)
+    while lines.peek().is_some_and(|line| !line.starts_with("@@")) {
+        lines.next();
+    }
+    while let Some(header) = lines.next() {
+        let ((old_start, old_len), (_, new_len)) = parse_header(header)
+            .ok_or_else(|| PatchError::ParseError(format!("bad hunk header \"{}\"", header)))?;
+        // Lines are numbered from 1, so only an empty range can start at line 0
+        if old_start == 0 && old_len > 0 {
+            return Err(PatchError::ParseError(format!("bad hunk header \"{}\"", header)));
+        }
+        let mut hunk = Hunk { old_start, old_len, lines: Vec::new() };
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while old_seen < old_len || new_seen < new_len {
+            let line = lines.next().ok_or_else(|| {
+                PatchError::ParseError(format!("hunk \"{}\" ends early", header))
+            })?;
+            match line.chars().next() {
+                Some('-') => old_seen += 1,
+                Some('+') => new_seen += 1,
+                // Some tools strip the space from empty context lines
+                Some(' ') | None => {
+                    old_seen += 1;
+                    new_seen += 1;
+                }
+                Some('\\') => continue, // "\ No newline at end of file"
+                _ => return Err(PatchError::ParseError(format!("bad hunk line \"{}\"", line))),
+            }
+            hunk.lines.push(line);
+        }
+        if old_seen != old_len || new_seen != new_len {
+            return Err(PatchError::ParseError(format!("hunk \"{}\" has the wrong number of lines", header)));
+        }
+        hunks.push(hunk);
+        while lines.peek().is_some_and(|line| line.starts_with('\\')) {
+            lines.next();
+        }
+    }
+    Ok(hunks)
+}
+
+/// Applies a unified diff to the lines of a file, checking that the lines the patch expects to
+/// see (context and removed lines) are there, and returns the patched lines.
+pub fn apply_patch(original: &[String], patch: &str) -> Result<Vec<String>, PatchError> {
+    let mut patched = Vec::new();
+    let mut pos = 0;
+    for (n, hunk) in parse_hunks(patch)?.iter().enumerate() {
+        // An empty range is given by the line before it
+        let start = if hunk.old_len == 0 { hunk.old_start } else { hunk.old_start - 1 };
+        if start < pos {
+            return Err(PatchError::ParseError(format!("hunk {} overlaps the one before it", n + 1)));
+        }
+        if start > original.len() {
+            return Err(PatchError::HunkMismatch { hunk: n + 1, line: start + 1 });
+        }
+        patched.extend_from_slice(&original[pos..start]);
+        pos = start;
+        for line in &hunk.lines {
+            let (prefix, text) = line.split_at(std::cmp::min(1, line.len()));
+            if prefix == "+" {
+                patched.push(text.to_string());
+                continue;
+            }
+            if original.get(pos).map(String::as_str) != Some(text) {
+                return Err(PatchError::HunkMismatch { hunk: n + 1, line: pos + 1 });
+            }
+            if prefix != "-" {
+                patched.push(text.to_string());
+            }
+            pos += 1;
+        }
+    }
+    patched.extend_from_slice(&original[pos..]);
+    Ok(patched)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {edit_script, lcs, read_file_lines, unified_diff};
+
+    fn make_patch(lines1: &[String], lines2: &[String], context: usize) -> String {
+        let edits = edit_script(&lcs(lines1, lines2), lines1, lines2);
+        format!("--- a/one\n+++ b/two\n{}", unified_diff(&edits, &[], lines1, lines2, context, None))
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let lines1 = read_file_lines(&String::from("handout-a.txt")).unwrap();
+        let lines2 = read_file_lines(&String::from("handout-b.txt")).unwrap();
+        let patch = make_patch(&lines1, &lines2, 3);
+        assert_eq!(apply_patch(&lines1, &patch), Ok(lines2.clone()));
+        assert_eq!(apply_patch(&lines1, ""), Ok(lines1.clone()));
+
+        let lines1: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut lines2 = lines1.clone();
+        lines2[1] = String::from("two");
+        lines2.remove(14);
+        lines2.push(String::from("21"));
+        lines2.insert(0, String::from("0"));
+        for context in 0..4 {
+            let patch = make_patch(&lines1, &lines2, context);
+            assert_eq!(apply_patch(&lines1, &patch), Ok(lines2.clone()));
+        }
+    }
+
+    #[test]
+    fn test_patch_errors() {
+        let lines1: Vec<String> = (1..=5).map(|i| i.to_string()).collect();
+        let mut lines2 = lines1.clone();
+        lines2[3] = String::from("four");
+        let patch = make_patch(&lines1, &lines2, 1);
+        let mut changed = lines1.clone();
+        changed[2] = String::from("three");
+        assert_eq!(apply_patch(&changed, &patch), Err(PatchError::HunkMismatch { hunk: 1, line: 3 }));
+
+        assert!(matches!(apply_patch(&lines1, "@@ -1,2 @@\n"), Err(PatchError::ParseError(_))));
+        assert!(matches!(apply_patch(&lines1, "@@ -1,2 +1,2 @@\n 1\n"), Err(PatchError::ParseError(_))));
+        assert!(matches!(apply_patch(&lines1, "@@ -0,2 +1,2 @@\n 1\n 2\n"), Err(PatchError::ParseError(_))));
+        // Adding lines to the start of a file is given as after line 0
+        assert_eq!(apply_patch(&lines1, "@@ -0,0 +1 @@\n+0\n").unwrap()[..2], ["0", "1"]);
+    }
+}
@@ -2,23 +2,85 @@ use std::{env, io};
 use std::fs::File;
 use std::io::BufRead;
 use std::process;
-//  given an input file, output the number of words, lines, and characters in the file
+//  given input files (or stdin, if there are none or one is -), output the number of words, lines,
+//  and characters (or bytes, with --bytes) in each file, and in all of them if there's more than
+//  one
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Too few arguments.");
-        process::exit(1);
+    let mut filenames: Vec<&str> = vec![];
+    let mut count_characters_in_lines: fn(&[String]) -> usize = count_unicode_chars;
+    let mut characters_label = "characters";
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--bytes" | "-c" => {
+                count_characters_in_lines = count_bytes;
+                characters_label = "bytes";
+            }
+            "--chars" | "-m" => {
+                count_characters_in_lines = count_unicode_chars;
+                characters_label = "characters";
+            }
+            option if option.starts_with('-') && option != "-" => {
+                println!("Unknown option {}.", option);
+                process::exit(1);
+            }
+            filename => filenames.push(filename),
+        }
+    }
+    if filenames.is_empty() {
+        filenames.push("-");
+    }
+    let mut results = vec![];
+    let (mut total_words, mut total_lines, mut total_chars) = (0, 0, 0);
+    for filename in filenames {
+        let lines = read_input_lines(filename).unwrap_or_else(|_| panic!("read from file {} fail", filename));
+        let words = count_words_in_lines(&lines);
+        let chars = count_characters_in_lines(&lines);
+        total_words += words;
+        total_lines += lines.len();
+        total_chars += chars;
+        results.push((words, lines.len(), chars, filename));
+    }
+    if let [(words, lines, chars, _)] = results[..] {
+        println!("words: {}, lines: {}, {}: {}", words, lines, characters_label, chars);
+        return;
+    }
+
+    results.push((total_words, total_lines, total_chars, "total"));
+    // The totals are the biggest numbers, so line everything up with them
+    let width = total_words.max(total_lines).max(total_chars).to_string().len();
+    for (words, lines, chars, name) in results {
+        println!(
+            "words: {:>width$}, lines: {:>width$}, {}: {:>width$} {}",
+            words,
+            lines,
+            characters_label,
+            chars,
+            name,
+            width = width
+        );
+    }
+}
+
+/// Reads the file at the supplied path, or stdin if the path is "-", and returns a vector of
+/// strings.
+fn read_input_lines(filename: &str) -> Result<Vec<String>, io::Error> {
+    if filename == "-" {
+        read_lines(io::BufReader::new(io::stdin()))
+    } else {
+        read_file_lines(filename)
     }
-    let filename = &args[1];
-    let lines = read_file_lines(filename).expect(&*format!("read from file {} fail", filename));
-    println!("words: {}, lines: {}, characters: {}", count_words_in_lines(&lines), lines.len(), count_characters_in_lines(&lines));
 }
 
 /// Reads the file at the supplied path, and returns a vector of strings.
-fn read_file_lines(filename: &String) -> Result<Vec<String>, io::Error> {
-    let file = File::open(filename)?;
+fn read_file_lines(filename: &str) -> Result<Vec<String>, io::Error> {
+    read_lines(io::BufReader::new(File::open(filename)?))
+}
+
+/// Reads everything from reader, and returns a vector of its lines.
+fn read_lines<R: BufRead>(reader: R) -> Result<Vec<String>, io::Error> {
     let mut v = Vec::<String>::new();
-    for line in io::BufReader::new(file).lines() {
+    for line in reader.lines() {
         let line_str = line?;
         v.push(line_str);
     };
@@ -28,13 +90,13 @@ fn read_file_lines(filename: &String) -> Result<Vec<String>, io::Error> {
 fn count_words_in_lines(lines: &Vec<String>) -> usize {
     let mut count = 0;
     for line in lines {
-        let one = count_words_in_line(&line);
+        let one = count_words_in_line(line);
         count += one;
     }
     count
 }
 
-fn count_words_in_line(line: &String) -> usize {
+fn count_words_in_line(line: &str) -> usize {
     let words: Vec<&str> = line.split(" ").collect();
     let mut word_count = 0;
     for w in words.iter() {
@@ -47,7 +109,17 @@ fn count_words_in_line(line: &String) -> usize {
     word_count
 }
 
-fn count_characters_in_lines(lines: &Vec<String>) -> usize {
+/// Counts the characters in the lines, as Unicode scalar values, so "日本" is two.
+fn count_unicode_chars(lines: &[String]) -> usize {
+    let mut count = 0;
+    for line in lines {
+        count += line.chars().count();
+    }
+    count
+}
+
+/// Counts the bytes in the lines' UTF-8, so "日本" is six.
+fn count_bytes(lines: &[String]) -> usize {
     let mut count = 0;
     for line in lines {
         count += line.len();
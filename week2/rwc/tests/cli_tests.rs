@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs rwc with args, feeding it input on stdin, and returns what it printed.
+fn run_rwc(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rwc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not run rwc");
+    child
+        .stdin
+        .take()
+        .expect("Child process somehow missing stdin pipe!")
+        .write_all(input.as_bytes())
+        .expect("Could not write to rwc's stdin");
+    let output = child.wait_with_output().expect("Could not wait for rwc");
+    assert!(output.status.success(), "rwc exited with {}", output.status);
+    String::from_utf8(output.stdout).expect("rwc printed invalid UTF-8")
+}
+
+#[test]
+fn test_stdin() {
+    let input = "the quick brown fox\njumps over\nthe lazy dog\n";
+    assert_eq!(run_rwc(&[], input), "words: 9, lines: 3, characters: 41\n");
+    assert_eq!(run_rwc(&["-"], input), "words: 9, lines: 3, characters: 41\n");
+}
+
+/// Pulls the word counts out of rwc's output for several files, with the name of each file.
+fn word_counts(output: &str) -> Vec<(usize, String)> {
+    output
+        .lines()
+        .map(|line| {
+            let words = line.split(',').next().unwrap().trim_start_matches("words:").trim();
+            let name = line.rsplit(' ').next().unwrap();
+            (words.parse().expect("word count isn't a number"), name.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_multiple_files() {
+    let dir = std::env::temp_dir().join(format!("rwc-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file1 = dir.join("file1.txt");
+    let file2 = dir.join("file2.txt");
+    fs::write(&file1, "one two three\nfour\n").unwrap();
+    fs::write(&file2, "five six\n").unwrap();
+    let (file1, file2) = (file1.to_str().unwrap(), file2.to_str().unwrap());
+
+    let output = run_rwc(&[file1, file2, "-"], "seven eight nine ten\n");
+    let counts = word_counts(&output);
+    let names: Vec<&str> = counts.iter().map(|(_, name)| name.as_str()).collect();
+    assert_eq!(names, [file1, file2, "-", "total"]);
+    let words: Vec<usize> = counts.iter().map(|&(words, _)| words).collect();
+    assert_eq!(words, [4, 2, 4, 10]);
+    assert_eq!(words[3], words[..3].iter().sum::<usize>());
+    // The numbers line up in columns as wide as the biggest one
+    assert!(output.starts_with("words:  4, lines:  2, characters: 17 "));
+    assert!(output.ends_with("words: 10, lines:  4, characters: 45 total\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_bytes_and_chars() {
+    let dir = std::env::temp_dir().join(format!("rwc-unicode-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("japanese.txt");
+    // Each of these characters is three bytes of UTF-8
+    fs::write(&file, "日本語 の 文章\nこんにちは\n").unwrap();
+    let file = file.to_str().unwrap();
+
+    assert_eq!(run_rwc(&[file], ""), "words: 4, lines: 2, characters: 13\n");
+    assert_eq!(run_rwc(&["--chars", file], ""), "words: 4, lines: 2, characters: 13\n");
+    assert_eq!(run_rwc(&["-m", file], ""), "words: 4, lines: 2, characters: 13\n");
+    assert_eq!(run_rwc(&["--bytes", file], ""), "words: 4, lines: 2, bytes: 35\n");
+    assert_eq!(run_rwc(&["-c", file], ""), "words: 4, lines: 2, bytes: 35\n");
+    // ASCII is a byte per character
+    assert_eq!(run_rwc(&["-c"], "plain text\n"), "words: 2, lines: 1, bytes: 10\n");
+    assert_eq!(run_rwc(&["-m"], "plain text\n"), "words: 2, lines: 1, characters: 10\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
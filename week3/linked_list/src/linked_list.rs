@@ -1,7 +1,11 @@
-use std::borrow::BorrowMut;
+use std::cell::{Ref, RefCell};
 use std::fmt;
 use std::fmt::{Debug, Display};
+use std::iter::FromIterator;
+use std::ops::{Index, IndexMut};
 use std::option::Option;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
 
 pub struct LinkedList<T> where T: Clone+PartialEq {
     head: Option<Box<Node<T>>>,
@@ -57,6 +61,350 @@ impl <T: Clone+PartialEq> LinkedList<T> {
         self.size -= 1;
         Some(node.value)
     }
+
+    /// Returns the empty link at the end of the list. Nodes only point forward, so this walks the
+    /// whole list: O(n).
+    fn end(&mut self) -> &mut Option<Box<Node<T>>> {
+        let mut current = &mut self.head;
+        while let Some(node) = current {
+            current = &mut node.next;
+        }
+        current
+    }
+
+    /// Appends a value to the end of the list. Finding the end is O(n).
+    pub fn push_back(&mut self, value: T) {
+        *self.end() = Some(Box::new(Node::new(value, None)));
+        self.size += 1;
+    }
+
+    /// Moves all of other's values onto the end of this list, leaving other empty. The nodes are
+    /// moved rather than copied, but finding the end of this list is O(n).
+    pub fn append(&mut self, other: &mut LinkedList<T>) {
+        *self.end() = other.head.take();
+        self.size += other.size;
+        other.size = 0;
+    }
+
+    /// Removes the value at the end of the list. Like push_back, this is O(n).
+    pub fn pop_back(&mut self) -> Option<T> {
+        let mut current = &mut self.head;
+        while current.as_ref()?.next.is_some() {
+            current = &mut current.as_mut().unwrap().next;
+        }
+        let node = current.take()?;
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    pub fn peek_front(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    pub fn peek_front_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.value)
+    }
+
+    /// Returns the value at the end of the list. Like push_back, this is O(n).
+    pub fn peek_back(&self) -> Option<&T> {
+        let mut node = self.head.as_ref()?;
+        while let Some(next) = &node.next {
+            node = next;
+        }
+        Some(&node.value)
+    }
+
+    /// Like peek_back, but allows the value to be changed in place.
+    pub fn peek_back_mut(&mut self) -> Option<&mut T> {
+        let mut node = self.head.as_mut()?;
+        while node.next.is_some() {
+            node = node.next.as_mut().unwrap();
+        }
+        Some(&mut node.value)
+    }
+
+    /// Returns the node at the given index, which must be in bounds.
+    fn node(&self, index: usize) -> &Node<T> {
+        let mut node = self.head.as_ref().unwrap();
+        for _ in 0..index {
+            node = node.next.as_ref().unwrap();
+        }
+        node
+    }
+
+    /// Returns the node at the given index, which must be in bounds.
+    fn node_mut(&mut self, index: usize) -> &mut Box<Node<T>> {
+        let mut node = self.head.as_mut().unwrap();
+        for _ in 0..index {
+            node = node.next.as_mut().unwrap();
+        }
+        node
+    }
+
+    /// Inserts a value so that it ends up at the given index. Does nothing if the index is past
+    /// the end of the list.
+    pub fn insert(&mut self, index: usize, value: T) {
+        if index == 0 {
+            self.push_front(value);
+        } else if index == self.size {
+            self.push_back(value);
+        } else if index < self.size {
+            let prev = self.node_mut(index - 1);
+            prev.next = Some(Box::new(Node::new(value, prev.next.take())));
+            self.size += 1;
+        }
+    }
+
+    /// Removes the value at the given index, or returns None if there is no such index.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.size {
+            return None;
+        }
+        if index == 0 {
+            return self.pop_front();
+        }
+        let prev = self.node_mut(index - 1);
+        let node = prev.next.take().unwrap();
+        prev.next = node.next;
+        self.size -= 1;
+        Some(node.value)
+    }
+
+    /// Reverses the list in place, by pointing each node back at the one before it.
+    pub fn reverse(&mut self) {
+        let mut prev: Option<Box<Node<T>>> = None;
+        let mut current = self.head.take();
+        while let Some(mut node) = current {
+            let next_node = node.next.take();
+            node.next = prev;
+            prev = Some(node);
+            current = next_node;
+        }
+        self.head = prev;
+    }
+
+    /// Splits the list in two, keeping the first `index` values and returning a list of the rest.
+    /// If there are no more than `index` values, the returned list is empty.
+    pub fn split_at(&mut self, index: usize) -> LinkedList<T> {
+        let mut rest = LinkedList::new();
+        if index >= self.size {
+            return rest;
+        }
+        rest.head = if index == 0 {
+            self.head.take()
+        } else {
+            self.node_mut(index - 1).next.take()
+        };
+        rest.size = self.size - index;
+        self.size = index;
+        rest
+    }
+
+    /// Moves the first n values to the back of the list. Rotating by the size of the list (or a
+    /// multiple of it) leaves the list as it was.
+    pub fn rotate_left(&mut self, n: usize) {
+        if self.is_empty() {
+            return;
+        }
+        let n = n % self.size;
+        if n == 0 {
+            return;
+        }
+        let mut back = self.split_at(n);
+        back.append(self);
+        *self = back;
+    }
+
+    /// Moves the last n values to the front of the list.
+    pub fn rotate_right(&mut self, n: usize) {
+        if self.is_empty() {
+            return;
+        }
+        self.rotate_left(self.size - n % self.size);
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.into_iter().cloned().collect()
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.into_iter().any(|val| val == value)
+    }
+
+    /// Returns the index of the first occurrence of the value, if any.
+    pub fn position(&self, value: &T) -> Option<usize> {
+        self.into_iter().position(|val| val == value)
+    }
+
+    /// Returns the first value for which the predicate holds, if any.
+    pub fn find<F: Fn(&T) -> bool>(&self, predicate: F) -> Option<&T> {
+        self.into_iter().find(|val| predicate(val))
+    }
+
+    /// Removes every value for which the predicate doesn't hold.
+    pub fn retain<F: Fn(&T) -> bool>(&mut self, predicate: F) {
+        let mut cursor = self.cursor_mut();
+        while let Some(value) = cursor.current() {
+            if predicate(value) {
+                cursor.move_next();
+            } else {
+                cursor.remove_current();
+            }
+        }
+    }
+
+    /// Removes values that are equal to the one before them, so that each run of equal values is
+    /// left with just its first.
+    pub fn dedup(&mut self) {
+        let mut node = match self.head.as_mut() {
+            Some(node) => node,
+            None => return,
+        };
+        while let Some(mut next) = node.next.take() {
+            if next.value == node.value {
+                node.next = next.next.take();
+                self.size -= 1;
+            } else {
+                node = node.next.insert(next);
+            }
+        }
+    }
+
+    /// Returns an iterator that removes the values from the list, front to back. The list is left
+    /// empty, even if the iterator is dropped before it is finished.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain { list: self }
+    }
+
+    /// Returns a cursor pointing at the front of the list.
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor { current: self.head.as_deref() }
+    }
+
+    /// Returns a cursor pointing at the front of the list, which can change the list around it.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut { link: Some(&mut self.head), size: &mut self.size }
+    }
+}
+
+impl <T: Clone+PartialEq+Ord> LinkedList<T> {
+    /// Sorts the list with merge sort. The sort is stable: equal values keep their order.
+    pub fn sort(&mut self) {
+        if self.size < 2 {
+            return;
+        }
+        let (mut front, mut back) = LinkedList::split_half(std::mem::take(self));
+        front.sort();
+        back.sort();
+        *self = LinkedList::merge(front, back);
+    }
+
+    fn split_half(mut list: LinkedList<T>) -> (LinkedList<T>, LinkedList<T>) {
+        let back = list.split_at(list.size / 2);
+        (list, back)
+    }
+
+    /// Merges two sorted lists into one, moving their nodes. On ties, a's values go first.
+    fn merge(mut a: LinkedList<T>, mut b: LinkedList<T>) -> LinkedList<T> {
+        let mut merged = LinkedList::new();
+        let size = a.size + b.size;
+        let mut end = &mut merged.head;
+        loop {
+            let source = match (a.peek_front(), b.peek_front()) {
+                (Some(x), Some(y)) if x <= y => &mut a,
+                (Some(_), Some(_)) => &mut b,
+                _ => break,
+            };
+            let mut node = source.head.take().unwrap();
+            source.head = node.next.take();
+            end = &mut end.insert(node).next;
+        }
+        // One of the lists has run out, so the rest of the other goes on the end
+        *end = a.head.take().or_else(|| b.head.take());
+        merged.size = size;
+        merged
+    }
+}
+
+/// Joins a list of lists into one list, in order. The inner lists' nodes are moved, not copied.
+pub fn flatten<T: Clone+PartialEq+Debug>(list: LinkedList<LinkedList<T>>) -> LinkedList<T> {
+    let mut flattened = LinkedList::new();
+    for mut inner in list {
+        flattened.append(&mut inner);
+    }
+    flattened
+}
+
+/// Pairs up the values of two lists, stopping at the end of the shorter one.
+pub fn zip<A: Clone+PartialEq, B: Clone+PartialEq>(a: LinkedList<A>, b: LinkedList<B>) -> LinkedList<(A, B)> {
+    a.into_iter().zip(b).collect()
+}
+
+impl <T: Clone+PartialEq> Extend<T> for LinkedList<T> {
+    /// Adds the values to the end of the list. The end is only found once, rather than once per
+    /// value as with push_back.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut added = 0;
+        let mut end = self.end();
+        for value in iter {
+            let node = end.get_or_insert(Box::new(Node::new(value, None)));
+            end = &mut node.next;
+            added += 1;
+        }
+        self.size += added;
+    }
+}
+
+impl <T: Clone+PartialEq> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = LinkedList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+/// Indexing walks the list from the front, so it is O(n) rather than O(1) as for a Vec. Panics if
+/// the index is out of bounds.
+impl <T: Clone+PartialEq> Index<usize> for LinkedList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        if index >= self.size {
+            panic!("index {} is out of bounds for a list of size {}", index, self.size);
+        }
+        &self.node(index).value
+    }
+}
+
+impl <T: Clone+PartialEq> IndexMut<usize> for LinkedList<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        if index >= self.size {
+            panic!("index {} is out of bounds for a list of size {}", index, self.size);
+        }
+        &mut self.node_mut(index).value
+    }
+}
+
+/// Lists are serialized as sequences (JSON arrays, for instance), front to back.
+#[cfg(feature = "serde")]
+impl <T: Clone+PartialEq+serde::Serialize> serde::Serialize for LinkedList<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl <'de, T: Clone+PartialEq+serde::Deserialize<'de>> serde::Deserialize<'de> for LinkedList<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values: Vec<T> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(values.into_iter().collect())
+    }
+}
+
+impl <T: Clone+PartialEq> Default for LinkedList<T> {
+    fn default() -> Self {
+        LinkedList::new()
+    }
 }
 
 
@@ -64,14 +412,9 @@ impl <T: Clone+PartialEq> fmt::Display for LinkedList<T> where T: Display {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut current: &Option<Box<Node<T>>> = &self.head;
         let mut result = String::new();
-        loop {
-            match current {
-                Some(node) => {
-                    result = format!("{} {}", result, node.value);
-                    current = &node.next;
-                },
-                None => break,
-            }
+        while let Some(node) = current {
+            result = format!("{} {}", result, node.value);
+            current = &node.next;
         }
         write!(f, "{}", result)
     }
@@ -157,4 +500,909 @@ impl <'a, T: Clone+PartialEq> IntoIterator for &'a LinkedList<T> {
             current: &self.head,
         }
     }
-}
\ No newline at end of file
+}
+
+pub struct ListIteratorMut<'a, T> where T: Clone+PartialEq {
+    current: Option<&'a mut Box<Node<T>>>,
+}
+
+impl <'a, T: Clone+PartialEq> Iterator for ListIteratorMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.current.take()?;
+        self.current = node.next.as_mut();
+        Some(&mut node.value)
+    }
+}
+
+impl <'a, T: Clone+PartialEq> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = ListIteratorMut<'a, T>;
+
+    fn into_iter(self) -> ListIteratorMut<'a, T> {
+        ListIteratorMut {
+            current: self.head.as_mut(),
+        }
+    }
+}
+
+pub struct Drain<'a, T> where T: Clone+PartialEq {
+    list: &'a mut LinkedList<T>,
+}
+
+impl <'a, T: Clone+PartialEq> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+}
+
+impl <'a, T: Clone+PartialEq> Drop for Drain<'a, T> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+/// A position in a list. Nodes only point forward, so cursors can only move towards the back. Once
+/// a cursor has moved past the last value, it points at nothing and stays there.
+pub struct Cursor<'a, T> where T: Clone+PartialEq {
+    current: Option<&'a Node<T>>,
+}
+
+impl <'a, T: Clone+PartialEq> Cursor<'a, T> {
+    pub fn move_next(&mut self) {
+        if let Some(node) = self.current {
+            self.current = node.next.as_deref();
+        }
+    }
+
+    pub fn current(&self) -> Option<&'a T> {
+        self.current.map(|node| &node.value)
+    }
+}
+
+/// Like Cursor, but with exclusive access to the list so that it can insert and remove values.
+pub struct CursorMut<'a, T> where T: Clone+PartialEq {
+    /// The link to the current node. This is only None while the cursor is being moved.
+    link: Option<&'a mut Option<Box<Node<T>>>>,
+    size: &'a mut usize,
+}
+
+impl <'a, T: Clone+PartialEq> CursorMut<'a, T> {
+    fn link(&mut self) -> &mut Option<Box<Node<T>>> {
+        self.link.as_mut().unwrap()
+    }
+
+    pub fn move_next(&mut self) {
+        let link = self.link.take().unwrap();
+        self.link = Some(match link {
+            Some(node) => &mut node.next,
+            None => link,
+        });
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.link().as_mut().map(|node| &mut node.value)
+    }
+
+    /// Inserts a value before the current one, leaving the cursor where it is. If the cursor
+    /// points at nothing, the value is added to the end of the list.
+    pub fn insert_before(&mut self, value: T) {
+        let link = self.link.take().unwrap();
+        let next = link.take();
+        let node = link.insert(Box::new(Node::new(value, next)));
+        self.link = Some(&mut node.next);
+        *self.size += 1;
+    }
+
+    /// Inserts a value after the current one, leaving the cursor where it is. If the cursor
+    /// points at nothing, this is the same as insert_before.
+    pub fn insert_after(&mut self, value: T) {
+        match self.link() {
+            Some(node) => {
+                node.next = Some(Box::new(Node::new(value, node.next.take())));
+                *self.size += 1;
+            }
+            None => self.insert_before(value),
+        }
+    }
+
+    /// Removes the current value, moving the cursor on to the next one.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let link = self.link.as_mut().unwrap();
+        let node = link.take()?;
+        **link = node.next;
+        *self.size -= 1;
+        Some(node.value)
+    }
+}
+
+/// An iterator that moves the values out of a list, front to back.
+pub struct IntoIter<T> where T: Clone+PartialEq {
+    list: LinkedList<T>,
+}
+
+impl <T: Clone+PartialEq> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+}
+
+impl <T: Clone+PartialEq> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+impl <T: Clone+PartialEq> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { list: self }
+    }
+}
+
+/// A last in, first out stack, with the top of the stack at the front of the list.
+pub struct Stack<T: Clone+PartialEq>(LinkedList<T>);
+
+impl <T: Clone+PartialEq> Stack<T> {
+    pub fn new() -> Stack<T> {
+        Stack(LinkedList::new())
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.0.push_front(value);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.0.peek_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.get_size()
+    }
+}
+
+impl <T: Clone+PartialEq> Default for Stack<T> {
+    fn default() -> Self {
+        Stack::new()
+    }
+}
+
+impl <T: Clone+PartialEq+Display> fmt::Display for Stack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A first in, first out queue. Values join at the back of the list and leave from the front, so
+/// enqueue is O(n).
+pub struct Queue<T: Clone+PartialEq>(LinkedList<T>);
+
+impl <T: Clone+PartialEq> Queue<T> {
+    pub fn new() -> Queue<T> {
+        Queue(LinkedList::new())
+    }
+
+    pub fn enqueue(&mut self, value: T) {
+        self.0.push_back(value);
+    }
+
+    pub fn dequeue(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    pub fn peek_front(&self) -> Option<&T> {
+        self.0.peek_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.get_size()
+    }
+}
+
+impl <T: Clone+PartialEq> Default for Queue<T> {
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl <T: Clone+PartialEq+Display> fmt::Display for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A list that can be shared between threads. Clones share the same list, and each method locks it
+/// for just as long as the operation takes.
+pub struct SharedLinkedList<T> where T: Clone+PartialEq {
+    list: Arc<Mutex<LinkedList<T>>>,
+}
+
+impl <T: Clone+PartialEq> SharedLinkedList<T> {
+    pub fn new() -> SharedLinkedList<T> {
+        SharedLinkedList { list: Arc::new(Mutex::new(LinkedList::new())) }
+    }
+
+    pub fn push_front(&self, value: T) {
+        self.list.lock().unwrap().push_front(value);
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        self.list.lock().unwrap().pop_front()
+    }
+
+    pub fn push_back(&self, value: T) {
+        self.list.lock().unwrap().push_back(value);
+    }
+
+    pub fn pop_back(&self) -> Option<T> {
+        self.list.lock().unwrap().pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.lock().unwrap().get_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.lock().unwrap().is_empty()
+    }
+
+    /// Returns a copy of the value at the front, since another thread could remove the value
+    /// itself as soon as the lock is released.
+    pub fn peek_front(&self) -> Option<T> {
+        self.list.lock().unwrap().peek_front().cloned()
+    }
+
+    /// Returns a copy of the current contents of the list, front to back.
+    pub fn iter_snapshot(&self) -> Vec<T> {
+        self.list.lock().unwrap().to_vec()
+    }
+}
+
+impl <T: Clone+PartialEq> Clone for SharedLinkedList<T> {
+    fn clone(&self) -> Self {
+        SharedLinkedList { list: Arc::clone(&self.list) }
+    }
+}
+
+impl <T: Clone+PartialEq> Default for SharedLinkedList<T> {
+    fn default() -> Self {
+        SharedLinkedList::new()
+    }
+}
+
+/// A list whose nodes point both ways, so that both ends can be added to and removed from in O(1),
+/// and it can be iterated from either end.
+///
+/// This is the safe version: nodes are shared through Rc<RefCell<..>>, with Weak pointers going
+/// backwards so that there are no reference cycles. The price is reference counting and runtime
+/// borrow checks on every access, and values can't be borrowed for longer than a RefCell borrow,
+/// so peeking returns a Ref and iterating yields clones. Raw prev pointers would avoid all of this,
+/// but at the cost of unsafe code.
+pub struct DoublyLinkedList<T> where T: Clone {
+    head: DLink<T>,
+    tail: DLink<T>,
+    size: usize,
+}
+
+type DLink<T> = Option<Rc<RefCell<DNode<T>>>>;
+
+struct DNode<T> where T: Clone {
+    value: T,
+    next: DLink<T>,
+    prev: Option<Weak<RefCell<DNode<T>>>>,
+}
+
+impl <T: Clone> DNode<T> {
+    fn new(value: T) -> Rc<RefCell<DNode<T>>> {
+        Rc::new(RefCell::new(DNode { value, next: None, prev: None }))
+    }
+
+    /// Returns the value of a node that has been unlinked from its list.
+    fn into_value(node: Rc<RefCell<DNode<T>>>) -> T {
+        match Rc::try_unwrap(node) {
+            Ok(node) => node.into_inner().value,
+            Err(_) => panic!("an unlinked node is still referenced"),
+        }
+    }
+}
+
+impl <T: Clone> DoublyLinkedList<T> {
+    pub fn new() -> DoublyLinkedList<T> {
+        DoublyLinkedList { head: None, tail: None, size: 0 }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_size() == 0
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let node = DNode::new(value);
+        match self.head.take() {
+            Some(old) => {
+                old.borrow_mut().prev = Some(Rc::downgrade(&node));
+                node.borrow_mut().next = Some(old);
+            }
+            None => self.tail = Some(Rc::clone(&node)),
+        }
+        self.head = Some(node);
+        self.size += 1;
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = DNode::new(value);
+        match self.tail.take() {
+            Some(old) => {
+                node.borrow_mut().prev = Some(Rc::downgrade(&old));
+                old.borrow_mut().next = Some(Rc::clone(&node));
+            }
+            None => self.head = Some(Rc::clone(&node)),
+        }
+        self.tail = Some(node);
+        self.size += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let old = self.head.take()?;
+        match old.borrow_mut().next.take() {
+            Some(next) => {
+                next.borrow_mut().prev = None;
+                self.head = Some(next);
+            }
+            None => self.tail = None,
+        }
+        self.size -= 1;
+        Some(DNode::into_value(old))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let old = self.tail.take()?;
+        match old.borrow_mut().prev.take().and_then(|prev| prev.upgrade()) {
+            Some(prev) => {
+                prev.borrow_mut().next = None;
+                self.tail = Some(prev);
+            }
+            None => self.head = None,
+        }
+        self.size -= 1;
+        Some(DNode::into_value(old))
+    }
+
+    pub fn peek_front(&self) -> Option<Ref<'_, T>> {
+        self.head.as_ref().map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn peek_back(&self) -> Option<Ref<'_, T>> {
+        self.tail.as_ref().map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    /// Returns an iterator over copies of the values, which can be run from either end.
+    pub fn iter(&self) -> DoublyLinkedListIterator<'_, T> {
+        DoublyLinkedListIterator {
+            front: self.head.clone(),
+            back: self.tail.clone(),
+            remaining: self.size,
+            _list: std::marker::PhantomData,
+        }
+    }
+}
+
+impl <T: Clone> Default for DoublyLinkedList<T> {
+    fn default() -> Self {
+        DoublyLinkedList::new()
+    }
+}
+
+impl <T: Clone> Drop for DoublyLinkedList<T> {
+    fn drop(&mut self) {
+        // Dropping the nodes one by one avoids recursing down a long chain of next pointers
+        while self.pop_front().is_some() {}
+    }
+}
+
+pub struct DoublyLinkedListIterator<'a, T> where T: Clone {
+    front: DLink<T>,
+    back: DLink<T>,
+    /// How many values are left between front and back, so the two ends know when they meet
+    remaining: usize,
+    /// The list can't be changed while this is iterating over it
+    _list: std::marker::PhantomData<&'a DoublyLinkedList<T>>,
+}
+
+impl <'a, T: Clone> Iterator for DoublyLinkedListIterator<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.front.take()?;
+        let node = node.borrow();
+        self.front = node.next.clone();
+        self.remaining -= 1;
+        Some(node.value.clone())
+    }
+}
+
+impl <'a, T: Clone> DoubleEndedIterator for DoublyLinkedListIterator<'a, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.back.take()?;
+        let node = node.borrow();
+        self.back = node.prev.as_ref().and_then(|prev| prev.upgrade());
+        self.remaining -= 1;
+        Some(node.value.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_push_pop_back() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        for i in 1..4 {
+            list.push_back(i);
+        }
+        list.push_front(0);
+        assert_eq!(list.to_string(), " 0 1 2 3");
+        assert_eq!(list.get_size(), 4);
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.to_string(), " 0 1");
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_back(), Some(0));
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        assert_eq!(list.peek_front(), None);
+        assert_eq!(list.peek_back(), None);
+        assert_eq!(list.peek_front_mut(), None);
+        assert_eq!(list.peek_back_mut(), None);
+        for i in 1..4 {
+            list.push_back(i);
+        }
+        assert_eq!(list.peek_front(), Some(&1));
+        assert_eq!(list.peek_back(), Some(&3));
+        *list.peek_front_mut().unwrap() = 10;
+        *list.peek_back_mut().unwrap() *= 10;
+        assert_eq!(list.get_size(), 3);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), Some(30));
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        for i in 1..5 {
+            list.push_back(i);
+        }
+        for val in &mut list {
+            *val *= 2;
+        }
+        let values: Vec<u32> = (&list).into_iter().cloned().collect();
+        assert_eq!(values, vec![2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut list: LinkedList<String> = LinkedList::new();
+        for word in &["a", "b", "c"] {
+            list.push_back(word.to_string());
+        }
+        let values: Vec<String> = list.clone().into_iter().collect();
+        assert_eq!(values, vec!["a", "b", "c"]);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some("a".to_string()));
+        assert_eq!(iter.list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        for i in 1..5 {
+            list.push_back(i);
+        }
+        list.insert(2, 10);
+        assert_eq!(list.to_string(), " 1 2 10 3 4");
+        assert_eq!(list.get_size(), 5);
+        assert_eq!(list.remove(2), Some(10));
+        assert_eq!(list.to_string(), " 1 2 3 4");
+        assert_eq!(list.get_size(), 4);
+
+        list.insert(0, 0);
+        list.insert(5, 5);
+        list.insert(7, 7);
+        assert_eq!(list.to_string(), " 0 1 2 3 4 5");
+        assert_eq!(list.remove(6), None);
+        assert_eq!(list.remove(5), Some(5));
+        assert_eq!(list.remove(0), Some(0));
+        assert_eq!(list.get_size(), 4);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        list.reverse();
+        assert!(list.is_empty());
+        list.push_back(1);
+        list.reverse();
+        assert_eq!(list.to_string(), " 1");
+
+        for i in 2..=5 {
+            list.push_back(i);
+        }
+        list.reverse();
+        assert_eq!(list.get_size(), 5);
+        for i in (1..=5).rev() {
+            assert_eq!(list.pop_front(), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_split_at() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        for i in 1..=5 {
+            list.push_back(i);
+        }
+        let rest = list.split_at(2);
+        assert_eq!(list.to_string(), " 1 2");
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(rest.to_string(), " 3 4 5");
+        assert_eq!(rest.get_size(), 3);
+
+        assert!(list.split_at(2).is_empty());
+        let rest = list.split_at(0);
+        assert!(list.is_empty());
+        assert_eq!(rest.to_string(), " 1 2");
+    }
+
+    #[test]
+    fn test_append() {
+        let mut list: LinkedList<u32> = LinkedList::new();
+        let mut other: LinkedList<u32> = LinkedList::new();
+        for i in 1..=4 {
+            list.push_back(i);
+        }
+        for i in 5..=7 {
+            other.push_back(i);
+        }
+        list.append(&mut other);
+        assert_eq!(list.to_string(), " 1 2 3 4 5 6 7");
+        assert_eq!(list.get_size(), 7);
+        assert!(other.is_empty());
+        assert_eq!(other.peek_front(), None);
+    }
+
+    #[test]
+    fn test_collect_extend() {
+        let mut list: LinkedList<u32> = vec![1, 2, 3].into_iter().collect();
+        assert_eq!(list.to_string(), " 1 2 3");
+        assert_eq!(list.get_size(), 3);
+        list.extend(4..=5);
+        assert_eq!(list.to_string(), " 1 2 3 4 5");
+        assert_eq!(list.get_size(), 5);
+
+        let empty: LinkedList<u32> = LinkedList::new().into_iter().collect();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_search() {
+        let list: LinkedList<u32> = vec![4, 8, 15, 16, 8].into_iter().collect();
+        assert!(list.contains(&15));
+        assert!(!list.contains(&42));
+        assert_eq!(list.position(&8), Some(1));
+        assert_eq!(list.position(&16), Some(3));
+        assert_eq!(list.position(&42), None);
+        assert_eq!(list.find(|val| val % 5 == 0), Some(&15));
+        assert_eq!(list.find(|val| *val > 100), None);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let list: LinkedList<u32> = (1..=3).collect();
+        let mut cursor = list.cursor();
+        assert_eq!(cursor.current(), Some(&1));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&3));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut list: LinkedList<u32> = (1..=6).collect();
+        let mut cursor = list.cursor_mut();
+        while cursor.current().is_some() {
+            cursor.remove_current();
+            cursor.move_next();
+        }
+        assert_eq!(list.to_string(), " 2 4 6");
+        assert_eq!(list.get_size(), 3);
+
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        cursor.insert_before(3);
+        cursor.insert_after(5);
+        *cursor.current().unwrap() *= 10;
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.insert_after(7);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(list.to_string(), " 2 3 40 5 6 7");
+        assert_eq!(list.get_size(), 6);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut list: LinkedList<u32> = vec![5, 3, 9, 1, 3, 7, 2].into_iter().collect();
+        list.sort();
+        assert_eq!(list.to_string(), " 1 2 3 3 5 7 9");
+        assert_eq!(list.get_size(), 7);
+
+        let mut words: LinkedList<&str> = vec!["pear", "apple", "fig"].into_iter().collect();
+        words.sort();
+        assert_eq!(words.to_string(), " apple fig pear");
+
+        for values in [vec![], vec![1], vec![1, 2, 3, 4], vec![4, 3, 2, 1]] {
+            let mut list: LinkedList<u32> = values.into_iter().collect();
+            let size = list.get_size();
+            list.sort();
+            let sorted: Vec<u32> = list.into_iter().collect();
+            assert_eq!(sorted, (1..=size as u32).collect::<Vec<u32>>());
+        }
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut list: LinkedList<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8].into_iter().collect();
+        list.retain(|val| val % 2 == 0);
+        assert_eq!(list.to_string(), " 2 4 6 8");
+        assert_eq!(list.get_size(), 4);
+        list.retain(|_| false);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut list: LinkedList<u32> = vec![1, 1, 2, 3, 3, 3, 1, 4, 4].into_iter().collect();
+        list.dedup();
+        assert_eq!(list.to_string(), " 1 2 3 1 4");
+        assert_eq!(list.get_size(), 5);
+
+        let mut empty: LinkedList<u32> = LinkedList::new();
+        empty.dedup();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut list: LinkedList<u32> = (1..=6).collect();
+        let drained: Vec<u32> = list.drain().take(3).collect();
+        assert_eq!(drained, vec![1, 2, 3]);
+        assert!(list.is_empty());
+        assert_eq!(list.peek_front(), None);
+
+        list.push_back(7);
+        assert_eq!(list.drain().collect::<Vec<u32>>(), vec![7]);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_index() {
+        let mut list: LinkedList<u32> = vec![3, 1, 4, 1, 5].into_iter().collect();
+        assert_eq!(list[0], 3);
+        assert_eq!(list[2], 4);
+        assert_eq!(list[4], 5);
+        list[1] = 10;
+        list[4] += 1;
+        let values: Vec<u32> = (&list).into_iter().cloned().collect();
+        assert_eq!(values, vec![3, 10, 4, 1, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 5 is out of bounds for a list of size 5")]
+    fn test_index_out_of_bounds() {
+        let list: LinkedList<u32> = (1..=5).collect();
+        let _ = list[5];
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut list: LinkedList<u32> = (1..=5).collect();
+        list.rotate_left(2);
+        assert_eq!(list.peek_front(), Some(&3));
+        assert_eq!(list.to_vec(), vec![3, 4, 5, 1, 2]);
+        assert_eq!(list.get_size(), 5);
+        list.rotate_right(2);
+        assert_eq!(list.to_vec(), vec![1, 2, 3, 4, 5]);
+        list.rotate_left(0);
+        list.rotate_right(5);
+        assert_eq!(list.to_vec(), vec![1, 2, 3, 4, 5]);
+        list.rotate_right(6);
+        assert_eq!(list.to_vec(), vec![5, 1, 2, 3, 4]);
+
+        let mut empty: LinkedList<u32> = LinkedList::new();
+        empty.rotate_left(3);
+        empty.rotate_right(3);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let lists: LinkedList<LinkedList<u32>> = (0..3)
+            .map(|i| (i * 3 + 1..=i * 3 + 3).collect())
+            .collect();
+        let list = flatten(lists);
+        assert_eq!(list.to_vec(), (1..=9).collect::<Vec<u32>>());
+        assert_eq!(list.get_size(), 9);
+    }
+
+    #[test]
+    fn test_zip() {
+        let numbers: LinkedList<u32> = (1..=5).collect();
+        let words: LinkedList<&str> = vec!["one", "two", "three"].into_iter().collect();
+        let pairs = zip(numbers, words);
+        assert_eq!(pairs.to_vec(), vec![(1, "one"), (2, "two"), (3, "three")]);
+        assert_eq!(pairs.get_size(), 3);
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let list: LinkedList<u32> = (1..=3).collect();
+        assert_eq!(list.to_vec(), vec![1, 2, 3]);
+        assert!(LinkedList::<u32>::new().to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_stack() {
+        let mut stack: Stack<u32> = Stack::new();
+        for i in 1..=4 {
+            stack.push(i);
+        }
+        assert_eq!(stack.to_string(), " 4 3 2 1");
+        assert_eq!(stack.peek(), Some(&4));
+        assert_eq!(stack.len(), 4);
+        let mut reversed = vec![];
+        while let Some(value) = stack.pop() {
+            reversed.push(value);
+        }
+        assert_eq!(reversed, vec![4, 3, 2, 1]);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn test_queue() {
+        let mut queue: Queue<u32> = Queue::new();
+        for i in 1..=4 {
+            queue.enqueue(i);
+        }
+        assert_eq!(queue.to_string(), " 1 2 3 4");
+        assert_eq!(queue.peek_front(), Some(&1));
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.dequeue(), Some(1));
+        queue.enqueue(5);
+        let mut processed = vec![];
+        while let Some(value) = queue.dequeue() {
+            processed.push(value);
+        }
+        assert_eq!(processed, vec![2, 3, 4, 5]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_shared_list() {
+        let list: SharedLinkedList<u32> = SharedLinkedList::new();
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let list = list.clone();
+                std::thread::spawn(move || {
+                    for j in 0..100 {
+                        list.push_back(i * 100 + j);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(list.len(), 400);
+        let mut values = list.iter_snapshot();
+        values.sort();
+        assert_eq!(values, (0..400).collect::<Vec<u32>>());
+
+        list.push_front(1000);
+        assert_eq!(list.peek_front(), Some(1000));
+        assert_eq!(list.pop_front(), Some(1000));
+        while list.pop_back().is_some() {}
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_doubly_linked_list() {
+        let mut list: DoublyLinkedList<u32> = DoublyLinkedList::new();
+        assert!(list.peek_front().is_none());
+        assert_eq!(list.pop_back(), None);
+        // This would take a very long time if pushing to the back meant walking the list
+        for i in 0..100_000 {
+            list.push_back(i);
+        }
+        for _ in 0..99_997 {
+            list.pop_back();
+        }
+        list.push_front(10);
+        assert_eq!(*list.peek_front().unwrap(), 10);
+        assert_eq!(*list.peek_back().unwrap(), 2);
+        assert_eq!(list.get_size(), 4);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_front(), Some(0));
+        assert!(list.is_empty());
+        assert!(list.peek_back().is_none());
+    }
+
+    #[test]
+    fn test_doubly_linked_list_iter() {
+        let mut list: DoublyLinkedList<u32> = DoublyLinkedList::new();
+        for i in 1..=5 {
+            list.push_back(i);
+        }
+        assert_eq!(list.iter().collect::<Vec<u32>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(list.iter().rev().collect::<Vec<u32>>(), vec![5, 4, 3, 2, 1]);
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(5));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let list: LinkedList<String> = vec!["a".to_string(), "b".to_string()].into_iter().collect();
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(json, r#"["a","b"]"#);
+        let parsed: LinkedList<String> = serde_json::from_str(&json).unwrap();
+        assert!(parsed == list);
+        assert_eq!(parsed.get_size(), 2);
+    }
+}
@@ -13,16 +13,17 @@ fn main() {
     println!("top element: {}", list.pop_front().unwrap());
     println!("{}", list);
     println!("size: {}", list.get_size());
-    println!("{}", list.to_string()); // ToString impl for anything impl Display
+    let string = list.to_string(); // ToString impl for anything impl Display
+    println!("{}", string);
 
     let clone_list = list.clone();
     println!("clone {}", clone_list);
     println!("two list equal: {}", list.eq(&clone_list));
     list.push_front(100);
     println!("origin {}, clone {}", list, clone_list);
-    assert!(true, list.ne(&clone_list));
+    assert!(list.ne(&clone_list));
     let _ = list.pop_front();
-    assert!(true, list.eq(&clone_list));
+    assert!(list.eq(&clone_list));
 
     // If you implement iterator trait:
     for val in &list {
@@ -1,15 +1,48 @@
-use crossbeam_channel;
-use std::{thread, time};
-use std::sync::mpsc::{channel, sync_channel};
-use std::sync::Arc;
+use std::{fmt, panic, thread, time};
+use std::any::Any;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinSet;
 
-fn parallel_map<T, U, F>(mut input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<U>
+/// A flag for telling a parallel map to stop early. Clones share the flag, so one can be kept to
+/// cancel the map that another was passed to.
+#[derive(Clone, Default)]
+struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Maps f over input_vec on num_threads threads. Once token is cancelled, or a result satisfies
+/// stop (which cancels it), the threads stop taking new items, and the items they didn't get to
+/// are left as None. As each result comes in, progress is called with how many there are so far
+/// and how many items there are in all.
+fn parallel_map_until<T, U, F>(
+    input_vec: Vec<T>,
+    num_threads: usize,
+    f: F,
+    stop: fn(&U) -> bool,
+    token: CancellationToken,
+    progress: &dyn Fn(usize, usize),
+) -> Vec<Option<U>>
 where
     F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
     T: Send + 'static,
-    U: Send + 'static + Default,
+    U: Send + 'static,
 {
-    let mut output_vec: Vec<U> = Vec::with_capacity(input_vec.len());
+    let mut output_vec: Vec<Option<U>> = input_vec.iter().map(|_| None).collect();
     let mut senders = vec![];
     let mut receivers = vec![];
     for _ in 0..num_threads {
@@ -19,7 +52,7 @@ where
     }
     for (idx, e) in input_vec.into_iter().enumerate() {
         let sender = senders[idx%num_threads].clone();
-        sender.send((idx, e));
+        sender.send((idx, e)).expect("worker hung up");
     }
     drop(senders);
 
@@ -28,36 +61,522 @@ where
     let f = Arc::new(f);
     for receiver in receivers {
         let f = f.clone();
+        let token = token.clone();
         let result_sender = result_sender.clone();
         let handler = thread::spawn(move || {
-            loop {
-                match receiver.recv() {
-                    Ok((idx, e)) => {
-                        let result = f(e);
-                        result_sender.send((idx, result));
-                    },
-                    _ => break,
+            while let Ok((idx, e)) = receiver.recv() {
+                if token.is_cancelled() {
+                    break;
+                }
+                let result = f(e);
+                if stop(&result) {
+                    token.cancel();
                 }
+                result_sender.send((idx, result)).expect("result receiver hung up");
             }
         });
         handlers.push(handler);
     }
+    drop(result_sender);
+    let total = output_vec.len();
+    for (done, (idx, r)) in result_receiver.iter().enumerate() {
+        output_vec[idx] = Some(r);
+        progress(done + 1, total);
+    }
     for handler in handlers {
-        handler.join();
+        handler.join().expect("worker thread panicked");
+    }
+    output_vec
+}
+
+fn parallel_map<T, U, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<U>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    parallel_map_until(input_vec, num_threads, f, |_| false, CancellationToken::new(), &|_, _| {})
+        .into_iter()
+        .map(|r| r.expect("every item is mapped"))
+        .collect()
+}
+
+/// Like parallel_map, but calls progress with how many items are done and how many there are in
+/// all each time an item is done. It's called on this thread, not the worker threads.
+fn parallel_map_with_progress<T, U, F, P>(input_vec: Vec<T>, num_threads: usize, f: F, progress: P) -> Vec<U>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+    P: Fn(usize, usize) + Send + Sync + 'static,
+{
+    parallel_map_until(input_vec, num_threads, f, |_| false, CancellationToken::new(), &progress)
+        .into_iter()
+        .map(|r| r.expect("every item is mapped"))
+        .collect()
+}
+
+/// Like parallel_map, but stops taking new items once token is cancelled. Items that weren't
+/// mapped by then are None.
+fn parallel_map_cancellable<T, U, F>(
+    input_vec: Vec<T>,
+    num_threads: usize,
+    f: F,
+    token: CancellationToken,
+) -> Vec<Option<U>>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    parallel_map_until(input_vec, num_threads, f, |_| false, token, &|_, _| {})
+}
+
+/// The error for an item that took too long to map.
+#[derive(Debug, PartialEq)]
+struct TimeoutError;
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "timed out")
+    }
+}
+
+/// Like parallel_map, but gives up on any item that takes longer than timeout to map. Each item
+/// is mapped on a thread of its own, which is abandoned if it times out: it keeps running (and
+/// using a CPU) until f returns, since Rust has no way to kill a thread.
+fn parallel_map_with_timeout<T, U, F>(
+    input_vec: Vec<T>,
+    num_threads: usize,
+    timeout: time::Duration,
+    f: F,
+) -> Vec<Result<U, TimeoutError>>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    parallel_map(input_vec, num_threads, move |e| {
+        let (result_sender, result_receiver) = channel();
+        thread::spawn(move || {
+            // If this times out, nobody is listening any more
+            let _ = result_sender.send(f(e));
+        });
+        match result_receiver.recv_timeout(timeout) {
+            Ok(result) => Ok(result),
+            Err(RecvTimeoutError::Timeout) => Err(TimeoutError),
+            Err(RecvTimeoutError::Disconnected) => panic!("mapping an item panicked"),
+        }
+    })
+}
+
+/// Gets the message out of a panic's payload, which is a String or a &str if it came from panic!
+/// with a message.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => String::from("panicked without a message"),
+        },
+    }
+}
+
+/// Like parallel_map, but an item that f panics on doesn't take its thread (and the whole map)
+/// down with it: its result is the panic's message instead.
+fn parallel_map_safe<T, U, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<Result<U, String>>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    parallel_map(input_vec, num_threads, move |e| {
+        panic::catch_unwind(panic::AssertUnwindSafe(|| f(e))).map_err(panic_message)
+    })
+}
+
+/// Like parallel_map, for async work (like waiting on the network): runs the futures f returns as
+/// tasks, up to concurrency of them at a time, on the current tokio runtime.
+async fn parallel_map_async<T, U, F, Fut>(input_vec: Vec<T>, concurrency: usize, f: F) -> Vec<U>
+where
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = U> + Send + 'static,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    assert!(concurrency > 0, "at least one task has to run at a time");
+    let mut output_vec: Vec<Option<U>> = input_vec.iter().map(|_| None).collect();
+    let mut tasks = JoinSet::new();
+    for (idx, e) in input_vec.into_iter().enumerate() {
+        if tasks.len() == concurrency {
+            let (idx, result) = tasks.join_next().await.unwrap().expect("task panicked");
+            output_vec[idx] = Some(result);
+        }
+        let future = f(e);
+        tasks.spawn(async move { (idx, future.await) });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        let (idx, result) = joined.expect("task panicked");
+        output_vec[idx] = Some(result);
+    }
+    output_vec.into_iter().map(|r| r.expect("every item is mapped")).collect()
+}
+
+/// Like parallel_map, but only queues up to queue_depth items for the threads at a time, blocking
+/// until they catch up. With a lazy iterator as input, this bounds how many items are in memory
+/// at once.
+fn parallel_map_bounded<I, T, U, F>(input: I, num_threads: usize, queue_depth: usize, f: F) -> Vec<U>
+where
+    I: IntoIterator<Item = T>,
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    let (sender, receiver) = sync_channel(queue_depth);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let (result_sender, result_receiver) = channel();
+    let mut handlers = vec![];
+    for _ in 0..num_threads {
+        let receiver = receiver.clone();
+        let result_sender = result_sender.clone();
+        let handler = thread::spawn(move || loop {
+            // Only hold the lock while waiting for an item, not while working on it
+            let next = receiver.lock().unwrap().recv();
+            match next {
+                Ok((idx, e)) => result_sender.send((idx, f(e))).expect("result receiver hung up"),
+                Err(_) => break,
+            }
+        });
+        handlers.push(handler);
     }
     drop(result_sender);
-    for _ in 0..output_vec.capacity() {
-        output_vec.push(U::default());
-    }
-    for _ in 0..output_vec.capacity() {
-        match result_receiver.recv() {
-            Ok((idx, r)) => {
-                output_vec[idx] = r;
-            },
-            _ => break,
+
+    let mut len = 0;
+    for (idx, e) in input.into_iter().enumerate() {
+        sender.send((idx, e)).expect("workers hung up");
+        len += 1;
+    }
+    drop(sender);
+    for handler in handlers {
+        handler.join().expect("worker thread panicked");
+    }
+    let mut output_vec: Vec<Option<U>> = (0..len).map(|_| None).collect();
+    for (idx, r) in result_receiver {
+        output_vec[idx] = Some(r);
+    }
+    output_vec.into_iter().map(|r| r.expect("every item is mapped")).collect()
+}
+
+/// Runs f on each item on num_threads threads, for its side effects.
+fn parallel_for_each<T, F>(input_vec: Vec<T>, num_threads: usize, f: F)
+where
+    F: FnOnce(T) + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+{
+    parallel_map(input_vec, num_threads, f);
+}
+
+/// Maps each item to any number of values on num_threads threads, and returns all the values,
+/// in the order of the items they came from.
+fn parallel_flat_map<T, U, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<U>
+where
+    F: FnOnce(T) -> Vec<U> + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    // parallel_map keeps the results in order, so they only need joining up
+    parallel_map(input_vec, num_threads, f).into_iter().flatten().collect()
+}
+
+/// Like parallel_map, but hands the items to the threads chunk_size at a time, which saves on
+/// sending things between threads when f is quick.
+fn parallel_map_chunked<T, U, F>(input_vec: Vec<T>, num_threads: usize, chunk_size: usize, f: F) -> Vec<U>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    let chunks = split_every(input_vec, chunk_size);
+    parallel_flat_map(chunks, num_threads, move |chunk: Vec<T>| {
+        let mut results = Vec::with_capacity(chunk.len());
+        for e in chunk {
+            results.push(f(e));
         }
+        results
+    })
+}
+
+/// Keeps the items that satisfy predicate, which is run on num_threads threads, in their order.
+fn parallel_filter<T, F>(input_vec: Vec<T>, num_threads: usize, predicate: F) -> Vec<T>
+where
+    F: FnOnce(T) -> bool + Send + Copy + 'static + Sync,
+    T: Send + 'static + Clone,
+{
+    parallel_map(input_vec, num_threads, move |e: T| {
+        let keep = predicate(e.clone());
+        (e, keep)
+    })
+    .into_iter()
+    .filter_map(|(e, keep)| if keep { Some(e) } else { None })
+    .collect()
+}
+
+/// Splits the items into those that satisfy predicate and those that don't, keeping them in
+/// order. Each of num_threads threads sorts out a run of the items.
+fn parallel_partition<T, F>(input_vec: Vec<T>, num_threads: usize, predicate: F) -> (Vec<T>, Vec<T>)
+where
+    F: Fn(&T) -> bool + Send + Sync + Copy + 'static,
+    T: Send + 'static,
+{
+    let mut handlers = vec![];
+    for chunk in split_chunks(input_vec, num_threads) {
+        handlers.push(thread::spawn(move || chunk.into_iter().partition::<Vec<T>, _>(predicate)));
     }
-    output_vec
+    let (mut matching, mut rest) = (vec![], vec![]);
+    for handler in handlers {
+        let (chunk_matching, chunk_rest) = handler.join().expect("worker thread panicked");
+        matching.extend(chunk_matching);
+        rest.extend(chunk_rest);
+    }
+    (matching, rest)
+}
+
+/// Splits input_vec into num_chunks runs of consecutive items, as evenly as possible.
+fn split_chunks<T>(input_vec: Vec<T>, num_chunks: usize) -> Vec<Vec<T>> {
+    let chunk_size = input_vec.len().div_ceil(num_chunks);
+    split_every(input_vec, chunk_size)
+}
+
+/// Splits input_vec into runs of chunk_size consecutive items (and whatever is left at the end).
+fn split_every<T>(input_vec: Vec<T>, chunk_size: usize) -> Vec<Vec<T>> {
+    assert!(chunk_size > 0 || input_vec.is_empty(), "chunks must hold at least one item");
+    if input_vec.is_empty() {
+        return vec![input_vec];
+    }
+    // Collecting each chunk, rather than using split_off, keeps the chunks from each holding on
+    // to the whole of input_vec's allocation
+    let mut items = input_vec.into_iter().peekable();
+    let mut chunks = vec![];
+    while items.peek().is_some() {
+        chunks.push(items.by_ref().take(chunk_size).collect());
+    }
+    chunks
+}
+
+/// Combines the items with f on num_threads threads, each reducing a run of the items starting
+/// from identity, and then combines what the threads came up with. f must be associative, and
+/// identity must leave items alone, for this to give the same answer as a sequential reduce.
+fn parallel_reduce<T, F>(input_vec: Vec<T>, num_threads: usize, identity: T, f: F) -> T
+where
+    F: Fn(T, T) -> T + Send + Sync + 'static,
+    T: Send + Clone + 'static,
+{
+    let f = Arc::new(f);
+    let partial_f = f.clone();
+    let partials = parallel_fold_chunks(input_vec, num_threads, identity.clone(), move |acc, e| partial_f(acc, e));
+    partials.into_iter().fold(identity, |acc, partial| f(acc, partial))
+}
+
+/// Folds the items with fold_fn on num_threads threads, each folding a run of the items starting
+/// from init, and then merges what the threads came up with using merge_fn. init is used once
+/// per thread, so it should be something merge_fn leaves alone (like 0 for a sum).
+fn parallel_fold<T, B, F>(
+    input_vec: Vec<T>,
+    init: B,
+    num_threads: usize,
+    fold_fn: F,
+    merge_fn: fn(B, B) -> B,
+) -> B
+where
+    F: Fn(B, T) -> B + Send + Sync + 'static,
+    T: Send + 'static,
+    B: Send + Clone + 'static,
+{
+    let mut partials = parallel_fold_chunks(input_vec, num_threads, init, fold_fn).into_iter();
+    let first = partials.next().expect("there is at least one chunk");
+    partials.fold(first, merge_fn)
+}
+
+/// Folds each chunk of the input on its own thread, returning the results in order.
+fn parallel_fold_chunks<T, B, F>(input_vec: Vec<T>, num_threads: usize, init: B, fold_fn: F) -> Vec<B>
+where
+    F: Fn(B, T) -> B + Send + Sync + 'static,
+    T: Send + 'static,
+    B: Send + Clone + 'static,
+{
+    let fold_fn = Arc::new(fold_fn);
+    let mut handlers = vec![];
+    for chunk in split_chunks(input_vec, num_threads) {
+        let fold_fn = fold_fn.clone();
+        let init = init.clone();
+        handlers.push(thread::spawn(move || chunk.into_iter().fold(init, |acc, e| fold_fn(acc, e))));
+    }
+    handlers.into_iter().map(|handler| handler.join().expect("worker thread panicked")).collect()
+}
+
+/// Returns the running totals of the items, combining them with f (so the first total is the
+/// first item, and the last is all of them combined). f must be associative, and identity must
+/// leave items alone. Each of num_threads threads totals up a run of the items, then the totals
+/// of the runs before each run are added onto it, again on a thread per run.
+fn parallel_scan<T, F>(input_vec: Vec<T>, identity: T, f: F, num_threads: usize) -> Vec<T>
+where
+    F: Fn(&T, &T) -> T + Send + Sync + 'static + Copy,
+    T: Send + Clone + 'static,
+{
+    let mut handlers = vec![];
+    for chunk in split_chunks(input_vec, num_threads) {
+        handlers.push(thread::spawn(move || {
+            let mut totals: Vec<T> = Vec::with_capacity(chunk.len());
+            for e in chunk {
+                let total = match totals.last() {
+                    Some(last) => f(last, &e),
+                    None => e,
+                };
+                totals.push(total);
+            }
+            totals
+        }));
+    }
+    let chunks: Vec<Vec<T>> =
+        handlers.into_iter().map(|handler| handler.join().expect("worker thread panicked")).collect();
+
+    // What comes before each chunk is what came before the one before it, plus that one's total
+    let mut offsets = Vec::with_capacity(chunks.len());
+    let mut offset = identity;
+    for chunk in &chunks {
+        offsets.push(offset.clone());
+        if let Some(total) = chunk.last() {
+            offset = f(&offset, total);
+        }
+    }
+
+    let mut handlers = vec![];
+    for (chunk, offset) in chunks.into_iter().zip(offsets) {
+        handlers.push(thread::spawn(move || chunk.iter().map(|e| f(&offset, e)).collect::<Vec<T>>()));
+    }
+    handlers.into_iter().flat_map(|handler| handler.join().expect("worker thread panicked")).collect()
+}
+
+/// Runs shorter than this are sorted without splitting them up any further, since spawning a
+/// thread for them costs more than it saves.
+const SEQUENTIAL_SORT_THRESHOLD: usize = 4096;
+
+/// Sorts the items with a merge sort that sorts each half on a thread of its own, using up to
+/// num_threads threads in all (including this one).
+fn parallel_sort<T: Ord + Send + 'static>(input_vec: Vec<T>, num_threads: usize) -> Vec<T> {
+    parallel_sort_with_threshold(input_vec, num_threads, SEQUENTIAL_SORT_THRESHOLD)
+}
+
+/// Like parallel_sort, but runs of up to sequential_threshold items are sorted on one thread.
+fn parallel_sort_with_threshold<T: Ord + Send + 'static>(
+    mut input_vec: Vec<T>,
+    num_threads: usize,
+    sequential_threshold: usize,
+) -> Vec<T> {
+    if num_threads <= 1 || input_vec.len() <= sequential_threshold {
+        input_vec.sort();
+        return input_vec;
+    }
+    // Half of the threads go to sorting each half, and this thread counts as one of the first's
+    let right = input_vec.split_off(input_vec.len() / 2);
+    let right_threads = num_threads / 2;
+    let (sender, receiver) = channel();
+    let handler = thread::spawn(move || {
+        let sorted = parallel_sort_with_threshold(right, right_threads, sequential_threshold);
+        sender.send(sorted).expect("sorter hung up");
+    });
+    let left = parallel_sort_with_threshold(input_vec, num_threads - right_threads, sequential_threshold);
+    let right = receiver.recv().expect("sorting thread panicked");
+    handler.join().expect("sorting thread panicked");
+    merge(left, right)
+}
+
+/// Merges two sorted runs into one, taking from left first when items are equal.
+fn merge<T: Ord>(left: Vec<T>, right: Vec<T>) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if r < l {
+            merged.push(right.next().unwrap());
+        } else {
+            merged.push(left.next().unwrap());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+/// Like parallel_map, but rather than dealing the items out to the threads in turn, puts them all
+/// in one queue that each thread takes from when it's ready for more. That way, threads that get
+/// quick items don't sit idle while others are stuck with slow ones.
+fn parallel_map_work_stealing<T, U, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<U>
+where
+    F: FnOnce(T) -> U + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    for e in input_vec.into_iter().enumerate() {
+        sender.send(e).expect("receiver is still here");
+    }
+    drop(sender);
+
+    let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+    let mut handlers = vec![];
+    for _ in 0..num_threads {
+        let receiver = receiver.clone();
+        let result_sender = result_sender.clone();
+        handlers.push(thread::spawn(move || {
+            for (idx, e) in receiver {
+                result_sender.send((idx, f(e))).expect("result receiver hung up");
+            }
+        }));
+    }
+    drop(result_sender);
+    for handler in handlers {
+        handler.join().expect("worker thread panicked");
+    }
+    let mut results: Vec<(usize, U)> = result_receiver.into_iter().collect();
+    results.sort_by_key(|&(idx, _)| idx);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Like parallel_map, for a function that can fail: each item's result says whether it did.
+fn parallel_map_result<T, U, E, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Vec<Result<U, E>>
+where
+    F: FnOnce(T) -> Result<U, E> + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+    E: Send + 'static,
+{
+    parallel_map(input_vec, num_threads, f)
+}
+
+/// Like parallel_map_result, but gives up on the first error, returning it (or the first in the
+/// input, if the threads hit more than one before they stopped).
+fn parallel_map_collect<T, U, E, F>(input_vec: Vec<T>, num_threads: usize, f: F) -> Result<Vec<U>, E>
+where
+    F: FnOnce(T) -> Result<U, E> + Send + Copy + 'static + Sync,
+    T: Send + 'static,
+    U: Send + 'static,
+    E: Send + 'static,
+{
+    let results =
+        parallel_map_until(input_vec, num_threads, f, Result::is_err, CancellationToken::new(), &|_, _| {});
+    // If an item was skipped, some other item failed, so this finds an error before it finds None
+    let mut output_vec = Vec::with_capacity(results.len());
+    let mut skipped = false;
+    for r in results {
+        match r {
+            Some(Ok(value)) if !skipped => output_vec.push(value),
+            Some(Ok(_)) => {}
+            Some(Err(err)) => return Err(err),
+            None => skipped = true,
+        }
+    }
+    Ok(output_vec)
 }
 
 fn main() {
@@ -68,4 +587,386 @@ fn main() {
         num * num
     });
     println!("squares: {:?}", squares);
+
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    thread::spawn(move || {
+        thread::sleep(time::Duration::from_millis(250));
+        canceller.cancel();
+    });
+    let slow_squares = parallel_map_cancellable((1..=10).collect(), 2, |num: u64| {
+        thread::sleep(time::Duration::from_millis(100));
+        num * num
+    }, token);
+    println!("squares before cancelling: {:?}", slow_squares);
+
+    let naps = parallel_map_with_timeout(vec![50, 500, 100], 3, time::Duration::from_millis(200), |ms| {
+        thread::sleep(time::Duration::from_millis(ms));
+        ms
+    });
+    println!("naps: {:?}", naps);
+
+    let quotients = parallel_map_safe(vec![4, 2, 0, 1], 2, |num: i32| 100 / num);
+    println!("quotients: {:?}", quotients);
+
+    let runtime = tokio::runtime::Runtime::new().expect("couldn't start a tokio runtime");
+    let waits = runtime.block_on(parallel_map_async(vec![300, 100, 200], 2, |ms: u64| async move {
+        tokio::time::sleep(time::Duration::from_millis(ms)).await;
+        ms
+    }));
+    println!("waits: {:?}", waits);
+
+    let cubes = parallel_map_bounded(1..=10, 3, 2, |num: u64| num * num * num);
+    println!("cubes: {:?}", cubes);
+
+    parallel_for_each(vec!["a", "b", "c"], 2, |s| println!("visiting {}", s));
+
+    let repeated = parallel_flat_map(vec![1, 2, 3], 2, |num: usize| vec![num; num]);
+    println!("repeated: {:?}", repeated);
+
+    let triples = parallel_map_with_progress((1..=5).collect(), 2, |num: u32| num * 3, |done, total| {
+        println!("{}/{} tripled", done, total);
+    });
+    println!("triples: {:?}", triples);
+
+    let doubles = parallel_map_work_stealing((1..=10).collect(), 3, |num: u32| num * 2);
+    println!("doubles: {:?}", doubles);
+
+    let incremented = parallel_map_chunked((0..1000).collect(), 4, 256, |num: u32| num + 1);
+    println!("incremented {} numbers", incremented.len());
+
+    let evens = parallel_filter((1..=20).collect(), 4, |num: u32| num.is_multiple_of(2));
+    println!("evens: {:?}", evens);
+
+    let (small, big) = parallel_partition((1..=10).collect(), 3, |num: &u32| *num <= 5);
+    println!("small: {:?}, big: {:?}", small, big);
+
+    let sum = parallel_reduce((1..=100).collect(), 4, 0, |a: u32, b| a + b);
+    let longest = parallel_fold(vec!["one", "three", "seven"], 0, 2, |len, s: &str| len.max(s.len()), usize::max);
+    println!("sum: {}, longest: {}", sum, longest);
+
+    let sorted = parallel_sort(vec![5, 3, 9, 1, 7], 2);
+    println!("sorted: {:?}", sorted);
+
+    let triangles = parallel_scan((1..=10).collect(), 0, |a: &u32, b| a + b, 4);
+    println!("triangle numbers: {:?}", triangles);
+
+    let half = |num: i32| {
+        if num % 2 == 0 {
+            Ok(num / 2)
+        } else {
+            Err(format!("{} is odd", num))
+        }
+    };
+    println!("halves: {:?}", parallel_map_result(vec![2, 4, 6, 7, 8], 2, half));
+    println!("all halved: {:?}", parallel_map_collect(vec![2, 4, 6, 7, 8], 2, half));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn half(num: i32) -> Result<i32, String> {
+        if num % 2 == 0 {
+            Ok(num / 2)
+        } else {
+            Err(format!("{} is odd", num))
+        }
+    }
+
+    #[test]
+    fn test_parallel_map() {
+        let v: Vec<i32> = (0..20).collect();
+        assert_eq!(parallel_map(v.clone(), 3, |num| num * num), v.iter().map(|num| num * num).collect::<Vec<i32>>());
+        assert_eq!(parallel_map(Vec::<i32>::new(), 3, |num| num), Vec::<i32>::new());
+        // Values without a Default
+        let strings = parallel_map(vec![1, 2, 3], 2, |num| Box::new(num.to_string()));
+        assert_eq!(strings.iter().map(|s| s.as_str()).collect::<Vec<&str>>(), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_parallel_map_with_progress() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let recorder = calls.clone();
+        let output = parallel_map_with_progress((0..40).collect(), 4, |num: i32| num + 1, move |done, total| {
+            recorder.lock().unwrap().push((done, total));
+        });
+        assert_eq!(output, (1..41).collect::<Vec<i32>>());
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 40);
+        assert!(calls.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(calls.iter().all(|&(_, total)| total == 40));
+        assert_eq!(calls.last(), Some(&(40, 40)));
+    }
+
+    #[test]
+    fn test_parallel_map_cancellable() {
+        // One thread, which cancels the map (from another thread) while working on item 4
+        let token = CancellationToken::new();
+        let input: Vec<(usize, CancellationToken)> = (0..10).map(|idx| (idx, token.clone())).collect();
+        let results = parallel_map_cancellable(input, 1, |(idx, token): (usize, CancellationToken)| {
+            if idx == 4 {
+                thread::spawn(move || {
+                    thread::sleep(time::Duration::from_millis(10));
+                    token.cancel();
+                })
+                .join()
+                .unwrap();
+            }
+            idx
+        }, token);
+        assert_eq!(results, [Some(0), Some(1), Some(2), Some(3), Some(4), None, None, None, None, None]);
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let results = parallel_map_cancellable((0..10).collect(), 3, |num: i32| num, token);
+        assert!(results.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_parallel_map_with_timeout() {
+        let timeout = time::Duration::from_millis(200);
+        let results = parallel_map_with_timeout((0..8).collect(), 4, timeout, |idx: u64| {
+            if idx.is_multiple_of(2) {
+                thread::sleep(time::Duration::from_secs(10));
+            }
+            idx
+        });
+        for (idx, result) in results.into_iter().enumerate() {
+            if idx % 2 == 0 {
+                assert_eq!(result, Err(TimeoutError));
+            } else {
+                assert_eq!(result, Ok(idx as u64));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_map_safe() {
+        let results = parallel_map_safe((0..8).collect(), 3, |idx: usize| {
+            if idx == 3 {
+                panic!("panic message");
+            }
+            if idx == 5 {
+                panic!("index {} is bad too", idx);
+            }
+            idx * 2
+        });
+        for (idx, result) in results.into_iter().enumerate() {
+            match idx {
+                3 => assert_eq!(result, Err(String::from("panic message"))),
+                5 => assert_eq!(result, Err(String::from("index 5 is bad too"))),
+                _ => assert_eq!(result, Ok(idx * 2)),
+            }
+        }
+        assert_eq!(panic_message(Box::new(7)), "panicked without a message");
+    }
+
+    #[tokio::test]
+    async fn test_parallel_map_async() {
+        // Later items finish first, but the results still come back in order
+        let start = time::Instant::now();
+        let results = parallel_map_async((0..20).collect(), 5, |idx: u64| async move {
+            tokio::time::sleep(time::Duration::from_millis(200 - idx * 10)).await;
+            idx * idx
+        })
+        .await;
+        assert_eq!(results, (0..20).map(|idx| idx * idx).collect::<Vec<u64>>());
+        // The items take 2.1 seconds one after another
+        assert!(start.elapsed() < time::Duration::from_millis(1000));
+        assert_eq!(parallel_map_async(Vec::<i32>::new(), 5, |num| async move { num }).await, Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_parallel_map_work_stealing() {
+        let v: Vec<i32> = (0..50).collect();
+        assert_eq!(parallel_map_work_stealing(v.clone(), 4, |num| num * num), parallel_map(v, 4, |num| num * num));
+
+        // Dealt out to two threads in turn, the slow items all go to the first thread
+        let nap = |idx: u64| {
+            let ms = if idx.is_multiple_of(2) { 50 } else { 1 };
+            thread::sleep(time::Duration::from_millis(ms));
+            idx
+        };
+        let start = time::Instant::now();
+        let fixed = parallel_map((0..8).collect(), 2, nap);
+        let fixed_time = start.elapsed();
+        let start = time::Instant::now();
+        let stealing = parallel_map_work_stealing((0..8).collect(), 2, nap);
+        let stealing_time = start.elapsed();
+        assert_eq!(fixed, stealing);
+        // About 200ms for the fixed assignment against about 100ms for work stealing
+        assert!(stealing_time * 3 < fixed_time * 2, "{:?} vs {:?}", stealing_time, fixed_time);
+    }
+
+    #[test]
+    fn test_parallel_map_bounded() {
+        use std::sync::atomic::AtomicUsize;
+        static PRODUCED: AtomicUsize = AtomicUsize::new(0);
+        static FINISHED: AtomicUsize = AtomicUsize::new(0);
+        static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+        let (num_threads, queue_depth) = (4, 2);
+        let input = (0..100).inspect(|_| {
+            PRODUCED.fetch_add(1, Ordering::SeqCst);
+        });
+        let output = parallel_map_bounded(input, num_threads, queue_depth, |num: usize| {
+            let in_flight = PRODUCED.load(Ordering::SeqCst) - FINISHED.load(Ordering::SeqCst);
+            MAX_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+            thread::sleep(time::Duration::from_millis(2));
+            FINISHED.fetch_add(1, Ordering::SeqCst);
+            num * 2
+        });
+        assert_eq!(output, (0..100).map(|num| num * 2).collect::<Vec<usize>>());
+        // Items being worked on, items in the queue, and the one waiting to go into the queue
+        assert!(MAX_IN_FLIGHT.load(Ordering::SeqCst) <= num_threads + queue_depth + 1);
+    }
+
+    #[test]
+    fn test_parallel_for_each() {
+        let counter = Arc::new(Mutex::new(0));
+        let input: Vec<Arc<Mutex<usize>>> = (0..500).map(|_| counter.clone()).collect();
+        let len = input.len();
+        parallel_for_each(input, 6, |counter| *counter.lock().unwrap() += 1);
+        assert_eq!(*counter.lock().unwrap(), len);
+    }
+
+    fn prime_factors(mut num: u64) -> Vec<u64> {
+        let mut factors = vec![];
+        let mut d = 2;
+        while num > 1 {
+            while num.is_multiple_of(d) {
+                factors.push(d);
+                num /= d;
+            }
+            d += 1;
+        }
+        factors
+    }
+
+    #[test]
+    fn test_parallel_flat_map() {
+        let v: Vec<u64> = vec![12, 1, 97, 100, 30];
+        assert_eq!(parallel_flat_map(v, 3, prime_factors), [2, 2, 3, 97, 2, 2, 5, 5, 2, 3, 5]);
+        let v: Vec<u64> = (1..300).collect();
+        let expected: Vec<u64> = v.iter().flat_map(|&num| prime_factors(num)).collect();
+        assert_eq!(parallel_flat_map(v, 8, prime_factors), expected);
+    }
+
+    fn is_prime(num: u32) -> bool {
+        num >= 2 && (2..).take_while(|d| d * d <= num).all(|d| !num.is_multiple_of(d))
+    }
+
+    #[test]
+    fn test_parallel_filter() {
+        let v: Vec<u32> = (0..1000).collect();
+        let primes = parallel_filter(v.clone(), 8, is_prime);
+        assert_eq!(primes, v.into_iter().filter(|&num| is_prime(num)).collect::<Vec<u32>>());
+        assert_eq!(primes.len(), 168);
+        assert_eq!(parallel_filter(vec![1, 4, 6], 2, is_prime), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_parallel_partition() {
+        let (evens, odds) = parallel_partition((1..=1000).collect(), 8, |num: &u32| num.is_multiple_of(2));
+        assert_eq!(evens, (1..=1000).filter(|num| num % 2 == 0).collect::<Vec<u32>>());
+        assert_eq!(odds, (1..=1000).filter(|num| num % 2 == 1).collect::<Vec<u32>>());
+        assert!(evens.iter().all(|num| !odds.contains(num)));
+        assert_eq!(evens.len() + odds.len(), 1000);
+        assert_eq!(parallel_partition(vec![], 4, |num: &u32| *num > 0), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_split_chunks() {
+        assert_eq!(split_chunks((0..7).collect(), 3), [vec![0, 1, 2], vec![3, 4, 5], vec![6]]);
+        assert_eq!(split_chunks(vec![1, 2], 4), [vec![1], vec![2]]);
+        assert_eq!(split_chunks(Vec::<i32>::new(), 2), [Vec::<i32>::new()]);
+        assert_eq!(split_every((0..5).collect(), 2), [vec![0, 1], vec![2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn test_parallel_map_chunked() {
+        assert_eq!(parallel_map_chunked((0..10).collect(), 3, 4, |num: i32| num * 10), (0..100).step_by(10).collect::<Vec<i32>>());
+        assert_eq!(parallel_map_chunked(Vec::<i32>::new(), 3, 4, |num| num), Vec::<i32>::new());
+
+        let v: Vec<u64> = (0..1_000_000).collect();
+        let start = time::Instant::now();
+        let unchunked = parallel_map(v.clone(), 4, |num| num + 1);
+        let unchunked_time = start.elapsed();
+        let start = time::Instant::now();
+        let chunked = parallel_map_chunked(v, 4, 256, |num| num + 1);
+        let chunked_time = start.elapsed();
+        assert_eq!(chunked, unchunked);
+        assert!(chunked_time * 2 < unchunked_time, "{:?} vs {:?}", chunked_time, unchunked_time);
+    }
+
+    #[test]
+    fn test_parallel_reduce() {
+        let v: Vec<u64> = (1..=100_000).collect();
+        let sum = parallel_reduce(v.clone(), 8, 0, |a, b| a + b);
+        assert_eq!(sum, v.iter().sum::<u64>());
+        let product = parallel_reduce(v.clone(), 8, 1, u64::wrapping_mul);
+        assert_eq!(product, v.iter().fold(1, |a: u64, &b| a.wrapping_mul(b)));
+        assert_eq!(parallel_reduce(vec![], 3, 0, |a: u64, b| a + b), 0);
+    }
+
+    #[test]
+    fn test_parallel_fold() {
+        let v: Vec<u32> = (1..=100_000).collect();
+        let sum = parallel_fold(v.clone(), 0, 8, |acc: u64, e| acc + e as u64, |a, b| a + b);
+        assert_eq!(sum, v.iter().map(|&e| e as u64).sum::<u64>());
+        let product = parallel_fold(v.clone(), 1, 8, |acc: u64, e| acc.wrapping_mul(e as u64), u64::wrapping_mul);
+        assert_eq!(product, v.iter().fold(1, |a: u64, &b| a.wrapping_mul(b as u64)));
+        let words = parallel_fold(vec!["a", "b", "c", "d"], String::new(), 3, |acc, e| acc + e, |a, b| a + &b);
+        assert_eq!(words, "abcd");
+    }
+
+    #[test]
+    fn test_parallel_scan() {
+        let v: Vec<u64> = (0..1_000_000).collect();
+        let sums = parallel_scan(v, 0, |a, b| a + b, 8);
+        assert_eq!(sums.len(), 1_000_000);
+        for (i, &sum) in sums.iter().enumerate() {
+            let i = i as u64;
+            assert_eq!(sum, i * (i + 1) / 2);
+        }
+        // Concatenating isn't commutative, so this checks the runs are combined in order
+        let words: Vec<String> = ["a", "b", "c", "d", "e"].iter().map(|s| s.to_string()).collect();
+        let words = parallel_scan(words, String::new(), |a, b| a.clone() + b, 3);
+        assert_eq!(words, ["a", "ab", "abc", "abcd", "abcde"]);
+        assert_eq!(parallel_scan(vec![], 0, |a: &u64, b| a + b, 4), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_parallel_sort() {
+        // 7919 has no factors in common with a million, so this shuffles 0..1_000_000
+        let shuffled: Vec<u64> = (0..1_000_000).map(|i| i * 7919 % 1_000_000).collect();
+        let mut expected = shuffled.clone();
+        expected.sort();
+        assert_eq!(parallel_sort(shuffled.clone(), 8), expected);
+        assert_eq!(parallel_sort_with_threshold(shuffled, 3, 1000), expected);
+        assert_eq!(parallel_sort_with_threshold(vec![3, 1, 2, 1], 4, 1), [1, 1, 2, 3]);
+        assert_eq!(parallel_sort(Vec::<u64>::new(), 4), Vec::<u64>::new());
+        assert_eq!(merge(vec![1, 4, 5], vec![2, 3, 6, 7]), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_parallel_map_result() {
+        let results = parallel_map_result((0..10).collect(), 4, half);
+        for (num, result) in results.iter().enumerate() {
+            if num % 2 == 0 {
+                assert_eq!(*result, Ok(num as i32 / 2));
+            } else {
+                assert_eq!(*result, Err(format!("{} is odd", num)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_map_collect() {
+        assert_eq!(parallel_map_collect(vec![0, 2, 4, 6], 3, half), Ok(vec![0, 1, 2, 3]));
+        let err = parallel_map_collect((0..100).collect(), 4, half).unwrap_err();
+        let num: i32 = err.split(' ').next().unwrap().parse().unwrap();
+        assert_eq!(num % 2, 1);
+        assert_eq!(parallel_map_collect(vec![2, 4, 5, 8], 1, half), Err(String::from("5 is odd")));
+    }
 }